@@ -0,0 +1,69 @@
+// Allocations made by a perft of the standard position, with the boards created by each move copied as usual or taken from a `BoardPool`
+
+mod common;
+
+use chess5dlib::prelude::full::*;
+
+#[global_allocator]
+static ALLOCATOR: common::CountingAllocator = common::CountingAllocator;
+
+/// The number of turns searched
+const DEPTH: usize = 3;
+
+/**
+    Returns the number of lines of `depth` turns from the given position, counting the turns made of a single move, as a perft does;
+    the boards of each move are taken from `pool` if there is one, and handed back to it once the move was searched.
+**/
+fn perft(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, depth: usize, pool: &mut Option<BoardPool>) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+    let opponent_boards = get_opponent_boards(game, virtual_boards, info);
+    let mut res = 0;
+    for board in get_own_boards(game, virtual_boards, info) {
+        for mv in probable_moves(game, board, virtual_boards) {
+            let generated = match pool {
+                Some(pool) => mv.generate_vboards_pooled(game, info, virtual_boards, &vec![], pool),
+                None => mv.generate_vboards(game, info, virtual_boards, &vec![]),
+            };
+            let (mut new_info, boards) = match generated {
+                Some(generated) => generated,
+                None => continue,
+            };
+            let merged_vboards: Vec<&Board> = virtual_boards.iter().copied().chain(boards.iter()).collect();
+            // Same checks as `resolve::score_moveset`
+            if is_moveset_legal(game, &merged_vboards, &new_info, boards.iter())
+                && is_moveset_legal(game, &merged_vboards, &new_info, opponent_boards.iter().copied())
+                && all_boards_played(game, &merged_vboards, &new_info)
+            {
+                new_info.present += 1;
+                new_info.active_player = !new_info.active_player;
+                res += perft(game, &merged_vboards, &new_info, depth - 1, pool);
+            }
+            drop(merged_vboards);
+            if let Some(pool) = pool {
+                pool.release_all(boards);
+            }
+        }
+    }
+    res
+}
+
+fn main() {
+    let game = chess5dlib::variant::standard();
+    let virtual_boards: Vec<&Board> = Vec::new();
+    println!("perft({}) of the standard position", DEPTH);
+
+    let without = common::count_allocations("without pool", || perft(&game, &virtual_boards, &game.info, DEPTH, &mut None));
+    let mut pool = Some(BoardPool::new());
+    let with = common::count_allocations("with pool", || perft(&game, &virtual_boards, &game.info, DEPTH, &mut pool));
+    println!(
+        "the pool allocated {} piece arrays; {:.2}x the allocations without it",
+        pool.as_ref().map_or(0, BoardPool::allocations),
+        with as f64 / without as f64
+    );
+
+    common::bench("without pool", || perft(&game, &virtual_boards, &game.info, DEPTH, &mut None));
+    let mut pool = Some(BoardPool::new());
+    common::bench("with pool", || perft(&game, &virtual_boards, &game.info, DEPTH, &mut pool));
+}
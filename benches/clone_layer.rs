@@ -0,0 +1,39 @@
+// Cost of copying the state of a search branch: the top layer of a `RecursiveVirtualBoardset`, compared to a `SimpleVirtualBoardset` and to the whole `Game`
+
+mod common;
+
+use chess5dlib::prelude::full::*;
+
+fn main() {
+    let game = common::load("standard-complex-2.json");
+
+    // Six turns' worth of boards, one layer per turn: each turn advances the last board of every timeline
+    let turns: Vec<Vec<Board>> = (1..=6)
+        .map(|n| {
+            game.timelines
+                .values()
+                .map(|tl| {
+                    let mut board = tl.states[tl.states.len() - 1].clone();
+                    board.t += n;
+                    board
+                })
+                .collect()
+        })
+        .collect();
+    let all_boards: Vec<Board> = turns.iter().flatten().cloned().collect();
+
+    let layer_0 = RecursiveVirtualBoardset::new(&game, turns[0].clone());
+    let layer_1 = layer_0.push(turns[1].clone());
+    let layer_2 = layer_1.push(turns[2].clone());
+    let layer_3 = layer_2.push(turns[3].clone());
+    let layer_4 = layer_3.push(turns[4].clone());
+    let layer_5 = layer_4.push(turns[5].clone());
+    let simple = SimpleVirtualBoardset::new(&game, all_boards.clone());
+    assert_eq!(layer_5.virtual_boards().count(), simple.virtual_boards().count());
+
+    println!("{} boards in the game, {} virtual boards over {} layers", game.timelines.values().map(|tl| tl.states.len()).sum::<usize>(), all_boards.len(), turns.len());
+    let layer = common::bench("RecursiveVirtualBoardset::clone_layer", || layer_5.clone_layer());
+    let flat = common::bench("SimpleVirtualBoardset::clone", || simple.clone());
+    common::bench("Game::clone", || game.clone());
+    println!("clone_layer is {:.1}x faster than copying every virtual board", flat.as_secs_f64() / layer.as_secs_f64());
+}
@@ -0,0 +1,67 @@
+// Helpers shared by the benchmarks, which are plain binaries timed with `std::time` (run them with `cargo bench`)
+// Not every benchmark uses every helper
+#![allow(dead_code)]
+
+use chess5dlib::prelude::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Minimum time spent running each benchmark
+const MIN_DURATION: Duration = Duration::from_millis(500);
+
+/// Loads the game in `tests/games/<name>`
+pub fn load(name: &str) -> Game {
+    let path = format!("{}/tests/games/{}", env!("CARGO_MANIFEST_DIR"), name);
+    try_read_and_parse(&path).unwrap_or_else(|e| panic!("Couldn't load {}: {}", path, e))
+}
+
+/// Runs `f` repeatedly for at least `MIN_DURATION`, prints the average time it took under `label` and returns it
+pub fn bench<T, F: FnMut() -> T>(label: &str, mut f: F) -> Duration {
+    let begin = Instant::now();
+    let mut iterations = 0u32;
+    while iterations == 0 || begin.elapsed() < MIN_DURATION {
+        black_box(f());
+        iterations += 1;
+    }
+    let average = begin.elapsed() / iterations;
+    println!("{:<40} {:>12.3?} ({} iterations)", label, average, iterations);
+    average
+}
+
+/**
+    The system allocator, counting the allocations made and the bytes they requested, for `count_allocations`.
+    A benchmark counting its allocations registers it with `#[global_allocator] static ALLOCATOR: common::CountingAllocator = common::CountingAllocator;`
+**/
+pub struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Runs `f` once and prints the number of allocations it made, the bytes they requested and the number it returned, under `label`; returns the number of allocations
+pub fn count_allocations<F: FnMut() -> usize>(label: &str, mut f: F) -> usize {
+    let (allocations, bytes) = (ALLOCATIONS.load(Ordering::Relaxed), BYTES.load(Ordering::Relaxed));
+    let n = f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{:<40} {:>10} allocations, {:>8.1} MB ({})",
+        label,
+        allocations,
+        (BYTES.load(Ordering::Relaxed) - bytes) as f64 / 1e6,
+        n
+    );
+    allocations
+}
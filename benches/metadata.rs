@@ -0,0 +1,15 @@
+// Cost of reading the metadata of a game (`parse::read_metadata`), compared to parsing the whole game (`parse::try_read_and_parse`)
+
+mod common;
+
+use chess5dlib::parse::{read_metadata, try_read_and_parse};
+
+fn main() {
+    let path = format!("{}/tests/games/standard-complex-2.json", env!("CARGO_MANIFEST_DIR"));
+    let meta = read_metadata(&path).unwrap();
+    println!("{} timelines, {} boards, turn {}", meta.timelines, meta.boards, meta.turn);
+
+    let parse = common::bench("try_read_and_parse", || try_read_and_parse(&path).unwrap());
+    let metadata = common::bench("read_metadata", || read_metadata(&path).unwrap());
+    println!("reading the metadata takes {:.2}x the time of parsing the game", metadata.as_secs_f64() / parse.as_secs_f64());
+}
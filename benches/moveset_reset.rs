@@ -0,0 +1,75 @@
+// Allocations made by `MovesetIter` when going through many positions, creating an iterator for each of them or resetting a single one (`MovesetIter::reset`)
+
+mod common;
+
+use chess5dlib::moves::ranked_moves;
+use chess5dlib::prelude::full::*;
+use chess5dlib::variant;
+
+/// The number of moves considered per board
+const MAX_MOVES: usize = 3;
+/// The number of movesets looked for in each position
+const MOVESETS: usize = 200;
+/// The number of turns played from each starting position
+const TURNS: usize = 12;
+
+#[global_allocator]
+static ALLOCATOR: common::CountingAllocator = common::CountingAllocator;
+
+/// Returns the moves of each of the active player's boards as `ranked_moves` does, with the boards sorted by their coordinates rather than in the order of `Game::timelines`, so that every run goes through the same movesets
+fn sorted_ranked_moves(game: &Game) -> Vec<RankedMoves> {
+    let virtual_boards: Vec<&Board> = Vec::new();
+    let mut res = ranked_moves(game, &game.info, &virtual_boards, |_| true);
+    res.sort_by_key(|moves| moves.first().map(|(mv, _, _, _)| (mv.src.0, mv.src.1)));
+    res
+}
+
+/// Returns `start` along with the positions reached by playing the first legal moveset up to `TURNS` times (see `PrunedMovesetIter`)
+fn positions(start: Game) -> Vec<Game> {
+    let mut res = vec![start];
+    for _ in 0..TURNS {
+        let game = res.last().unwrap();
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let mut iter = PrunedMovesetIter::new(game, &virtual_boards, &game.info, sorted_ranked_moves(game));
+        iter.max_moves_considered = MAX_MOVES;
+        let moveset = match iter.next() {
+            Some(moves) => Moveset(moves),
+            None => break,
+        };
+        let mut game = game.clone();
+        game.advance_turn(&moveset).unwrap();
+        res.push(game);
+    }
+    res
+}
+
+fn main() {
+    let mut games = positions(variant::standard());
+    games.extend(positions(common::load("standard-complex-2.json")));
+    let virtual_boards: Vec<&Board> = Vec::new();
+    let ranked: Vec<Vec<RankedMoves>> = games.iter().map(sorted_ranked_moves).collect();
+    println!("{} positions, {} movesets each", games.len(), MOVESETS);
+
+    let new_per_position = || {
+        games.iter().zip(ranked.iter()).map(|(game, moves)| {
+            let mut iter = MovesetIter::new(game, &virtual_boards, &game.info, moves.clone());
+            iter.max_moves_considered = MAX_MOVES;
+            iter.max_movesets_considered = MOVESETS;
+            iter.count()
+        }).sum::<usize>()
+    };
+    let reset_per_position = || {
+        let mut iter = MovesetIter::new(&games[0], &virtual_boards, &games[0].info, Vec::new());
+        iter.max_moves_considered = MAX_MOVES;
+        iter.max_movesets_considered = MOVESETS;
+        games.iter().zip(ranked.iter()).map(|(game, moves)| {
+            iter.reset(game, &virtual_boards, &game.info, moves.clone());
+            iter.by_ref().count()
+        }).sum::<usize>()
+    };
+
+    common::count_allocations("new iterator per position", new_per_position);
+    common::count_allocations("one iterator, reset per position", reset_per_position);
+    common::bench("new iterator per position", new_per_position);
+    common::bench("one iterator, reset per position", reset_per_position);
+}
@@ -0,0 +1,53 @@
+// Cost of finding the first legal movesets of a position with many boards to play on, combining the moves with `MovesetIter` then filtering them, or with `PrunedMovesetIter`
+
+mod common;
+
+use chess5dlib::prelude::full::*;
+use chess5dlib::moves::ranked_moves;
+use chess5dlib::resolve::score_moveset;
+
+/// The number of moves considered per board
+const MAX_MOVES: usize = 3;
+/// The number of legal movesets looked for
+const MOVESETS: usize = 50;
+
+fn main() {
+    let game = common::load("standard-complex-2.json");
+    let virtual_boards: Vec<&Board> = Vec::new();
+    let info = game.info;
+    let ranked = || ranked_moves(&game, &info, &virtual_boards, |_| true);
+
+    // Every moveset yielded by `MovesetIter` that isn't legal counts as rejected
+    let filtered = || {
+        let mut iter = MovesetIter::new(&game, &virtual_boards, &info, ranked());
+        iter.max_moves_considered = MAX_MOVES;
+        let (mut legal, mut rejected) = (0, 0);
+        for moveset in iter {
+            let opponent_boards = get_opponent_boards(&game, &virtual_boards, &info);
+            if score_moveset(&game, &virtual_boards, &info, opponent_boards.into_iter(), moveset).is_some() {
+                legal += 1;
+                if legal == MOVESETS {
+                    break;
+                }
+            } else {
+                rejected += 1;
+            }
+        }
+        (legal, rejected)
+    };
+    let pruned = || {
+        let mut iter = PrunedMovesetIter::new(&game, &virtual_boards, &info, ranked());
+        iter.max_moves_considered = MAX_MOVES;
+        let legal = iter.by_ref().take(MOVESETS).count();
+        (legal, iter.rejected)
+    };
+
+    let (legal, rejected) = filtered();
+    println!("MovesetIter: {} legal movesets, {} movesets rejected", legal, rejected);
+    let (legal, rejected) = pruned();
+    println!("PrunedMovesetIter: {} legal movesets, {} combinations rejected", legal, rejected);
+
+    let unpruned = common::bench("MovesetIter, then filtered", filtered);
+    let pruned = common::bench("PrunedMovesetIter", pruned);
+    println!("pruning takes {:.2}x the time of filtering", pruned.as_secs_f64() / unpruned.as_secs_f64());
+}
@@ -0,0 +1,39 @@
+// Cost of checking the legality of the moves of each piece, compared to only generating its pseudo-legal moves
+
+mod common;
+
+use chess5dlib::moves::probable_moves_at;
+use chess5dlib::prelude::*;
+
+fn main() {
+    let game = common::load("standard-complex-2.json");
+    let virtual_boards: Vec<&Board> = Vec::new();
+    let squares: Vec<Coords> = get_own_boards(&game, &virtual_boards, &game.info)
+        .into_iter()
+        .flat_map(|board| {
+            (0..board.height).flat_map(move |y| (0..board.width).map(move |x| (board.l, board.t, x, y)))
+        })
+        .filter(|&(l, t, x, y)| {
+            game.get_board(l, t)
+                .and_then(|board| board.get(x, y))
+                .is_some_and(|piece| piece.is_white() == game.info.active_player && !piece.is_blank())
+        })
+        .collect();
+
+    let pseudo = common::bench("pseudo-legal moves (probable_moves_at)", || {
+        squares
+            .iter()
+            .map(|&(l, t, x, y)| probable_moves_at(&game, game.get_board(l, t).unwrap(), &virtual_boards, x, y).len())
+            .sum::<usize>()
+    });
+    let legal = common::bench("legal moves (validate_moves)", || {
+        squares
+            .iter()
+            .map(|&(l, t, x, y)| {
+                let moves = probable_moves_at(&game, game.get_board(l, t).unwrap(), &virtual_boards, x, y);
+                validate_moves(&game, &virtual_boards, &game.info, &moves).into_iter().filter(|valid| *valid).count()
+            })
+            .sum::<usize>()
+    });
+    println!("filtering the moves of {} pieces is {:.1}x slower", squares.len(), legal.as_secs_f64() / pseudo.as_secs_f64());
+}
@@ -0,0 +1,84 @@
+// Opening books
+use crate::{game::*, hash::*, moves::*};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+/// A source of pre-computed movesets, indexed by the position's Zobrist key
+pub trait OpeningBook {
+    /// Returns the preferred moveset for the position whose key is `key`, if that position is known
+    fn lookup(&self, key: ZobristKey) -> Option<Vec<Move>>;
+}
+
+/**
+    A simple opening book, mapping position keys to movesets.
+    It can be saved to and loaded from a JSON file.
+**/
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileBook {
+    pub entries: HashMap<ZobristKey, Vec<Move>>,
+}
+
+impl FileBook {
+    /// Creates a new, empty book
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a book from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        serde_json::from_str(&contents)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the book to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let contents = serde_json::to_string(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        File::create(path)?.write_all(contents.as_bytes())
+    }
+
+    /// Registers `moveset` as the preferred moveset for the current position of `game`
+    pub fn insert(&mut self, game: &Game, moveset: Vec<Move>) {
+        self.entries.insert(zobrist_key(game, &vec![], &game.info), moveset);
+    }
+}
+
+impl OpeningBook for FileBook {
+    fn lookup(&self, key: ZobristKey) -> Option<Vec<Move>> {
+        self.entries.get(&key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn book_move_at_start() {
+        let start = crate::variant::standard();
+        let knight = *probable_moves(&start, start.get_board(0, 0).unwrap(), &vec![])
+            .iter()
+            .find(|mv| mv.dst == (0, 0, 5, 2))
+            .unwrap();
+        let mut book = FileBook::new();
+        book.insert(&start, vec![knight]);
+
+        let path = std::env::temp_dir().join(format!("chess5dlib-book-{}.json", std::process::id()));
+        book.save(&path).unwrap();
+        let book = FileBook::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(book.lookup(zobrist_key(&start, &vec![], &start.info)), Some(vec![knight]));
+        let mut game = start.clone();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        assert_eq!(book.lookup(zobrist_key(&game, &vec![], &game.info)), None);
+    }
+}
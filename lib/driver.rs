@@ -0,0 +1,177 @@
+// A convenience layer to play games turn by turn, for terminal clients and bot harnesses
+
+use crate::{game::*, moves::*, moveset::*};
+use std::fmt;
+
+/// The state of a game from the point of view of the active player, see `GameDriver::state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    /// The active player has at least one legal moveset
+    Ongoing,
+    /// The active player has no legal moveset and is in check: the opponent wins
+    Checkmate,
+    /// The active player has no legal moveset but is not in check
+    Stalemate,
+}
+
+/// Reasons for which `GameDriver::submit_text` may reject a turn
+#[derive(Debug, Clone, PartialEq)]
+pub enum DriverError {
+    /// The notation could not be read, see `AnnotatedMoveset::parse`
+    Notation(NotationError),
+    /// The moves were read but do not make a legal turn
+    Turn(TurnError),
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverError::Notation(e) => write!(f, "Couldn't read turn: {}", e),
+            DriverError::Turn(e) => write!(f, "Illegal turn: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for DriverError {}
+
+impl From<NotationError> for DriverError {
+    fn from(e: NotationError) -> Self {
+        DriverError::Notation(e)
+    }
+}
+
+impl From<TurnError> for DriverError {
+    fn from(e: TurnError) -> Self {
+        DriverError::Turn(e)
+    }
+}
+
+/**
+    Plays a game turn by turn: turns are submitted as movesets (`submit`) or in notation (`submit_text`), are validated and played on the held `Game`.
+    Every position played from is kept, so that turns can be taken back with `undo` and played again with `redo`; submitting a turn forgets the turns that were undone.
+**/
+#[derive(Debug, Clone)]
+pub struct GameDriver {
+    game: Game,
+    /// The positions played from, along with the moveset played from each of them
+    past: Vec<(Game, Moveset)>,
+    /// The turns that were undone, the last one undone first, along with the position that they lead to
+    undone: Vec<(Moveset, Game)>,
+}
+
+impl GameDriver {
+    /// Creates a new driver, playing from `game`'s current position
+    pub fn new(game: Game) -> Self {
+        GameDriver {
+            game,
+            past: Vec::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    /// Returns the current position
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Returns the player whose turn it is (true for white)
+    pub fn active_player(&self) -> bool {
+        self.game.info.active_player
+    }
+
+    /// Returns the number of turns played through the driver so far, not counting the undone ones
+    pub fn turns_played(&self) -> usize {
+        self.past.len()
+    }
+
+    /// Returns the turns played through the driver so far, in order
+    pub fn played(&self) -> impl Iterator<Item = &Moveset> {
+        self.past.iter().map(|(_, ms)| ms)
+    }
+
+    /**
+        Returns the state of the current position: whether the active player can still play, or is checkmated or stalemated.
+        This looks for a legal moveset (see `legal_movesets`), which may take a while if there are none and many boards to play on.
+    **/
+    pub fn state(&self) -> GameState {
+        let virtual_boards: Vec<&Board> = Vec::new();
+        if legal_movesets(&self.game, &self.game.info, &virtual_boards, 0, 0).next().is_some() {
+            GameState::Ongoing
+        } else if is_draw(&self.game, &virtual_boards, &self.game.info) {
+            GameState::Stalemate
+        } else {
+            GameState::Checkmate
+        }
+    }
+
+    /// Plays `moveset` as the active player's turn (see `Game::advance_turn`) and returns the state of the resulting position; the position is left untouched if the moveset is rejected
+    pub fn submit(&mut self, moveset: &Moveset) -> Result<GameState, TurnError> {
+        let previous = self.game.clone();
+        self.game.advance_turn(moveset)?;
+        self.past.push((previous, moveset.clone()));
+        self.undone.clear();
+        Ok(self.state())
+    }
+
+    /// Reads a turn written in notation (see `AnnotatedMoveset::parse`, annotations are discarded) and plays it like `submit`
+    pub fn submit_text(&mut self, raw: &str) -> Result<GameState, DriverError> {
+        let parsed = AnnotatedMoveset::parse(&self.game, &Vec::new(), &self.game.info, raw)?;
+        Ok(self.submit(&Moveset(parsed.moves))?)
+    }
+
+    /// Takes back the last turn played, returning it; returns None if no turn was played through the driver
+    pub fn undo(&mut self) -> Option<Moveset> {
+        let (previous, moveset) = self.past.pop()?;
+        let current = std::mem::replace(&mut self.game, previous);
+        self.undone.push((moveset.clone(), current));
+        Some(moveset)
+    }
+
+    /// Plays again the last turn undone, returning it; returns None if there is none
+    pub fn redo(&mut self) -> Option<Moveset> {
+        let (moveset, next) = self.undone.pop()?;
+        let previous = std::mem::replace(&mut self.game, next);
+        self.past.push((previous, moveset.clone()));
+        Some(moveset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn play_to_checkmate_and_back() {
+        // The fixture has a single board, so that black's king cannot escape to the past
+        let mut driver = GameDriver::new(testing::fixture("mate-in-one.json"));
+        assert_eq!(driver.state(), GameState::Ongoing);
+        assert_eq!(driver.submit_text("(0T1)Ra1a2"), Ok(GameState::Ongoing));
+        assert!(!driver.active_player());
+        let quiet = driver.undo().unwrap();
+
+        // Submitting the mate instead forgets the turn undone
+        assert_eq!(driver.submit_text("(0T1)Ra1a8"), Ok(GameState::Checkmate));
+        assert_eq!(driver.redo(), None);
+        assert_eq!(driver.turns_played(), 1);
+        assert!(!driver.active_player());
+        let mate = driver.played().last().unwrap().clone();
+        assert_ne!(mate, quiet);
+
+        // Black has nothing left to play
+        assert!(matches!(driver.submit_text("(0T1)Kg8h8"), Err(DriverError::Turn(_))));
+        assert_eq!(driver.state(), GameState::Checkmate);
+        assert_eq!(driver.turns_played(), 1);
+
+        // Taking back the mating turn and playing it again
+        assert_eq!(driver.undo(), Some(mate.clone()));
+        assert_eq!(driver.state(), GameState::Ongoing);
+        assert!(driver.active_player());
+        assert_eq!(driver.turns_played(), 0);
+        assert_eq!(driver.undo(), None);
+        assert_eq!(driver.redo(), Some(mate.clone()));
+        assert_eq!(driver.state(), GameState::Checkmate);
+        assert_eq!(driver.played().collect::<Vec<_>>(), vec![&mate]);
+        assert_eq!(driver.redo(), None);
+    }
+}
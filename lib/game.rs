@@ -5,18 +5,76 @@
 use std::fmt;
 use std::convert::TryFrom;
 use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::moves::{get_own_boards, is_travel_allowed, legal_movesets, probable_moves_as, probable_moves_filtered, Move};
+use crate::moveset::{Moveset, TurnError};
+use crate::random::Rng;
+use crate::resolve::{PieceSquareTables, PieceValues, KING_PROTECTION_VALUE, KING_PROTECTION_VALUE_2};
+use crate::variant::VariantRegistry;
+
+/// The coordinates of a square: `(l, t, x, y)`
+pub type Coords = (i32, isize, u8, u8);
 
 /// The main structure, contains the entire state of a game
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Game {
     pub timelines: HashMap<i32, Timeline>,
     pub width: u8,
     pub height: u8,
     pub info: GameInfo,
+    pub travel_rules: TravelRules,
+    /// The pieces that a player may not leave to be taken, for variants with other royalty rules
+    pub check_rule: CheckRule,
+    /// The ranks on which white's and black's pawns promote, for variants where they differ from the last rank of each side; see `Game::promotion_rank`
+    pub promotion_ranks: Option<(u8, u8)>,
+}
+
+/// Restrictions on time travel, for variants with house rules; moves breaking them are not generated
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TravelRules {
+    /// The maximum number of full turns that a piece may travel back in time, None for no limit
+    pub max_time_jump: Option<u32>,
+    /// Whether pieces may travel to boards that were already played on, creating new timelines
+    pub allow_branching: bool,
+}
+
+impl Default for TravelRules {
+    fn default() -> Self {
+        TravelRules {
+            max_time_jump: None,
+            allow_branching: true,
+        }
+    }
+}
+
+/**
+    Which of a player's royal pieces must be safe at the end of their turn, for variants with other royalty rules; consulted by `moves::is_moveset_legal`, and thus when generating and validating movesets.
+    Check detection (eg. `moves::is_draw` and `moves::checkers_of`) still considers every king.
+**/
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum CheckRule {
+    /// None of the player's kings may be left to be taken, as in standard 5D chess
+    #[default]
+    AllKingsSafe,
+    /// At least one of the player's kings must be safe; kings that can be taken are allowed as long as another king of them cannot
+    AnyKingSafe,
+    /// The pieces of the given kind are royal in place of the kings (the color of the given piece is ignored): none of them may be left to be taken
+    SpecificRoyal(Piece),
+}
+
+impl CheckRule {
+    /// Returns whether `piece` is one of `white`'s royal pieces under that rule
+    pub fn is_royal(&self, piece: Piece, white: bool) -> bool {
+        piece.is_own_piece(white)
+            && match self {
+                CheckRule::SpecificRoyal(royal) => piece.as_uppercase() == royal.as_uppercase(),
+                CheckRule::AllKingsSafe | CheckRule::AnyKingSafe => piece.is_king(),
+            }
+    }
 }
 
 /// Information about whose turn it is, where the present is and timeline priority
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct GameInfo {
     pub present: isize,
     pub active_player: bool,
@@ -26,7 +84,7 @@ pub struct GameInfo {
 }
 
 /// Represents an in-game timeline
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Timeline {
     pub index: i32,
     pub states: Vec<Board>,
@@ -36,22 +94,82 @@ pub struct Timeline {
     pub emerges_from: Option<i32>,
 }
 
-/// Represents an in-game board (be it active or not)
-#[derive(Debug, Clone)]
+/**
+    Represents an in-game board (be it active or not).
+    The number of pieces of each player is kept up to date by `set` and `set_unsafe`; `recount_pieces` must be called after writing to `pieces` directly.
+**/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "BoardRaw")]
 pub struct Board {
     pub pieces: Vec<Piece>,
     pub width: u8,
     pub height: u8,
     pub l: i32, // its timeline
     pub t: isize, // its time coordinate
-    pub king_w: Option<(u8, u8)>, // TODO: update if the king moves
+    pub king_w: Option<(u8, u8)>, // The starting square of the king, for castling
     pub king_b: Option<(u8, u8)>,
     pub castle_w: (bool, bool),
     pub castle_b: (bool, bool),
+    #[serde(skip)]
+    piece_counts: (u16, u16), // The number of white and black pieces, see `has_pieces_of`
+}
+
+/// The serialized fields of a `Board`, from which its piece counts are rebuilt when deserializing it
+#[derive(Deserialize)]
+struct BoardRaw {
+    pieces: Vec<Piece>,
+    width: u8,
+    height: u8,
+    l: i32,
+    t: isize,
+    king_w: Option<(u8, u8)>,
+    king_b: Option<(u8, u8)>,
+    castle_w: (bool, bool),
+    castle_b: (bool, bool),
+}
+
+impl From<BoardRaw> for Board {
+    fn from(raw: BoardRaw) -> Self {
+        let mut res = Board {
+            pieces: raw.pieces,
+            width: raw.width,
+            height: raw.height,
+            l: raw.l,
+            t: raw.t,
+            king_w: raw.king_w,
+            king_b: raw.king_b,
+            castle_w: raw.castle_w,
+            castle_b: raw.castle_b,
+            piece_counts: (0, 0),
+        };
+        res.recount_pieces();
+        res
+    }
+}
+
+/**
+    A pool of piece arrays, reused by the boards created during a search instead of allocating new ones; see `Move::generate_vboards_pooled`.
+    Boards that are no longer needed (eg. when the search backtracks) are handed back with `release`. Boards that are never released are simply dropped, as usual.
+**/
+#[derive(Debug, Clone, Default)]
+pub struct BoardPool {
+    free: Vec<Vec<Piece>>,
+    allocations: usize,
+}
+
+/**
+    A game that was saved in the middle of a turn, see `parse::try_parse_partial` and `parse::try_parse_snapshot`.
+    `game` is the position at the beginning of the turn and `boards` are the boards created by the moves already made during the turn, to be used as virtual boards along with `info`.
+**/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialTurn {
+    pub game: Game,
+    pub boards: Vec<Board>,
+    pub info: GameInfo,
 }
 
 /// Represents the contents of a board's square
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Piece {
     Blank,
 
@@ -76,6 +194,84 @@ pub enum Piece {
     PrincessB,
 }
 
+/**
+    What stands on a square, as returned by `Board::tile` and `Game::tile`: unlike the `Option<Piece>` of `Board::get`, it can be matched on directly,
+    without telling `None` and `Some(Piece::Blank)` apart by hand.
+**/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tile {
+    /// The square does not exist: it lies outside of the board, or the board itself does not exist
+    Void,
+    /// The square exists and is empty
+    Empty,
+    /// The square holds a piece, which is never `Piece::Blank`
+    Piece(Piece),
+}
+
+/// An inconsistency within a `Game`, as found by `Game::validate_consistency`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsistencyError {
+    /// The timeline is stored under an index different from its own
+    MisindexedTimeline(i32),
+    /// The timeline's index lies outside of `info.min_timeline..=info.max_timeline`
+    TimelineOutOfRange(i32),
+    /// The timeline contains no board
+    EmptyTimeline(i32),
+    /// The board at `(l, t)` in its timeline has different coordinates
+    MisplacedBoard(i32, isize),
+    /// The board at `(l, t)` does not have the game's size
+    WrongBoardSize(i32, isize),
+    /// The timeline emerges from a board that does not exist
+    MissingParent(i32),
+    /// None of the timelines are active
+    NoActiveTimeline,
+    /// `info.present` differs from the present computed from the timelines, which is given
+    WrongPresent(isize),
+}
+
+/// A reason for which `GameBuilder::build` may refuse to build a game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildError {
+    /// No board was added
+    NoBoards,
+    /// Several boards were added at `(l, t)`
+    DuplicateBoard(i32, isize),
+    /// The timeline has boards before and after `(l, t)`, but none at `(l, t)`
+    MissingBoard(i32, isize),
+    /// The active player is not the one whose turn it is on the boards of the present
+    WrongActivePlayer,
+    /// The assembled game is not consistent, see `Game::validate_consistency`
+    Inconsistent(ConsistencyError),
+}
+
+/**
+    Assembles a `Game` out of individual boards, which makes it easy to write multiverse positions in code rather than as JSON:
+
+    ```
+    let game = GameBuilder::new(8, 8)
+        .add_board(0, 0, first_board)
+        .add_board(0, 1, second_board)
+        .add_board(1, 1, branched_board)
+        .emerges_from(1, 0)
+        .build()?;
+    ```
+
+    The boards of a timeline must follow each other, and the timeline begins with its earliest board;
+    timelines are considered to be starting timelines unless they are given a parent with `emerges_from`.
+    Unless they are given, the present is computed from the timelines (see `Game::validate_consistency`) and the active player is the one whose turn it is on the boards of the present.
+    The castling rights and king positions of the boards are kept as they are, see `populate_castling_rights`.
+**/
+#[derive(Debug, Clone)]
+pub struct GameBuilder {
+    width: u8,
+    height: u8,
+    boards: Vec<Board>,
+    parents: HashMap<i32, i32>,
+    active_player: Option<bool>,
+    present: Option<isize>,
+    even_initial_timelines: bool,
+}
+
 impl Game {
     pub fn new(width: u8, height: u8) -> Self {
         Game {
@@ -88,9 +284,87 @@ impl Game {
                 active_player: true,
                 min_timeline: 0,
                 max_timeline: 0,
+            },
+            travel_rules: TravelRules::default(),
+            check_rule: CheckRule::default(),
+            promotion_ranks: None,
+        }
+    }
+}
+
+impl GameInfo {
+    /**
+        Returns whether or not the `l`-th timeline is active, ie. if it is within the range of timelines that the present is determined from.
+        A player's timelines are active as long as they did not create more than one timeline beyond the opponent's number of created timelines; the initial timelines are always active.
+    **/
+    pub fn is_timeline_active(&self, l: i32) -> bool {
+        if self.even_initial_timelines {
+            // Timelines -1 and 0 stand for -0 and +0, so that black's timelines are shifted by one
+            if l < 0 {
+                l >= -self.max_timeline - 2
+            } else {
+                l <= -self.min_timeline
+            }
+        } else if l < 0 {
+            l >= -self.max_timeline - 1
+        } else {
+            l <= -self.min_timeline + 1
+        }
+    }
+
+    /**
+        Returns the player who created the `l`-th timeline (`true` for white), or None for the initial timelines (`0`, and `-1` for even games, which stand for -0 and +0).
+        White's timelines have positive indices and black's have negative indices; note that this is unrelated to whose turn it is on a timeline's boards, which only depends on their time coordinate (see `Board::active_player`).
+    **/
+    pub fn timeline_owner(&self, l: i32) -> Option<bool> {
+        let lowest_initial = if self.even_initial_timelines { -1 } else { 0 };
+        if l > 0 {
+            Some(true)
+        } else if l < lowest_initial {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /**
+        Returns the indices of the inactive timelines, ie. the timelines created by a player beyond the opponent's number of timelines plus one.
+        Their boards do not need to be played on and do not count towards the present, until the opponent creates new timelines of their own.
+    **/
+    pub fn inactive_timelines(&self) -> Vec<i32> {
+        (self.min_timeline..=self.max_timeline)
+            .filter(|l| !self.is_timeline_active(*l))
+            .collect()
+    }
+
+    /// Returns the number of new timelines that `white` could create one after the other, should the opponent create none, while keeping them active (see `is_timeline_active`)
+    pub fn active_timelines_left(&self, white: bool) -> i32 {
+        let mut info = *self;
+        let mut res = 0;
+        loop {
+            let l = if white { info.max_timeline + 1 } else { info.min_timeline - 1 };
+            if !info.is_timeline_active(l) {
+                return res;
+            }
+            if white {
+                info.max_timeline = l;
+            } else {
+                info.min_timeline = l;
             }
+            res += 1;
         }
     }
+
+    /**
+        Returns the timeline balance, a strategic factor from white's point of view: `active_timelines_left(true) - active_timelines_left(false)`.
+        The player who may create more active timelines is the one who can move the present back and force the opponent to play on more boards, which inactive timelines do not.
+
+        With `w` and `b` the number of timelines created by white and black, it is `max(b + 1 - w, 0) - max(w + 1 - b, 0)`;
+        that is `0` if both created as many timelines, `b - w + 1` if black created more and `b - w - 1` if white created more.
+    **/
+    pub fn strategic_timeline_balance(&self) -> i32 {
+        self.active_timelines_left(true) - self.active_timelines_left(false)
+    }
 }
 
 impl Timeline {
@@ -106,10 +380,13 @@ impl Timeline {
     }
 }
 
-impl From<usize> for Piece {
-    /// Converts 5dchess-notation piece indices into `Piece`s.
-    fn from(raw: usize) -> Self {
-        match raw {
+impl TryFrom<usize> for Piece {
+    /// The index that does not correspond to any piece
+    type Error = usize;
+
+    /// Converts 5dchess-notation piece indices into `Piece`s; fails on unknown indices.
+    fn try_from(raw: usize) -> Result<Self, usize> {
+        Ok(match raw {
             0 => Piece::Blank,
             1 => Piece::PawnW,
             2 => Piece::KnightW,
@@ -129,8 +406,8 @@ impl From<usize> for Piece {
             39 => Piece::UnicornB,
             40 => Piece::DragonB,
             41 => Piece::PrincessB,
-            _ => panic!("Invalid piece: {}", raw),
-        }
+            _ => return Err(raw),
+        })
     }
 }
 
@@ -177,6 +454,63 @@ impl Piece {
         }
     }
 
+    /**
+        Returns the ASCII letter of that `Piece`: white pieces are uppercase (`N`) and black pieces are lowercase (`n`).
+        Princesses are written `S`/`s`, unicorns `U`/`u` and dragons `D`/`d`; `Piece::Blank` is written `.`.
+    **/
+    pub fn to_char(&self) -> char {
+        match &self {
+            Piece::Blank => '.',
+            Piece::PawnW => 'P',
+            Piece::KnightW => 'N',
+            Piece::BishopW => 'B',
+            Piece::RookW => 'R',
+            Piece::QueenW => 'Q',
+            Piece::KingW => 'K',
+            Piece::UnicornW => 'U',
+            Piece::DragonW => 'D',
+            Piece::PrincessW => 'S',
+            Piece::PawnB => 'p',
+            Piece::KnightB => 'n',
+            Piece::BishopB => 'b',
+            Piece::RookB => 'r',
+            Piece::QueenB => 'q',
+            Piece::KingB => 'k',
+            Piece::UnicornB => 'u',
+            Piece::DragonB => 'd',
+            Piece::PrincessB => 's',
+        }
+    }
+
+    /**
+        Returns the Unicode chess symbol of that `Piece`, using the white (outlined) symbols for white pieces (`♘`) and the black (filled) ones for black pieces (`♞`).
+        As Unicode has no symbol for the fairy pieces, princesses are drawn as queens rotated by 90 degrees, unicorns as knights rotated by 45 degrees and dragons as knights rotated by 135 degrees;
+        these come from the "Chess Symbols" block, which many fonts do not cover. `Piece::Blank` is drawn as a space.
+    **/
+    pub fn to_unicode(&self) -> char {
+        match &self {
+            Piece::Blank => ' ',
+            Piece::PawnW => '\u{2659}',
+            Piece::KnightW => '\u{2658}',
+            Piece::BishopW => '\u{2657}',
+            Piece::RookW => '\u{2656}',
+            Piece::QueenW => '\u{2655}',
+            Piece::KingW => '\u{2654}',
+            Piece::UnicornW => '\u{1fa06}',
+            Piece::DragonW => '\u{1fa1b}',
+            Piece::PrincessW => '\u{1fa0a}',
+            Piece::PawnB => '\u{265f}',
+            Piece::KnightB => '\u{265e}',
+            Piece::BishopB => '\u{265d}',
+            Piece::RookB => '\u{265c}',
+            Piece::QueenB => '\u{265b}',
+            Piece::KingB => '\u{265a}',
+            Piece::UnicornB => '\u{1fa07}',
+            Piece::DragonB => '\u{1fa1c}',
+            Piece::PrincessB => '\u{1fa10}',
+        }
+    }
+
     /// Returns whether or not that `Piece` is `Piece::Blank`
     #[inline]
     pub fn is_blank(&self) -> bool {
@@ -355,7 +689,170 @@ impl Piece {
     }
 }
 
+impl From<Option<Piece>> for Tile {
+    fn from(piece: Option<Piece>) -> Self {
+        match piece {
+            None => Tile::Void,
+            Some(Piece::Blank) => Tile::Empty,
+            Some(piece) => Tile::Piece(piece),
+        }
+    }
+}
+
+impl Tile {
+    /// Returns whether or not that square does not exist
+    #[inline]
+    pub fn is_void(&self) -> bool {
+        *self == Tile::Void
+    }
+
+    /// Returns whether or not that square exists and is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        *self == Tile::Empty
+    }
+
+    /// Returns the piece on that square, None if it is empty or does not exist
+    #[inline]
+    pub fn piece(&self) -> Option<Piece> {
+        match self {
+            Tile::Piece(piece) => Some(*piece),
+            _ => None,
+        }
+    }
+
+    /// Returns whether or not that square holds a piece of `white` (true for white)
+    #[inline]
+    pub fn is_piece_of_color(&self, white: bool) -> bool {
+        self.piece().is_some_and(|piece| piece.is_own_piece(white))
+    }
+}
+
 impl Game {
+    /// Returns a copy of the starting position of the variant registered as `name` in the `VariantRegistry`, None if there is no such variant
+    pub fn variant(name: &str) -> Option<Game> {
+        VariantRegistry::get(name)
+    }
+
+    /**
+        Returns the starting position of the standard variant, with `white` to move (true for white), eg. to set up puzzles.
+        With black to move, the starting board is the first board of black's turn (`t = 1`), as if white had passed.
+    **/
+    pub fn standard_with_turn(white: bool) -> Game {
+        let mut game = crate::variant::standard();
+        if !white {
+            game.delay_by_half_turn();
+            game.info.active_player = false;
+        }
+        game
+    }
+
+    /// Moves every board and the present one half-turn later, so that the boards on which it was one player's turn become boards on which it is the opponent's turn
+    pub(crate) fn delay_by_half_turn(&mut self) {
+        for tl in self.timelines.values_mut() {
+            tl.begins_at += 1;
+            for board in tl.states.iter_mut() {
+                board.t += 1;
+            }
+        }
+        self.info.present += 1;
+    }
+
+    /**
+        Returns every legal moveset of the active player in the current position (the starting position, for a freshly created game), eg. to build opening trees.
+        Unlike `legal_movesets`, all of them are generated and collected, which may take a while in positions with many boards to play on.
+    **/
+    pub fn legal_first_movesets(&self) -> Vec<Moveset> {
+        legal_movesets(self, &self.info, &vec![], 0, 0)
+            .map(|(moves, _, _, _)| Moveset::from(moves))
+            .collect()
+    }
+
+    /**
+        Plays `moveset` as the active player's turn: it is validated (see `Moveset::validate`), the boards that it creates are added to the game and `info` is advanced to the opponent's turn.
+        The game is left untouched if the moveset is rejected.
+    **/
+    pub fn advance_turn(&mut self, moveset: &Moveset) -> Result<(), TurnError> {
+        let (_, boards, info) = moveset.validate(self, &Vec::new(), &self.info)?;
+        self.commit_moves(boards);
+        self.info = info;
+        Ok(())
+    }
+
+    /**
+        Plays each of `movesets` in order, as with `advance_turn`, eg. to replay and validate a recorded game.
+        Stops at the first moveset that is rejected, returning its index along with the reason; the movesets before it stay played.
+    **/
+    pub fn apply_movesets(&mut self, movesets: &[Moveset]) -> Result<(), (usize, TurnError)> {
+        for (index, moveset) in movesets.iter().enumerate() {
+            self.advance_turn(moveset).map_err(|e| (index, e))?;
+        }
+        Ok(())
+    }
+
+    /**
+        Turns the game into a `PartialTurn` at the beginning of the active player's turn: no board has been played on yet (`boards` is empty) and `info` is the game's.
+        Its `game`, `virtual_boards()` and `info` can be passed wherever a position in the middle of a turn is expected, and generate the same moves as `self`, `&vec![]` and `&self.info` do.
+    **/
+    pub fn into_partial_turn(self) -> PartialTurn {
+        PartialTurn {
+            info: self.info,
+            game: self,
+            boards: Vec::new(),
+        }
+    }
+
+    /**
+        Returns a copy of the game whose timelines are renumbered, so that games that only differ by the indices of their timelines (eg. puzzles set up at another timeline) become identical.
+        The initial timelines (the earliest ones that do not emerge from another one) keep their order and are shifted so that the highest of them is the timeline `0`;
+        the other timelines are numbered after them, in the order of their former indices and without gaps: `1, 2, ...` above and `-1, -2, ...` below (from `-2` in games with even initial timelines).
+        Each board, `emerges_from`, `info.min_timeline` and `info.max_timeline` follow the new indices, and `info.present` is recomputed from the active timelines.
+    **/
+    pub fn normalize_timelines(&self) -> Game {
+        let mut res = self.clone();
+        let timelines: Vec<(i32, isize, bool)> = self
+            .timelines
+            .values()
+            .map(|tl| (tl.index, tl.begins_at, tl.emerges_from.is_some()))
+            .collect();
+        let (mapping, shift) = match normalized_indices(&timelines) {
+            Some(x) => x,
+            None => return res,
+        };
+
+        res.timelines = self
+            .timelines
+            .iter()
+            .map(|(l, tl)| {
+                let mut tl = tl.clone();
+                tl.index = mapping[l];
+                tl.emerges_from = tl.emerges_from.map(|parent| mapping.get(&parent).copied().unwrap_or(parent + shift));
+                for board in tl.states.iter_mut() {
+                    board.l = tl.index;
+                }
+                (tl.index, tl)
+            })
+            .collect();
+        res.info.min_timeline = *res.timelines.keys().min().unwrap();
+        res.info.max_timeline = *res.timelines.keys().max().unwrap();
+        if let Some(present) = res
+            .timelines
+            .values()
+            .filter(|tl| res.info.is_timeline_active(tl.index))
+            .map(|tl| tl.begins_at + tl.states.len() as isize - 1)
+            .min()
+        {
+            res.info.present = present;
+        }
+        res
+    }
+
+    /// Returns the rank on which `white`'s pawns promote: the one set in `promotion_ranks`, the last rank of that side by default (`height - 1` for white and `0` for black)
+    pub fn promotion_rank(&self, white: bool) -> u8 {
+        let (rank_w, rank_b) = self.promotion_ranks.unwrap_or((self.height - 1, 0));
+        if white { rank_w } else { rank_b }
+    }
+
     /// Returns whether or not there are +0/-0 timelines
     pub fn even_initial_timelines(&self) -> bool {
         self.info.even_initial_timelines
@@ -371,7 +868,7 @@ impl Game {
         self.timelines.get_mut(&l)
     }
 
-    /// Returns the `(l, t)` board, None if not found
+    /// Returns the `(l, t)` board, None if not found; see `moves::get_board` to also look among virtual boards, or `PartialTurn::board_at` among the boards played on during a turn
     pub fn get_board<'a>(&'a self, l: i32, t: isize) -> Option<&'a Board> {
         self.get_timeline(l).map(|tl| tl.get_board(t)).flatten()
     }
@@ -405,11 +902,89 @@ impl Game {
         self.get_timeline(l).map(|tl| tl.get(t, x, y)).flatten()
     }
 
+    /// Returns what stands on the `(l, t, x, y)` square, `Tile::Void` if it does not exist
+    pub fn tile(&self, l: i32, t: isize, x: u8, y: u8) -> Tile {
+        self.get(l, t, x, y).into()
+    }
+
     /// Returns the `(l, t, x, y)` piece, panics if not found
     pub fn get_unsafe<'a>(&'a self, l: i32, t: isize, x: u8, y: u8) -> Piece {
         self.timelines[&l].get_unsafe(t, x, y)
     }
 
+    /**
+        Returns the number of the current full turn, as displayed in-game (the `T` of `(0T1)`): it is based on the present rather than on the number of movesets played,
+        as travelling back in time also moves the present back.
+    **/
+    pub fn full_turn_number(&self) -> u32 {
+        (self.info.present.max(0) / 2) as u32 + 1
+    }
+
+    /// Returns the number of half-turns (plies) before the present, starting at 0 for white's first move; see `full_turn_number`
+    pub fn ply(&self) -> u32 {
+        self.info.present.max(0) as u32
+    }
+
+    /// Returns the smallest and largest `t` coordinates of the boards within the game
+    pub fn time_range(&self) -> (isize, isize) {
+        let min = self.timelines.values().map(|tl| tl.begins_at).min().unwrap_or(0);
+        let max = self
+            .timelines
+            .values()
+            .map(|tl| tl.begins_at + tl.states.len() as isize - 1)
+            .max()
+            .unwrap_or(0);
+        (min, max)
+    }
+
+    /** Converts `(l, t, x, y)` into a flat index, based on the game's dimensions, time range and timeline range (`info.min_timeline..=info.max_timeline`).
+        Returns None if the coordinates lie outside of that range.
+
+        The index is only stable for as long as these ranges don't change; it is meant for dense maps built over a given game state.
+    **/
+    pub fn coords_to_index(&self, coords: Coords) -> Option<u64> {
+        let (l, t, x, y) = coords;
+        let (min_t, max_t) = self.time_range();
+        if l < self.info.min_timeline
+            || l > self.info.max_timeline
+            || t < min_t
+            || t > max_t
+            || x >= self.width
+            || y >= self.height
+        {
+            return None;
+        }
+        let n_t = (max_t - min_t + 1) as u64;
+        let dl = (l as i64 - self.info.min_timeline as i64) as u64;
+        let dt = (t - min_t) as u64;
+
+        Some(((dl * n_t + dt) * self.height as u64 + y as u64) * self.width as u64 + x as u64)
+    }
+
+    /// Converts a flat index back into `(l, t, x, y)`; the inverse of `coords_to_index`. Returns None if the index is out of range.
+    pub fn coords_from_index(&self, index: u64) -> Option<Coords> {
+        let (min_t, max_t) = self.time_range();
+        let width = self.width as u64;
+        let height = self.height as u64;
+        let n_t = (max_t - min_t + 1) as u64;
+        let n_l = (self.info.max_timeline as i64 - self.info.min_timeline as i64 + 1) as u64;
+        if width == 0 || height == 0 || index >= n_l * n_t * height * width {
+            return None;
+        }
+
+        let x = index % width;
+        let y = (index / width) % height;
+        let dt = (index / width / height) % n_t;
+        let dl = index / width / height / n_t;
+
+        Some((
+            (self.info.min_timeline as i64 + dl as i64) as i32,
+            min_t + dt as isize,
+            x as u8,
+            y as u8,
+        ))
+    }
+
     /** Appends a set of boards to the current game structure; currently only supports appending one board to every timeline.
         This is usually used together with `Move::generate_vboards`:
 
@@ -451,6 +1026,317 @@ impl Game {
         }
         self.info.active_player = !self.info.active_player;
     }
+
+    /**
+        Verifies that the game's timelines are consistent with one another, returning the first inconsistency found. It checks that:
+
+        - every timeline is non-empty, indexed by its own index and within `info.min_timeline..=info.max_timeline`
+        - every board has the coordinates and the size of its place in its timeline
+        - every timeline emerging from another one branches off an existing board of that timeline (timelines with no `emerges_from` are considered to be starting timelines)
+        - the present corresponds to the last board of the earliest active timeline
+    **/
+    pub fn validate_consistency(&self) -> Result<(), ConsistencyError> {
+        let mut indices = self.timelines.keys().copied().collect::<Vec<_>>();
+        indices.sort_unstable();
+
+        for l in indices.iter().copied() {
+            let tl = &self.timelines[&l];
+            if tl.index != l {
+                return Err(ConsistencyError::MisindexedTimeline(l));
+            }
+            if l < self.info.min_timeline || l > self.info.max_timeline {
+                return Err(ConsistencyError::TimelineOutOfRange(l));
+            }
+            if tl.states.is_empty() {
+                return Err(ConsistencyError::EmptyTimeline(l));
+            }
+            for (i, board) in tl.states.iter().enumerate() {
+                let t = tl.begins_at + i as isize;
+                if board.l != l || board.t != t {
+                    return Err(ConsistencyError::MisplacedBoard(l, t));
+                }
+                if board.width != self.width
+                    || board.height != self.height
+                    || board.pieces.len() != self.width as usize * self.height as usize
+                {
+                    return Err(ConsistencyError::WrongBoardSize(l, t));
+                }
+            }
+            if let Some(parent) = tl.emerges_from {
+                if self.get_board(parent, tl.begins_at - 1).is_none() {
+                    return Err(ConsistencyError::MissingParent(l));
+                }
+            }
+        }
+
+        let present = indices
+            .iter()
+            .filter(|l| self.info.is_timeline_active(**l))
+            .map(|l| {
+                let tl = &self.timelines[l];
+                tl.begins_at + tl.states.len() as isize - 1
+            })
+            .min()
+            .ok_or(ConsistencyError::NoActiveTimeline)?;
+        if present != self.info.present {
+            return Err(ConsistencyError::WrongPresent(present));
+        }
+
+        Ok(())
+    }
+
+    /**
+        Replays the game turn by turn, starting from the first board of its initial timelines; see `Replay`.
+        The last position yielded has the same boards as `self`.
+    **/
+    pub fn replay(&self) -> Replay<'_> {
+        let lowest_initial = if self.info.even_initial_timelines { -1 } else { 0 };
+        let revealed: HashMap<i32, usize> = self
+            .timelines
+            .keys()
+            .filter(|l| **l >= lowest_initial && **l <= 0)
+            .map(|l| (*l, 1))
+            .collect();
+        let active_player = revealed
+            .keys()
+            .map(|l| &self.timelines[l].states[0])
+            .min_by_key(|b| b.t)
+            .is_none_or(|b| b.active_player());
+
+        let mut info = self.info;
+        info.active_player = active_player;
+        info.min_timeline = lowest_initial;
+        info.max_timeline = 0;
+
+        let mut res = Replay {
+            game: self,
+            revealed,
+            info,
+        };
+        res.info.present = res.present();
+        res
+    }
+}
+
+/**
+    An iterator replaying a game, yielding the boards created by each turn and the position reached after that turn.
+
+    As the moves themselves are not recorded, turns are inferred from the boards, which is a heuristic: during each turn, every timeline whose last board so far is of the player's turn
+    is advanced by one board if the game has more boards on it, and the player's new timelines are then created in the order of their indices (at most one per board played),
+    as soon as the board they branch off from has a successor since a previous turn.
+    The positions yielded are thus possible positions of the game, but they may differ from the ones of the game as played: optional boards that were left unplayed
+    are played on during the first turn that they could be, and there may then be fewer positions than turns. The last position always has the boards of the game.
+**/
+pub struct Replay<'a> {
+    game: &'a Game,
+    /// The number of boards of each timeline that are part of the current position
+    revealed: HashMap<i32, usize>,
+    info: GameInfo,
+}
+
+impl<'a> Replay<'a> {
+    /// Returns the present of the current position
+    fn present(&self) -> isize {
+        self.revealed
+            .iter()
+            .filter(|(l, _)| self.info.is_timeline_active(**l))
+            .map(|(l, n)| self.game.timelines[l].begins_at + *n as isize - 1)
+            .min()
+            .unwrap_or(self.info.present)
+    }
+
+    /// Returns whether the board `(l, t)` is part of the position in which `revealed` boards of each timeline are, without being the last board of its timeline
+    fn has_successor(&self, revealed: &HashMap<i32, usize>, l: i32, t: isize) -> bool {
+        match revealed.get(&l) {
+            Some(n) => {
+                let begins_at = self.game.timelines[&l].begins_at;
+                t >= begins_at && t < begins_at + *n as isize - 1
+            }
+            None => false,
+        }
+    }
+
+    /// Builds the current position
+    fn current_position(&self) -> Game {
+        let mut res = Game::new(self.game.width, self.game.height);
+        res.info = self.info;
+        res.travel_rules = self.game.travel_rules;
+        res.check_rule = self.game.check_rule;
+        res.promotion_ranks = self.game.promotion_ranks;
+        for (l, n) in self.revealed.iter() {
+            let tl = &self.game.timelines[l];
+            res.timelines.insert(*l, Timeline {
+                states: tl.states[0..*n].to_vec(),
+                ..tl.clone()
+            });
+        }
+        res
+    }
+}
+
+impl<'a> Iterator for Replay<'a> {
+    type Item = (Vec<Board>, Game);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let white = self.info.active_player;
+        let mut created: Vec<Board> = Vec::new();
+        let before = self.revealed.clone();
+
+        let mut indices = self.revealed.keys().copied().collect::<Vec<_>>();
+        indices.sort_unstable();
+        for l in indices {
+            let n = self.revealed[&l];
+            let tl = &self.game.timelines[&l];
+            if tl.states[n - 1].active_player() == white && n < tl.states.len() {
+                created.push(tl.states[n].clone());
+                self.revealed.insert(l, n + 1);
+            }
+        }
+
+        // Every new timeline needs a piece leaving one of the boards that were played on
+        let mut jumps_left = created.len();
+        while jumps_left > 0 {
+            let l = if white {
+                self.info.max_timeline + 1
+            } else {
+                self.info.min_timeline - 1
+            };
+            let tl = match self.game.timelines.get(&l) {
+                Some(tl) => tl,
+                None => break,
+            };
+            // A piece can only branch off a board that was not the last of its timeline at the beginning of the turn
+            let branches_off = match tl.emerges_from {
+                Some(parent) => self.has_successor(&before, parent, tl.begins_at - 1),
+                None => before
+                    .keys()
+                    .any(|parent| self.has_successor(&before, *parent, tl.begins_at - 1)),
+            };
+            if tl.states[0].active_player() == white || !branches_off {
+                break;
+            }
+
+            created.push(tl.states[0].clone());
+            self.revealed.insert(l, 1);
+            if white {
+                self.info.max_timeline = l;
+            } else {
+                self.info.min_timeline = l;
+            }
+            jumps_left -= 1;
+        }
+
+        if created.is_empty() {
+            return None;
+        }
+
+        self.info.active_player = !white;
+        self.info.present = self.present();
+        Some((created, self.current_position()))
+    }
+}
+
+impl GameBuilder {
+    /// Creates a new builder, for a game whose boards are `width` × `height` squares
+    pub fn new(width: u8, height: u8) -> Self {
+        GameBuilder {
+            width,
+            height,
+            boards: Vec::new(),
+            parents: HashMap::new(),
+            active_player: None,
+            present: None,
+            even_initial_timelines: false,
+        }
+    }
+
+    /// Adds `board` at `(l, t)`; the coordinates of `board` are overwritten
+    pub fn add_board(mut self, l: i32, t: isize, mut board: Board) -> Self {
+        board.l = l;
+        board.t = t;
+        self.boards.push(board);
+        self
+    }
+
+    /// Marks the timeline `l` as having branched off the timeline `parent`, on the board preceding its first board
+    pub fn emerges_from(mut self, l: i32, parent: i32) -> Self {
+        self.parents.insert(l, parent);
+        self
+    }
+
+    /// Sets the active player (`true` for white); `build` fails if it is not the one whose turn it is in the present
+    pub fn active_player(mut self, active_player: bool) -> Self {
+        self.active_player = Some(active_player);
+        self
+    }
+
+    /// Sets the present; `build` fails if it does not correspond to the last board of the earliest active timeline
+    pub fn present(mut self, t: isize) -> Self {
+        self.present = Some(t);
+        self
+    }
+
+    /// Sets whether the game starts with two timelines (`-1` and `0`) instead of one
+    pub fn even_initial_timelines(mut self, even_initial_timelines: bool) -> Self {
+        self.even_initial_timelines = even_initial_timelines;
+        self
+    }
+
+    /// Assembles the game, verifying that its timelines and its present are consistent
+    pub fn build(self) -> Result<Game, BuildError> {
+        let mut game = Game::new(self.width, self.height);
+        game.info.even_initial_timelines = self.even_initial_timelines;
+
+        let mut boards = self.boards;
+        boards.sort_by_key(|b| (b.l, b.t));
+        for board in boards {
+            match game.timelines.get_mut(&board.l) {
+                Some(tl) => {
+                    let next = tl.begins_at + tl.states.len() as isize;
+                    if board.t < next {
+                        return Err(BuildError::DuplicateBoard(board.l, board.t));
+                    } else if board.t > next {
+                        return Err(BuildError::MissingBoard(board.l, next));
+                    }
+                    tl.states.push(board);
+                }
+                None => {
+                    let mut tl = Timeline::new(
+                        board.l,
+                        self.width,
+                        self.height,
+                        board.t,
+                        self.parents.get(&board.l).copied(),
+                    );
+                    let l = board.l;
+                    tl.states.push(board);
+                    game.timelines.insert(l, tl);
+                }
+            }
+        }
+
+        game.info.min_timeline = *game.timelines.keys().min().ok_or(BuildError::NoBoards)?;
+        game.info.max_timeline = *game.timelines.keys().max().ok_or(BuildError::NoBoards)?;
+        game.info.present = match self.present {
+            Some(present) => present,
+            None => game
+                .timelines
+                .values()
+                .filter(|tl| game.info.is_timeline_active(tl.index))
+                .map(|tl| tl.begins_at + tl.states.len() as isize - 1)
+                .min()
+                .unwrap_or(0),
+        };
+        let to_play = game.info.present.rem_euclid(2) == 0;
+        game.info.active_player = self.active_player.unwrap_or(to_play);
+
+        game.validate_consistency().map_err(BuildError::Inconsistent)?;
+        if game.info.active_player != to_play {
+            return Err(BuildError::WrongActivePlayer);
+        }
+
+        Ok(game)
+    }
 }
 
 impl Timeline {
@@ -494,7 +1380,7 @@ impl Timeline {
 
     /// Returns the piece at `(t, x, y)` in this timeline, panics if the square does not exist. UB if that board's size is not equal to the timeline's own size
     pub fn get_unsafe<'a>(&'a self, t: isize, x: u8, y: u8) -> Piece {
-        self.states[(t - self.begins_at) as usize].pieces[(x + self.width * y) as usize]
+        self.states[(t - self.begins_at) as usize].pieces[x as usize + self.width as usize * y as usize]
     }
 }
 
@@ -511,6 +1397,7 @@ impl Board {
             king_b: None,
             castle_w: (false, false),
             castle_b: (false, false),
+            piece_counts: (0, 0),
         }
     }
 
@@ -519,28 +1406,88 @@ impl Board {
         if x >= self.width || y >= self.height {
             None
         } else {
-            self.pieces.get((x + y * self.width) as usize).copied()
+            self.pieces.get(x as usize + y as usize * self.width as usize).copied()
         }
     }
 
+    /// Returns what stands at `(x, y)`, `Tile::Void` if that square is outside of the board
+    pub fn tile(&self, x: u8, y: u8) -> Tile {
+        self.get(x, y).into()
+    }
+
     /// Returns the piece at `(x, y)`, panics if not found
     pub fn get_unsafe(&self, x: u8, y: u8) -> Piece {
-        self.pieces[(x + y * self.width) as usize]
+        self.pieces[x as usize + y as usize * self.width as usize]
+    }
+
+    /// Returns whether `white` has at least one piece on this board; the pieces are counted as the board is modified, so that no square is looked at
+    pub fn has_pieces_of(&self, white: bool) -> bool {
+        self.piece_count(white) > 0
+    }
+
+    /// Returns the number of pieces that `white` has on this board
+    pub fn piece_count(&self, white: bool) -> usize {
+        if white {
+            self.piece_counts.0 as usize
+        } else {
+            self.piece_counts.1 as usize
+        }
+    }
+
+    /// Counts the pieces of both players again, after `pieces` was written to directly; the pieces past the board's `width × height` squares are not counted
+    pub fn recount_pieces(&mut self) {
+        let (white, black) = self.coords().fold((0, 0), |(white, black), (_, piece)| {
+            (white + piece.is_white() as u16, black + piece.is_black() as u16)
+        });
+        self.piece_counts = (white, black);
+    }
+
+    /// Updates the piece counts for `old` being replaced by `new` on one of the squares
+    fn count_replaced(&mut self, old: Piece, new: Piece) {
+        self.piece_counts.0 = (self.piece_counts.0 + new.is_white() as u16).saturating_sub(old.is_white() as u16);
+        self.piece_counts.1 = (self.piece_counts.1 + new.is_black() as u16).saturating_sub(old.is_black() as u16);
+    }
+
+    /// Returns the coordinates of the square stored at `index` in `pieces`, None for indices past the board's `width × height` squares (which a malformed board may have)
+    pub fn square_of(&self, index: usize) -> Option<(u8, u8)> {
+        if index >= self.width as usize * self.height as usize {
+            None
+        } else {
+            Some(((index % self.width as usize) as u8, (index / self.width as usize) as u8))
+        }
+    }
+
+    /// Iterates over the squares of the board in row-major order (`(0, 0)`, `(1, 0)`, ..., `(0, 1)`, ...), yielding their coordinates and contents, empty squares included
+    pub fn coords(&self) -> impl Iterator<Item = (Coords, Piece)> + '_ {
+        self.pieces
+            .iter()
+            .enumerate()
+            .map_while(move |(index, piece)| self.square_of(index).map(|(x, y)| ((self.l, self.t, x, y), *piece)))
+    }
+
+    /// Returns the piece at `(x, y)` along with its coordinates, or None if that square is empty or outside of the board
+    pub fn piece_at(&self, x: u8, y: u8) -> Option<(Piece, Coords)> {
+        self.get(x, y)
+            .filter(|piece| !piece.is_blank())
+            .map(|piece| (piece, (self.l, self.t, x, y)))
     }
 
     /// Sets the piece at `(x, y)`, returns `Ok` on success and `Err` if the square does not exist
     pub fn set(&mut self, x: u8, y: u8, piece: Piece) -> Result<(), ()> {
-        if x >= self.width || y >= self.height {
-            Err(())
-        } else {
-            self.pieces[(x + y * self.width) as usize] = piece;
-            Ok(())
+        match self.pieces.get_mut(x as usize + y as usize * self.width as usize) {
+            Some(square) if x < self.width && y < self.height => {
+                let old = std::mem::replace(square, piece);
+                self.count_replaced(old, piece);
+                Ok(())
+            }
+            _ => Err(()),
         }
     }
 
     /// Sets the piece at `(x, y)`, panics if the square does not exist
     pub fn set_unsafe(&mut self, x: u8, y: u8, piece: Piece) {
-        self.pieces[(x + y * self.width) as usize] = piece;
+        let old = std::mem::replace(&mut self.pieces[x as usize + y as usize * self.width as usize], piece);
+        self.count_replaced(old, piece);
     }
 
     /// Returns whose player's turn it is on this board
@@ -550,51 +1497,253 @@ impl Board {
 
     /// Returns whether or not this board must be played on (does not check if it is the last board in its timeline)
     pub fn is_active(&self, info: &GameInfo) -> bool {
-        if info.even_initial_timelines {
-            self.t <= info.present
-                && if self.l < 0 {
-                    self.l >= -info.max_timeline - 2
-                } else {
-                    self.l <= -info.min_timeline + 1
+        self.t <= info.present && info.is_timeline_active(self.l)
+    }
+
+    /**
+        Returns the squares that `piece` would attack if it was placed on `(x, y)` instead of what is there, without modifying the board: the squares it could move to or take on (see `moves::probable_moves_at`).
+        `piece` moves as a piece of its own colour, even if it is not that player's turn on this board.
+        As in `moves::controlled_squares`, pawns attack the squares diagonally in front of them (whether or not there is a piece to take there) but not the squares in front of them.
+        The board does not need to be within `game` or `virtual_boards`.
+    **/
+    pub fn hypothetical_attacks(&self, game: &Game, virtual_boards: &Vec<&Board>, x: u8, y: u8, piece: Piece) -> Vec<Coords> {
+        let mut board = self.clone();
+        if piece.is_blank() || board.set(x, y, piece).is_err() {
+            return vec![];
+        }
+
+        if piece.is_pawn() {
+            let y = y as isize + if piece.is_white() { 1 } else { -1 };
+            return [x as isize - 1, x as isize + 1]
+                .iter()
+                .filter(|x| **x >= 0 && **x < self.width as isize && y >= 0 && y < self.height as isize)
+                .map(|x| (*x as u8, y as u8))
+                .filter(|(x, y)| board.get(*x, *y).is_some_and(|p| !p.is_own_piece(piece.is_white())))
+                .map(|(x, y)| (self.l, self.t, x, y))
+                .collect();
+        }
+
+        let mut moves = Vec::new();
+        probable_moves_as(game, &board, virtual_boards, &mut moves, piece, x, y, piece.is_white());
+        if game.travel_rules != TravelRules::default() {
+            moves.retain(|mv| is_travel_allowed(game, virtual_boards, mv));
+        }
+        moves.into_iter().map(|mv| mv.dst).collect()
+    }
+
+    /// Returns the sum of the values of the pieces on this board, as given by `values` (see `PieceValues::value`); positive values favour white
+    pub fn material(&self, values: &PieceValues) -> i32 {
+        self.pieces.iter().map(|piece| values.value(*piece)).sum()
+    }
+
+    /**
+        Updates the castling rights of this board before `piece` leaves the square `(x, y)`, or is taken on it: a king leaving its starting square (`king_w` or `king_b`) loses both rights,
+        and the castling rook of a side of the king, the rook of its rank closest to it on that side (as castling moves are generated), loses the right of that side; other rooks leave the rights untouched.
+        This must be called while the board still holds the pieces of before the move. Rights are never given back, so that a king or a rook that moved and came back cannot castle.
+    **/
+    pub fn revoke_castling_rights(&mut self, x: u8, y: u8, piece: Piece) {
+        let king = match piece {
+            Piece::KingW | Piece::RookW => self.king_w,
+            Piece::KingB | Piece::RookB => self.king_b,
+            _ => return,
+        };
+        let (kx, ky) = match king {
+            Some(king) => king,
+            None => return,
+        };
+        let castling_rook = piece.is_rook()
+            && y == ky
+            && x != kx
+            && (x.min(kx) + 1..x.max(kx)).all(|between| self.get(between, y) != Some(piece));
+        let rights = if piece.is_white() { &mut self.castle_w } else { &mut self.castle_b };
+        if piece.is_king() && (x, y) == (kx, ky) {
+            *rights = (false, false);
+        } else if castling_rook {
+            if x < kx {
+                rights.0 = false;
+            } else {
+                rights.1 = false;
+            }
+        }
+    }
+
+    /// Returns the sum of the piece-square bonuses of the pieces on this board (see `PieceSquareTables::value`); positive values favour white
+    pub fn pst(&self, tables: &PieceSquareTables) -> i32 {
+        self.coords()
+            .map(|((_, _, x, y), piece)| tables.value(piece, x, y, self.width, self.height))
+            .sum()
+    }
+
+    /**
+        Returns how well the kings on this board are sheltered, positive values favour white; this is the king protection term of `resolve::score_moveset`.
+        Each square next to a king that is empty or holds an opponent's piece costs `KING_PROTECTION_VALUE`, and `KING_PROTECTION_VALUE_2` more if the square behind it in the same direction is also open.
+    **/
+    pub fn king_safety(&self) -> f32 {
+        let mut res: f32 = 0.0;
+        let open = |x: isize, y: isize, white: bool| {
+            x >= 0
+                && y >= 0
+                && match self.tile(x as u8, y as u8) {
+                    Tile::Void => false,
+                    Tile::Empty => true,
+                    Tile::Piece(piece) => piece.is_opponent_piece(white),
                 }
-        } else {
-            self.t <= info.present
-                && if self.l < 0 {
-                    self.l >= -info.max_timeline - 1
-                } else {
-                    self.l <= -info.min_timeline + 1
+        };
+        for ((_, _, x, y), piece) in self.coords().filter(|(_, piece)| piece.is_king()) {
+            let (x, y) = (x as isize, y as isize);
+            let white = piece.is_white();
+            let mult: f32 = if white { 1.0 } else { -1.0 };
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if (dx != 0 || dy != 0) && open(x + dx, y + dy, white) {
+                        res -= KING_PROTECTION_VALUE * mult;
+                        if open(x + 2 * dx, y + 2 * dy, white) {
+                            res -= KING_PROTECTION_VALUE_2 * mult;
+                        }
+                    }
                 }
+            }
+        }
+        res
+    }
+
+    /**
+        Returns the number of moves that white's pieces on this board could make, minus the number of moves that black's pieces could make, whether or not it is their turn on this board.
+        These are the moves returned by `moves::probable_moves_at`, each piece moving as a piece of its own colour (as in `hypothetical_attacks`): they are not checked for legality and castling is not counted.
+    **/
+    pub fn mobility(&self, game: &Game, virtual_boards: &Vec<&Board>) -> i32 {
+        let mut res: i32 = 0;
+        let mut moves = Vec::new();
+        for ((_, _, x, y), piece) in self.coords().filter(|(_, piece)| !piece.is_blank()) {
+            moves.clear();
+            probable_moves_as(game, self, virtual_boards, &mut moves, piece, x, y, piece.is_white());
+            if game.travel_rules != TravelRules::default() {
+                moves.retain(|mv| is_travel_allowed(game, virtual_boards, mv));
+            }
+            res += if piece.is_white() { moves.len() as i32 } else { -(moves.len() as i32) };
+        }
+        res
+    }
+
+    /**
+        Returns a random move of this board's active player, without generating the moves of all of their pieces: pieces are drawn at random until one of them can move,
+        and one of its moves (as given by `moves::probable_moves`, castling included) is then drawn. Returns None if none of the pieces can move.
+        The move is only pseudo-legal, and as pieces are drawn first, the moves of pieces with few moves are more likely than those of pieces with many;
+        to play a random turn, combine the moves of several boards and check them with `moves::is_moveset_legal`, or see `moves::random_legal_moveset`.
+    **/
+    pub fn random_move<R: Rng>(&self, game: &Game, virtual_boards: &Vec<&Board>, rng: &mut R) -> Option<Move> {
+        let white = self.active_player();
+        let mut squares: Vec<(u8, u8)> = self
+            .coords()
+            .filter(|(_, piece)| piece.is_own_piece(white))
+            .map(|((_, _, x, y), _)| (x, y))
+            .collect();
+        while !squares.is_empty() {
+            let (x, y) = squares.swap_remove(rng.below(squares.len()));
+            let mut moves = probable_moves_filtered(game, self, virtual_boards, |_, (_, _, px, py)| (px, py) == (x, y));
+            if !moves.is_empty() {
+                return Some(moves.swap_remove(rng.below(moves.len())));
+            }
         }
+        None
+    }
+}
+
+impl BoardPool {
+    /// Creates a new, empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of `board`, whose piece array is taken from the pool if one is available
+    pub fn clone_board(&mut self, board: &Board) -> Board {
+        let pieces = match self.free.pop() {
+            Some(mut pieces) => {
+                pieces.clear();
+                pieces.extend_from_slice(&board.pieces);
+                pieces
+            }
+            None => {
+                self.allocations += 1;
+                board.pieces.clone()
+            }
+        };
+        Board { pieces, ..*board }
+    }
+
+    /// Hands the piece array of `board` back to the pool
+    pub fn release(&mut self, board: Board) {
+        self.free.push(board.pieces);
+    }
+
+    /// Hands the piece arrays of every board in `boards` back to the pool
+    pub fn release_all<I: IntoIterator<Item = Board>>(&mut self, boards: I) {
+        self.free.extend(boards.into_iter().map(|board| board.pieces));
+    }
+
+    /// Returns the number of piece arrays that are available for reuse
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Returns the number of piece arrays that the pool had to allocate, as none were available
+    pub fn allocations(&self) -> usize {
+        self.allocations
     }
 }
 
 impl fmt::Display for Piece {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Piece::Blank => ".",
-                Piece::PawnW => "P",
-                Piece::KnightW => "N",
-                Piece::BishopW => "B",
-                Piece::RookW => "R",
-                Piece::QueenW => "Q",
-                Piece::KingW => "K",
-                Piece::UnicornW => "U",
-                Piece::DragonW => "D",
-                Piece::PrincessW => "S",
-                Piece::PawnB => "p",
-                Piece::KnightB => "n",
-                Piece::BishopB => "b",
-                Piece::RookB => "r",
-                Piece::QueenB => "q",
-                Piece::KingB => "k",
-                Piece::UnicornB => "u",
-                Piece::DragonB => "d",
-                Piece::PrincessB => "s",
-            }
-        )
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl PartialTurn {
+    /// Copies a position in the middle of a turn, made of `game`, the boards played on top of it and `info`, so that it can outlive them (eg. to be saved with `parse::to_snapshot_json`)
+    pub fn snapshot(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> Self {
+        PartialTurn {
+            game: game.clone(),
+            boards: virtual_boards.iter().map(|b| (*b).clone()).collect(),
+            info: *info,
+        }
+    }
+
+    /// Returns references to `boards`, to be used as the virtual boards of `game`
+    pub fn virtual_boards(&self) -> Vec<&Board> {
+        self.boards.iter().collect()
+    }
+
+    /// Returns the `(l, t)` board, looking among `boards` first and then in `game`; None if not found
+    pub fn board_at(&self, l: i32, t: isize) -> Option<&Board> {
+        self.boards
+            .iter()
+            .rev()
+            .find(|b| b.l == l && b.t == t)
+            .or_else(|| self.game.get_board(l, t))
+    }
+
+    /// Returns the coordinates of the boards that still have to be played on to complete the turn
+    pub fn remaining_boards(&self) -> Vec<(i32, isize)> {
+        let virtual_boards = self.virtual_boards();
+        get_own_boards(&self.game, &virtual_boards, &self.info)
+            .into_iter()
+            .filter(|b| b.is_active(&self.info))
+            .map(|b| (b.l, b.t))
+            .collect()
+    }
+}
+
+/// Lets functions be generic over owned and borrowed boards (`B: AsRef<Board>` accepts both `Board` and `&Board`)
+impl AsRef<Board> for Board {
+    fn as_ref(&self) -> &Board {
+        self
+    }
+}
+
+/// Clones a borrowed board, so that `B: Into<Board>` accepts both `Board` and `&Board` where an owned board is needed
+impl From<&Board> for Board {
+    fn from(board: &Board) -> Self {
+        board.clone()
     }
 }
 
@@ -602,7 +1751,8 @@ impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for y in (0..self.height).rev() {
             for x in 0..self.width {
-                write!(f, "{}", self.pieces[(x + y * self.height) as usize])?;
+                let piece = self.pieces.get(x as usize + y as usize * self.width as usize).copied().unwrap_or(Piece::Blank);
+                write!(f, "{}", piece)?;
             }
             if y > 0 {
                 write!(f, "\n")?;
@@ -695,6 +1845,40 @@ pub fn write_timeline(l: i32, even_initial_timelines: bool) -> String {
     }
 }
 
+/**
+    Maps the index of each timeline to the one given to it by `Game::normalize_timelines`, along with the shift applied to the initial timelines; returns None if there are no timelines.
+    `timelines` lists the index of every timeline, the time at which it begins and whether it emerges from another timeline.
+**/
+pub(crate) fn normalized_indices(timelines: &[(i32, isize, bool)]) -> Option<(HashMap<i32, i32>, i32)> {
+    let first_t = timelines.iter().map(|(_, begins_at, _)| *begins_at).min()?;
+    let mut indices: Vec<i32> = timelines.iter().map(|(l, _, _)| *l).collect();
+    indices.sort_unstable();
+    indices.dedup();
+    let mut initial: Vec<i32> = indices
+        .iter()
+        .copied()
+        .filter(|l| timelines.iter().any(|(index, begins_at, emerges)| index == l && !emerges && *begins_at == first_t))
+        .collect();
+    if initial.is_empty() {
+        initial = vec![indices[0]];
+    }
+    let (lowest, highest) = (initial[0], initial[initial.len() - 1]);
+    let shift = -highest;
+
+    let mut mapping: HashMap<i32, i32> = HashMap::new();
+    for (n, l) in indices.iter().copied().filter(|l| *l > highest).enumerate() {
+        mapping.insert(l, n as i32 + 1);
+    }
+    for (n, l) in indices.iter().copied().rev().filter(|l| *l < lowest).enumerate() {
+        mapping.insert(l, lowest + shift - 1 - n as i32);
+    }
+    for l in indices.iter().copied().filter(|l| (lowest..=highest).contains(l)) {
+        mapping.insert(l, l + shift);
+    }
+
+    Some((mapping, shift))
+}
+
 /// Returns the string version of the `x` coordinate as displayed in-game
 pub fn write_file(x: u8) -> char {
     [
@@ -703,6 +1887,67 @@ pub fn write_file(x: u8) -> char {
     ][x as usize]
 }
 
+/**
+    Draws every board of the game and of `virtual_boards` as text, for debugging: timelines are laid out as rows (from the highest to the lowest) and time as columns.
+    Columns are labelled like in-game (`T1w`, `T1b`, ...) and the present is marked with a `*`.
+**/
+pub fn to_ascii(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> String {
+    let mut boards: HashMap<(i32, isize), &Board> = HashMap::new();
+    for tl in game.timelines.values() {
+        for board in tl.states.iter() {
+            boards.insert((board.l, board.t), board);
+        }
+    }
+    for board in virtual_boards {
+        boards.insert((board.l, board.t), board);
+    }
+
+    let mut timelines = boards.keys().map(|(l, _)| *l).collect::<Vec<_>>();
+    timelines.sort_unstable();
+    timelines.dedup();
+    let min_t = boards.keys().map(|(_, t)| *t).min().unwrap_or(0);
+    let max_t = boards.keys().map(|(_, t)| *t).max().unwrap_or(0);
+    let cell_width = (game.width as usize).max(4) + 2;
+
+    let mut res = String::from("    ");
+    for t in min_t..=max_t {
+        let label = format!(
+            "T{}{}{}",
+            t.div_euclid(2) + 1,
+            if t.rem_euclid(2) == 0 { "w" } else { "b" },
+            if t == info.present { "*" } else { "" }
+        );
+        res.push_str(&format!("{:<1$}", label, cell_width));
+    }
+    res.truncate(res.trim_end().len());
+    res.push('\n');
+
+    for l in timelines.into_iter().rev() {
+        for y in (0..game.height).rev() {
+            let label = if y == game.height - 1 {
+                write_timeline(l, info.even_initial_timelines)
+            } else {
+                String::new()
+            };
+            res.push_str(&format!("{:<4}", label));
+            for t in min_t..=max_t {
+                let row: String = match boards.get(&(l, t)) {
+                    Some(board) => (0..board.width)
+                        .map(|x| board.get(x, y).map_or(' ', |p| p.to_char()))
+                        .collect(),
+                    None => String::new(),
+                };
+                res.push_str(&format!("{:<1$}", row, cell_width));
+            }
+            res.truncate(res.trim_end().len());
+            res.push('\n');
+        }
+        res.push('\n');
+    }
+
+    res
+}
+
 /**
     Populates the castling rights of every board in the game; does so by induction (uses the `bubble_down` function)
 **/
@@ -866,3 +2111,621 @@ pub fn populate_castling_rights(game: &mut Game) {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::probable_moves;
+    use crate::moveset::TurnAssembler;
+    use crate::testing;
+
+    #[test]
+    fn coords_index_round_trip() {
+        let game = GameBuilder::new(3, 2)
+            .add_board(0, 0, Board::new(0, 0, 3, 2))
+            .add_board(0, 1, Board::new(1, 0, 3, 2))
+            .add_board(0, 2, Board::new(2, 0, 3, 2))
+            .add_board(-1, 2, Board::new(2, -1, 3, 2))
+            .emerges_from(-1, 0)
+            .add_board(1, 2, Board::new(2, 1, 3, 2))
+            .add_board(1, 3, Board::new(3, 1, 3, 2))
+            .emerges_from(1, 0)
+            .build()
+            .unwrap();
+        assert_eq!((game.info.min_timeline, game.info.max_timeline), (-1, 1));
+
+        let mut expected = 0;
+        for l in -1..=1 {
+            for t in 0..=3 {
+                for y in 0..2 {
+                    for x in 0..3 {
+                        let index = game.coords_to_index((l, t, x, y)).unwrap();
+                        assert_eq!(game.coords_from_index(index), Some((l, t, x, y)));
+                        assert!(index < 3 * 4 * 2 * 3);
+                        expected += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(expected, 3 * 4 * 2 * 3);
+        assert_eq!(game.coords_from_index(expected), None);
+
+        assert_eq!(game.coords_to_index((-2, 0, 0, 0)), None);
+        assert_eq!(game.coords_to_index((2, 0, 0, 0)), None);
+        assert_eq!(game.coords_to_index((0, 4, 0, 0)), None);
+        assert_eq!(game.coords_to_index((0, 0, 3, 0)), None);
+        assert_eq!(game.coords_to_index((0, 0, 0, 2)), None);
+    }
+
+    #[test]
+    fn testing_board_layout() {
+        let board = testing::board(0, 0, &["k...", "....", "...K"]);
+        assert_eq!(board.get(0, 2), Some(Piece::KingB));
+        assert_eq!(board.get(3, 0), Some(Piece::KingW));
+        assert_eq!(board.king_w, Some((3, 0)));
+
+        let game = testing::game(1, &["k...", "....", "...K"]);
+        assert!(!game.info.active_player);
+        assert_eq!(game.get_board(0, 1).unwrap().get(0, 2), Some(Piece::KingB));
+    }
+
+    #[test]
+    fn piece_letters_and_symbols() {
+        let board = testing::board(0, 0, &["KQPNRBUDS.", "kqpnrbuds."]);
+        let pieces: Vec<Piece> = (0..board.width).flat_map(|x| [board.get(x, 1).unwrap(), board.get(x, 0).unwrap()]).collect();
+        let letters: String = pieces.iter().map(Piece::to_char).collect();
+        assert_eq!(letters, "KkQqPpNnRrBbUuDdSs..");
+        assert_eq!(pieces.iter().map(|p| p.to_string()).collect::<String>(), letters);
+        for piece in pieces.iter().filter(|p| !p.is_blank()) {
+            assert_eq!(piece.to_char().to_ascii_uppercase().to_string(), piece.as_uppercase());
+            assert_eq!(piece.to_char().is_ascii_uppercase(), piece.is_white());
+        }
+
+        let symbols: String = pieces.iter().map(Piece::to_unicode).collect();
+        assert_eq!(symbols, "♔♚♕♛♙♟♘♞♖♜♗♝\u{1fa06}\u{1fa07}\u{1fa1b}\u{1fa1c}\u{1fa0a}\u{1fa10}  ");
+        let mut distinct: Vec<char> = symbols.chars().collect();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 19);
+    }
+
+    #[test]
+    fn build_games_and_reject_broken_ones() {
+        // 1. Nf3 / Nf6 / 2. Nf3>>(0T1)h3
+        let mut played = crate::variant::standard();
+        testing::play(&mut played, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut played, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        testing::play(&mut played, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+
+        let mut builder = GameBuilder::new(8, 8);
+        for tl in played.timelines.values() {
+            for board in tl.states.iter().rev() {
+                builder = builder.add_board(board.l, board.t, board.clone());
+            }
+        }
+        let built = builder.clone().build().unwrap();
+        let fields = |info: &GameInfo| (info.present, info.active_player, info.min_timeline, info.max_timeline, info.even_initial_timelines);
+        assert_eq!(fields(&built.info), fields(&played.info));
+        assert_same_boards(&built, &played);
+        let branched = builder.clone().emerges_from(1, 0).build().unwrap();
+        assert_eq!(fields(&branched.info), fields(&played.info));
+        assert_eq!(branched.timelines[&1].emerges_from, Some(0));
+        assert!(builder.clone().present(1).active_player(false).build().is_ok());
+        assert_eq!(builder.clone().active_player(true).build().unwrap_err(), BuildError::WrongActivePlayer);
+        assert!(matches!(builder.clone().present(2).build(), Err(BuildError::Inconsistent(_))));
+
+        let board = |t| testing::board(0, t, &["k...", "....", "....", "K..."]);
+        assert_eq!(GameBuilder::new(4, 4).build().unwrap_err(), BuildError::NoBoards);
+        assert_eq!(
+            GameBuilder::new(4, 4).add_board(0, 0, board(0)).add_board(0, 0, board(0)).build().unwrap_err(),
+            BuildError::DuplicateBoard(0, 0)
+        );
+        assert_eq!(
+            GameBuilder::new(4, 4).add_board(0, 0, board(0)).add_board(0, 2, board(2)).build().unwrap_err(),
+            BuildError::MissingBoard(0, 1)
+        );
+
+        // Two initial timelines, black to move; the coordinates of the boards are those given to `add_board`
+        let game = GameBuilder::new(4, 4)
+            .add_board(-1, 1, board(0))
+            .add_board(0, 1, board(0))
+            .even_initial_timelines(true)
+            .build()
+            .unwrap();
+        assert_eq!((game.info.min_timeline, game.info.max_timeline, game.info.present), (-1, 0, 1));
+        assert!(game.info.even_initial_timelines && !game.info.active_player);
+        assert_eq!((game.get_board(-1, 1).unwrap().l, game.get_board(-1, 1).unwrap().t), (-1, 1));
+    }
+
+    #[test]
+    fn first_movesets_of_the_standard_game() {
+        let mut game = crate::variant::standard();
+        let movesets = game.legal_first_movesets();
+        assert_eq!(movesets.len(), 20);
+        for moveset in movesets.iter() {
+            assert_eq!(moveset.0.len(), 1);
+            assert!(game.clone().advance_turn(moveset).is_ok());
+        }
+        let distinct: std::collections::HashSet<&Moveset> = movesets.iter().collect();
+        assert_eq!(distinct.len(), 20);
+
+        // Those of black, after white's first move
+        game.advance_turn(&movesets[0]).unwrap();
+        let replies = game.legal_first_movesets();
+        assert_eq!(replies.len(), 20);
+        assert!(replies.iter().all(|ms| ms.0[0].src_piece.is_black()));
+
+        let mated = testing::game(0, &["rr.k", "....", "....", "K..."]);
+        assert!(mated.legal_first_movesets().is_empty());
+    }
+
+    #[test]
+    fn advance_turns_and_reject_illegal_ones() {
+        let mut game = crate::variant::standard();
+        let mut movesets = Vec::new();
+        for n in 0..4 {
+            assert_eq!((game.info.present, game.info.active_player), (n, n % 2 == 0));
+            let moveset = game.legal_first_movesets().swap_remove(0);
+            game.advance_turn(&moveset).unwrap();
+            assert_eq!(game.validate_consistency(), Ok(()));
+            assert_eq!(game.timelines[&0].states.len(), n as usize + 2);
+            movesets.push(moveset);
+        }
+        assert_eq!((game.info.present, game.info.active_player), (4, true));
+
+        // White's first moveset cannot be played again: the game is left as it was
+        let before = game.clone();
+        assert_eq!(game.advance_turn(&movesets[0]), Err(TurnError::InvalidMove(0)));
+        assert_eq!(game.advance_turn(&Moveset(vec![])), Err(TurnError::UnplayedBoards));
+        assert_eq!((game.info.present, game.info.active_player), (before.info.present, before.info.active_player));
+        assert_same_boards(&game, &before);
+
+        // Replaying the same turns gives the same game, up to the first turn rejected
+        let mut replayed = crate::variant::standard();
+        assert_eq!(replayed.apply_movesets(&movesets), Ok(()));
+        assert_same_boards(&replayed, &game);
+        let mut replayed = crate::variant::standard();
+        assert_eq!(replayed.apply_movesets(&[movesets[0].clone(), movesets[0].clone()]), Err((1, TurnError::InvalidMove(0))));
+        assert_eq!(replayed.info.present, 1);
+    }
+
+    #[test]
+    fn consistent_after_branching() {
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        assert_eq!(game.validate_consistency(), Ok(()));
+
+        // The knight jumps back to the first board: black now has to play on the new timeline, one time step before the main one
+        testing::play(&mut game, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+        assert_eq!((game.info.max_timeline, game.info.present), (1, 1));
+        assert_eq!(game.validate_consistency(), Ok(()));
+
+        // Black branches off in turn, to a board of the present
+        testing::play(&mut game, &[((1, 1, 6, 7), (0, 1, 6, 5))]);
+        assert_eq!((game.info.min_timeline, game.info.present), (-1, 2));
+        assert_eq!(game.validate_consistency(), Ok(()));
+        testing::play(&mut game, &[((1, 2, 7, 2), (1, 2, 6, 4)), ((-1, 2, 4, 1), (-1, 2, 4, 3))]);
+        assert_eq!(game.info.present, 3);
+        assert_eq!(game.validate_consistency(), Ok(()));
+    }
+
+    #[test]
+    fn turn_numbers_follow_the_present() {
+        let mut game = crate::variant::standard();
+        assert_eq!((game.full_turn_number(), game.ply()), (1, 0));
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        assert_eq!((game.full_turn_number(), game.ply()), (1, 1));
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        assert_eq!((game.full_turn_number(), game.ply()), (2, 2));
+
+        // Travelling back in time moves the present back, to black's first move
+        testing::play(&mut game, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+        assert_eq!((game.full_turn_number(), game.ply()), (1, 1));
+        testing::play(&mut game, &[((1, 1, 6, 7), (0, 1, 6, 5))]);
+        assert_eq!((game.full_turn_number(), game.ply()), (2, 2));
+
+        // Boards before white's first move do not give a negative ply
+        let mut before = game.clone();
+        before.info.present = -1;
+        assert_eq!((before.full_turn_number(), before.ply()), (1, 0));
+    }
+
+    #[test]
+    fn hypothetical_attacks_either_colour() {
+        // Black to move: a white rook still takes black pieces and is blocked by white ones
+        let game = testing::game(1, &["k...", ".p..", "....", "...K"]);
+        let board = game.get_board(0, 1).unwrap();
+        let squares = |piece| {
+            let mut res: Vec<(u8, u8)> = board
+                .hypothetical_attacks(&game, &vec![], 1, 0, piece)
+                .into_iter()
+                .inspect(|&(l, t, _, _)| assert_eq!((l, t), (0, 1)))
+                .map(|(_, _, x, y)| (x, y))
+                .collect();
+            res.sort();
+            res
+        };
+        assert_eq!(squares(Piece::RookW), vec![(0, 0), (1, 1), (1, 2), (2, 0)]);
+        assert_eq!(squares(Piece::RookB), vec![(0, 0), (1, 1), (2, 0), (3, 0)]);
+        assert_eq!(board.get(1, 0), Some(Piece::Blank));
+    }
+
+    #[test]
+    fn timelines_after_both_players_branched() {
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        testing::play(&mut game, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+        testing::play(&mut game, &[((1, 1, 6, 7), (0, 1, 6, 5))]);
+        let info = game.info;
+        assert_eq!((info.min_timeline, info.max_timeline), (-1, 1));
+        assert_eq!(info.timeline_owner(1), Some(true));
+        assert_eq!(info.timeline_owner(0), None);
+        assert_eq!(info.timeline_owner(-1), Some(false));
+        assert!((-1..=1).all(|l| info.is_timeline_active(l)));
+        assert!(info.inactive_timelines().is_empty());
+
+        // Each player may create one timeline more than their opponent and keep it active, but not two
+        assert!(info.is_timeline_active(2) && info.is_timeline_active(-2));
+        assert!(!info.is_timeline_active(3) && !info.is_timeline_active(-3));
+        let info = GameInfo {
+            max_timeline: 3,
+            ..info
+        };
+        assert_eq!(info.inactive_timelines(), vec![3]);
+
+        // With an even number of initial timelines, -1 and 0 are both initial
+        let info = GameInfo {
+            min_timeline: -2,
+            max_timeline: 1,
+            even_initial_timelines: true,
+            ..info
+        };
+        assert_eq!(info.timeline_owner(0), None);
+        assert_eq!(info.timeline_owner(-1), None);
+        assert_eq!(info.timeline_owner(-2), Some(false));
+        assert_eq!(info.timeline_owner(1), Some(true));
+        assert!((-2..=1).all(|l| info.is_timeline_active(l)));
+        assert!(info.is_timeline_active(2) && info.is_timeline_active(-3));
+        assert!(!info.is_timeline_active(3) && !info.is_timeline_active(-4));
+    }
+
+    #[test]
+    fn inactive_timelines_of_either_player() {
+        let info = crate::variant::standard().info;
+        assert!(info.inactive_timelines().is_empty());
+
+        // Black created four timelines and white one: black's last two are inactive
+        let info = GameInfo {
+            min_timeline: -4,
+            max_timeline: 1,
+            ..info
+        };
+        assert_eq!(info.inactive_timelines(), vec![-4, -3]);
+        assert!((-2..=1).all(|l| info.is_timeline_active(l)));
+
+        // With an even number of initial timelines, white's timelines 1 to 3 against black's -2
+        let info = GameInfo {
+            min_timeline: -2,
+            max_timeline: 3,
+            even_initial_timelines: true,
+            ..info
+        };
+        assert_eq!(info.inactive_timelines(), vec![3]);
+    }
+
+    #[test]
+    fn timelines_left_and_balance() {
+        let info = crate::variant::standard().info;
+        // (min_timeline, max_timeline, even_initial_timelines) => (white's timelines left, black's, balance)
+        for ((min, max, even), expected) in [
+            ((0, 0, false), (1, 1, 0)),
+            ((0, 1, false), (0, 2, -2)),
+            ((-2, 0, false), (3, 0, 3)),
+            ((-4, 1, false), (4, 0, 4)),
+            ((-1, 0, true), (1, 1, 0)),
+            ((-2, 3, true), (0, 3, -3)),
+        ] {
+            let info = GameInfo {
+                min_timeline: min,
+                max_timeline: max,
+                even_initial_timelines: even,
+                ..info
+            };
+            let res = (info.active_timelines_left(true), info.active_timelines_left(false), info.strategic_timeline_balance());
+            assert_eq!(res, expected, "timelines {} to {}", min, max);
+        }
+    }
+
+    #[test]
+    fn coords_of_every_square() {
+        let board = testing::board(-1, 3, &["k..", "..K"]);
+        let squares: Vec<(Coords, Piece)> = board.coords().collect();
+        assert_eq!(
+            squares,
+            vec![
+                ((-1, 3, 0, 0), Piece::Blank),
+                ((-1, 3, 1, 0), Piece::Blank),
+                ((-1, 3, 2, 0), Piece::KingW),
+                ((-1, 3, 0, 1), Piece::KingB),
+                ((-1, 3, 1, 1), Piece::Blank),
+                ((-1, 3, 2, 1), Piece::Blank),
+            ]
+        );
+        for ((_, _, x, y), piece) in board.coords() {
+            assert_eq!(board.get(x, y), Some(piece));
+        }
+    }
+
+    #[test]
+    fn piece_at_squares_of_a_board() {
+        let board = testing::board(2, 5, &["k...", "Rn..", "...K"]);
+        assert_eq!(board.piece_at(0, 2), Some((Piece::KingB, (2, 5, 0, 2))));
+        assert_eq!(board.piece_at(1, 1), Some((Piece::KnightB, (2, 5, 1, 1))));
+        assert_eq!(board.piece_at(3, 0), Some((Piece::KingW, (2, 5, 3, 0))));
+        assert_eq!(board.piece_at(0, 0), None);
+        // (4, 0) would be stored where the rook of (0, 1) is
+        assert_eq!(board.piece_at(0, 1), Some((Piece::RookW, (2, 5, 0, 1))));
+        assert_eq!(board.piece_at(4, 0), None);
+        assert_eq!(board.piece_at(0, 3), None);
+    }
+
+    #[test]
+    fn tiles_of_a_board_and_game() {
+        let game = testing::game(0, &["k...", "Rn..", "...K"]);
+        let board = game.get_board(0, 0).unwrap();
+        let describe = |tile: Tile| match tile {
+            Tile::Void => "void".to_string(),
+            Tile::Empty => "empty".to_string(),
+            Tile::Piece(piece) => format!("{:?}", piece),
+        };
+        assert_eq!(describe(board.tile(0, 2)), "KingB");
+        assert_eq!(describe(board.tile(0, 0)), "empty");
+        assert_eq!(describe(board.tile(4, 0)), "void");
+        assert_eq!(describe(board.tile(0, 3)), "void");
+
+        assert_eq!(board.tile(0, 1), Tile::Piece(Piece::RookW));
+        assert_eq!(board.tile(0, 1).piece(), Some(Piece::RookW));
+        assert!(board.tile(0, 1).is_piece_of_color(true));
+        assert!(!board.tile(1, 1).is_piece_of_color(true));
+        assert!(board.tile(1, 1).is_piece_of_color(false));
+        assert!(board.tile(1, 0).is_empty() && !board.tile(1, 0).is_void());
+        assert_eq!(board.tile(1, 0).piece(), None);
+        assert!(board.tile(0, 3).is_void() && !board.tile(0, 3).is_empty());
+        assert!(!board.tile(0, 3).is_piece_of_color(true) && !board.tile(0, 3).is_piece_of_color(false));
+
+        // The game's tiles are those of its boards, and void on the boards that do not exist
+        assert_eq!(game.tile(0, 0, 3, 0), Tile::Piece(Piece::KingW));
+        assert_eq!(game.tile(0, 0, 2, 0), Tile::Empty);
+        assert_eq!(game.tile(0, 1, 3, 0), Tile::Void);
+        assert_eq!(game.tile(1, 0, 3, 0), Tile::Void);
+        assert_eq!(Tile::from(Some(Piece::Blank)), Tile::Empty);
+        assert_eq!(Tile::from(None), Tile::Void);
+    }
+
+    #[test]
+    fn malformed_boards_do_not_panic() {
+        let game = crate::variant::standard();
+        let tables = PieceSquareTables::default();
+
+        // A zero-width board, as read from malformed input, that still holds pieces
+        let mut empty = Board::new(0, 0, 0, 8);
+        empty.pieces = vec![Piece::KingW; 8];
+        empty.recount_pieces();
+        assert!(!empty.has_pieces_of(true));
+        assert_eq!(empty.square_of(0), None);
+        assert_eq!(empty.get(0, 0), None);
+        assert_eq!(empty.set(0, 0, Piece::QueenW), Err(()));
+        assert_eq!(empty.coords().count(), 0);
+        assert!(probable_moves(&game, &empty, &vec![]).is_empty());
+        assert_eq!(empty.mobility(&game, &vec![]), 0);
+        assert_eq!(empty.king_safety(), 0.0);
+        assert_eq!(empty.pst(&tables), 0);
+        assert_eq!(crate::hash::board_key(&empty), crate::hash::board_key(&Board::new(0, 0, 0, 8)));
+
+        // A board holding more pieces than its squares: the pieces past `width × height` are ignored
+        let mut board = testing::board(0, 0, &["..", "K."]);
+        let expected = board.clone();
+        board.pieces.extend([Piece::Blank, Piece::KingB]);
+        board.recount_pieces();
+        assert_eq!((board.piece_count(true), board.piece_count(false)), (1, 0));
+        assert_eq!(board.square_of(4), None);
+        assert_eq!(board.square_of(5), None);
+        assert_eq!(board.coords().count(), 4);
+        assert_eq!(board.set(0, 2, Piece::QueenW), Err(()));
+        assert_eq!(probable_moves(&game, &board, &vec![]).len(), probable_moves(&game, &expected, &vec![]).len());
+        assert_eq!(board.king_safety(), expected.king_safety());
+        assert_eq!(crate::hash::board_key(&board), crate::hash::board_key(&expected));
+    }
+
+    #[test]
+    fn ascii_drawing_of_timelines() {
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["k...", "....", "....", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &["...k", "....", "....", "...K"]))
+            .build()
+            .unwrap();
+        let moved = testing::board(0, 1, &["k...", "....", "....", ".K.."]);
+        let expected = [
+            "    T1w*  T1b",
+            "1   ...k",
+            "    ....",
+            "    ....",
+            "    ...K",
+            "",
+            "0   k...  k...",
+            "    ....  ....",
+            "    ....  ....",
+            "    K...  .K..",
+            "",
+            "",
+        ];
+        assert_eq!(to_ascii(&game, &vec![&moved], &game.info), expected.join("\n"));
+
+        // Timelines are labelled as in-game when there is an even number of initial timelines
+        let mut info = game.info;
+        info.even_initial_timelines = true;
+        info.present = 1;
+        let drawing = to_ascii(&game, &vec![], &info);
+        assert!(drawing.starts_with("    T1w\n1   ...k\n"));
+        assert!(drawing.contains("\n+0  k...\n"));
+    }
+
+    #[test]
+    fn display_non_square_boards() {
+        let board = testing::board(0, 0, &["k..", "...", ".p.", "...", "..K"]);
+        assert_eq!(board.to_string(), "k..\n...\n.p.\n...\n..K");
+        let board = testing::board(0, 0, &["q....", "....K"]);
+        assert_eq!(board.to_string(), "q....\n....K");
+
+        // Wider than 16 squares, where the square indices no longer fit in a byte
+        let mut ranks = vec!["................................"; 16];
+        ranks[0] = "...............................k";
+        ranks[15] = "K...............................";
+        let board = testing::board(0, 0, &ranks);
+        assert_eq!(board.to_string(), ranks.join("\n"));
+    }
+
+    #[test]
+    fn board_at_start_and_mid_turn() {
+        let mut game = crate::variant::standard();
+        let start = game.get_board(0, 0).unwrap();
+        assert_eq!((start.l, start.t), (0, 0));
+        assert_eq!(start.get(4, 0), Some(Piece::KingW));
+        assert!(game.get_board(0, 1).is_none());
+        assert!(game.get_board(1, 0).is_none());
+
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        let board = game.get_board(0, 1).unwrap();
+        assert_eq!(board.get(5, 2), Some(Piece::KnightW));
+        assert_eq!(game.get_board(0, 0).unwrap().get(6, 0), Some(Piece::KnightW));
+
+        // In the middle of black's turn, the board that black played on is found before the game's
+        let virtual_boards = Vec::new();
+        let mut assembler = TurnAssembler::new(&game, &virtual_boards, &game.info);
+        assembler.try_push_coords((0, 1, 6, 7), (0, 1, 5, 5)).unwrap();
+        let merged_vboards: Vec<&Board> = assembler.boards.iter().collect();
+        let turn = PartialTurn::snapshot(&game, &merged_vboards, &assembler.info);
+        assert_eq!(turn.board_at(0, 2).unwrap().get(5, 5), Some(Piece::KnightB));
+        assert!(turn.game.get_board(0, 2).is_none());
+        assert_eq!(turn.board_at(0, 1).unwrap().get(5, 2), Some(Piece::KnightW));
+        assert!(turn.board_at(0, 3).is_none());
+    }
+
+    /// Counts the kings of `boards`, which may be owned or borrowed
+    fn kings<B: AsRef<Board>>(boards: &[B]) -> usize {
+        boards.iter().map(|b| b.as_ref().pieces.iter().filter(|piece| piece.is_king()).count()).sum()
+    }
+
+    /// Collects `boards` into owned boards
+    fn owned<B: Into<Board>>(boards: Vec<B>) -> Vec<Board> {
+        boards.into_iter().map(Into::into).collect()
+    }
+
+    #[test]
+    fn owned_and_borrowed_boards() {
+        let game = crate::variant::standard();
+        let board = game.get_board(0, 0).unwrap();
+        let borrowed: Vec<&Board> = vec![board, board];
+        let copies: Vec<Board> = owned(borrowed.clone());
+        assert_eq!(kings(&borrowed), 4);
+        assert_eq!(kings(&copies), 4);
+        assert_eq!(kings(&owned(copies.clone())), 4);
+
+        let copy: Board = board.into();
+        assert_eq!(copy.pieces, board.pieces);
+        assert_eq!((copy.l, copy.t), (board.l, board.t));
+        assert_eq!(Board::from(&copy).pieces, board.pieces);
+    }
+
+    #[test]
+    fn material_of_starting_board() {
+        let values = PieceValues::default();
+        let game = crate::variant::standard();
+        assert_eq!(game.get_board(0, 0).unwrap().material(&values), 0);
+
+        // Without its queen, white is a queen behind; pieces are worth what `values` say
+        let game = testing::game(0, &["rnbqkbnr", "pppppppp", "........", "........", "........", "........", "PPPPPPPP", "RNB.KBNR"]);
+        let board = game.get_board(0, 0).unwrap();
+        assert_eq!(board.material(&values), -values.queen);
+        let values = PieceValues { queen: 1, pawn: 0, ..values };
+        assert_eq!(board.material(&values), -1);
+    }
+
+    #[test]
+    fn mobility_of_both_sides() {
+        // Each side has 20 moves, black's pieces not taking each other although it is white's turn
+        let game = crate::variant::standard();
+        let board = game.get_board(0, 0).unwrap();
+        assert_eq!(board.mobility(&game, &vec![]), 0);
+
+        // Black's rook may take white's queen but not its own king
+        let game = testing::game(0, &["r..k", "....", "....", "Q..K"]);
+        let board = game.get_board(0, 0).unwrap();
+        assert_eq!(board.mobility(&game, &vec![]), (8 + 3) - (5 + 3));
+    }
+
+    /// Asserts that `a` and `b` hold the same boards, and the same `GameInfo` but for the present
+    fn assert_same_boards(a: &Game, b: &Game) {
+        let mut timelines: Vec<i32> = a.timelines.keys().copied().collect();
+        timelines.sort_unstable();
+        let mut other: Vec<i32> = b.timelines.keys().copied().collect();
+        other.sort_unstable();
+        assert_eq!(timelines, other);
+        for l in timelines {
+            let (a, b) = (&a.timelines[&l], &b.timelines[&l]);
+            assert_eq!((a.begins_at, a.emerges_from), (b.begins_at, b.emerges_from));
+            assert_eq!(a.states.len(), b.states.len(), "timeline {}", l);
+            for (a, b) in a.states.iter().zip(b.states.iter()) {
+                assert_eq!(a.pieces, b.pieces, "board ({}T{})", a.l, a.t);
+            }
+        }
+        let info = |game: &Game| (game.info.active_player, game.info.min_timeline, game.info.max_timeline, game.info.even_initial_timelines);
+        assert_eq!(info(a), info(b));
+    }
+
+    #[test]
+    fn replay_reproduces_fixtures() {
+        for name in &["standard-complex-2.json", "mate-in-one.json"] {
+            let game = testing::fixture(name);
+            let mut positions = 0;
+            let mut last = None;
+            for (created, position) in game.replay() {
+                assert!(!created.is_empty());
+                assert!(position.timelines.values().map(|tl| tl.states.len()).sum::<usize>() > positions);
+                positions = position.timelines.values().map(|tl| tl.states.len()).sum();
+                last = Some(position);
+            }
+            match last {
+                Some(last) => assert_same_boards(&last, &game),
+                // A game of a single board has no turn to replay
+                None => assert_eq!(game.timelines.values().map(|tl| tl.states.len()).sum::<usize>(), 1),
+            }
+        }
+    }
+
+    #[test]
+    fn replay_yields_each_turn_played() {
+        let mut game = crate::variant::standard();
+        let mut played = Vec::new();
+        for moves in [
+            vec![((0, 0, 6, 0), (0, 0, 5, 2))],
+            vec![((0, 1, 6, 7), (0, 1, 5, 5))],
+            vec![((0, 2, 5, 2), (0, 0, 7, 2))],
+            vec![((1, 1, 6, 7), (0, 1, 6, 5))],
+            vec![((1, 2, 7, 2), (1, 2, 6, 4)), ((-1, 2, 4, 1), (-1, 2, 4, 3))],
+        ] {
+            testing::play(&mut game, &moves);
+            played.push(game.clone());
+        }
+
+        // The parsed game only holds the boards, from which the turns are inferred
+        let parsed = crate::parse::try_parse(&crate::parse::to_notation_tool_json(&game, &[])).unwrap();
+        let replayed: Vec<Game> = parsed.replay().map(|(_, position)| position).collect();
+        assert_eq!(replayed.len(), played.len());
+        for (replayed, played) in replayed.iter().zip(played.iter()) {
+            assert_same_boards(replayed, played);
+        }
+    }
+}
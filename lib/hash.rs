@@ -0,0 +1,410 @@
+// Position hashing, for use in opening books and transposition tables
+use crate::game::*;
+use std::collections::HashMap;
+
+/// A Zobrist-style hash of a position
+pub type ZobristKey = u64;
+
+const ACTIVE_PLAYER_KEY: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/**
+    A Zobrist hashing scheme: the pseudo-random values given to every (square, piece) pair are derived from a seed.
+
+    Two schemes with the same seed produce the same keys, across runs and machines; the default scheme uses the fixed seed `Zobrist::DEFAULT_SEED`,
+    which is the one used by the free functions of this module. Changing the seed changes every key, and thus invalidates the opening books and transposition tables that were persisted using another seed.
+**/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Zobrist {
+    seed: u64,
+}
+
+impl Default for Zobrist {
+    fn default() -> Self {
+        Self::with_seed(Self::DEFAULT_SEED)
+    }
+}
+
+impl Zobrist {
+    /// The seed of the default scheme
+    pub const DEFAULT_SEED: u64 = 0;
+
+    /// Creates a new hashing scheme whose values are derived from `seed`
+    pub fn with_seed(seed: u64) -> Self {
+        Zobrist { seed }
+    }
+
+    /// Returns the seed of that scheme
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /**
+        Returns the Zobrist key of the position made of `game`'s boards and `virtual_boards`.
+
+        Every (square, piece) pair of every board contributes a pseudo-random value, derived from its coordinates, which are XOR-ed together.
+        As such, the key does not depend on the order in which the boards were created, and adding a board to a position only requires XOR-ing its `board_key` in.
+    **/
+    pub fn key(&self, game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> ZobristKey {
+        let mut key: ZobristKey = if info.active_player { self.active_player_key() } else { 0 };
+
+        for tl in game.timelines.values() {
+            for board in tl.states.iter() {
+                key ^= self.board_key(board);
+            }
+        }
+        for board in virtual_boards {
+            key ^= self.board_key(board);
+        }
+
+        key
+    }
+
+    /// Returns the contribution of a single board to a position's Zobrist key
+    pub fn board_key(&self, board: &Board) -> ZobristKey {
+        self.board_key_at(board, board.l, board.t)
+    }
+
+    /**
+        Returns the Zobrist key of the position reached by playing a moveset, given the key of the position it is played from: `boards` are the boards created by the moveset,
+        `info` is the `GameInfo` of the position played from and `new_info` the resulting one.
+    **/
+    pub fn update_key(&self, key: ZobristKey, boards: &[Board], info: &GameInfo, new_info: &GameInfo) -> ZobristKey {
+        let mut key = key;
+        if info.active_player != new_info.active_player {
+            key ^= self.active_player_key();
+        }
+        boards.iter().fold(key, |key, board| key ^ self.board_key(board))
+    }
+
+    /// Same as `canonical_key`, using this scheme's values
+    pub fn canonical_key(&self, game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> CanonicalKey {
+        let mut timelines: Vec<(i32, isize, bool)> = game
+            .timelines
+            .values()
+            .map(|tl| (tl.index, tl.begins_at, tl.emerges_from.is_some()))
+            .collect();
+        // The boards of the timelines created by `virtual_boards`
+        for board in virtual_boards.iter().filter(|b| !game.timelines.contains_key(&b.l)) {
+            timelines.push((board.l, board.t, true));
+        }
+
+        let mut key: CanonicalKey = if info.active_player { self.active_player_key() } else { 0 };
+        let mapping = match normalized_indices(&timelines) {
+            Some((mapping, _)) => mapping,
+            None => return key,
+        };
+
+        let boards = game.timelines.values().flat_map(|tl| tl.states.iter()).chain(virtual_boards.iter().copied());
+        for board in boards {
+            key ^= self.board_key_at(board, mapping[&board.l], board.t);
+        }
+
+        key
+    }
+
+    /// Returns the contribution of a board to a position's key, as if it was at `(l, t)`
+    fn board_key_at(&self, board: &Board, l: i32, t: isize) -> ZobristKey {
+        let mut key: ZobristKey = 0;
+        for (index, piece) in board.pieces.iter().enumerate() {
+            if piece.is_blank() {
+                continue;
+            }
+            let (x, y) = match board.square_of(index) {
+                Some(square) => square,
+                None => break,
+            };
+            key ^= self.square_key((l, t, x, y), usize::from(*piece) as u64);
+        }
+
+        let castle = (board.castle_w.0 as u64)
+            | (board.castle_w.1 as u64) << 1
+            | (board.castle_b.0 as u64) << 2
+            | (board.castle_b.1 as u64) << 3;
+        if castle != 0 {
+            key ^= self.square_key((l, t, 0xff, 0xff), 0x100 | castle);
+        }
+
+        key
+    }
+
+    /// Value toggled in when white is the active player; it is `ACTIVE_PLAYER_KEY` for the default seed
+    fn active_player_key(&self) -> u64 {
+        ACTIVE_PLAYER_KEY ^ splitmix64(self.seed) ^ splitmix64(Self::DEFAULT_SEED)
+    }
+
+    /// Pseudo-random value associated with a piece on a square
+    fn square_key(&self, coords: Coords, piece: u64) -> u64 {
+        let mut h = splitmix64(self.seed ^ coords.0 as i64 as u64);
+        h = splitmix64(h ^ coords.1 as i64 as u64);
+        h = splitmix64(h ^ ((coords.2 as u64) << 8 | coords.3 as u64));
+        splitmix64(h ^ piece)
+    }
+}
+
+/// Returns the Zobrist key of the position made of `game`'s boards and `virtual_boards`, using the default scheme (see `Zobrist::key`)
+pub fn zobrist_key(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> ZobristKey {
+    Zobrist::default().key(game, virtual_boards, info)
+}
+
+/// Returns the contribution of a single board to a position's Zobrist key, using the default scheme
+pub fn board_key(board: &Board) -> ZobristKey {
+    Zobrist::default().board_key(board)
+}
+
+/// A hash of a position that does not depend on the indices of its timelines, see `canonical_key`
+pub type CanonicalKey = u64;
+
+/**
+    Returns a key of the position made of `game`'s boards and `virtual_boards`, which is equal for positions that only differ by the indices of their timelines (eg. the same puzzle set up at another timeline).
+
+    Each board is hashed at the index given to its timeline by `Game::normalize_timelines`: the initial timelines are shifted so that the highest of them is `0`,
+    and the other timelines are numbered after them in the order of their indices, without gaps. As this keeps the order of the timelines, positions whose timelines are laid out differently still have different keys.
+    Like the Zobrist key, the key does not depend on the order in which the moves of a turn were played.
+**/
+pub fn canonical_key(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> CanonicalKey {
+    Zobrist::default().canonical_key(game, virtual_boards, info)
+}
+
+/// The SplitMix64 mixing function
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/**
+    Counts how many times each position was reached during a game, to enforce repetition rules.
+    Positions are compared by their full Zobrist key (see `Zobrist::key`): every board of every timeline, along with the active player.
+    The table is updated incrementally as movesets are played, see `RepetitionTable::apply`; positions reached in another way (eg. by taking turns back) are counted with `RepetitionTable::record`.
+**/
+#[derive(Debug, Clone)]
+pub struct RepetitionTable {
+    scheme: Zobrist,
+    counts: HashMap<ZobristKey, u8>,
+    current: ZobristKey,
+}
+
+impl RepetitionTable {
+    /// Creates a new table, using the default hashing scheme, in which the given position was reached once
+    pub fn new(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> Self {
+        Self::with_scheme(Zobrist::default(), game, virtual_boards, info)
+    }
+
+    /// Creates a new table, using the hashing scheme `scheme`, in which the given position was reached once
+    pub fn with_scheme(scheme: Zobrist, game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> Self {
+        let current = scheme.key(game, virtual_boards, info);
+        let mut counts = HashMap::new();
+        counts.insert(current, 1);
+        RepetitionTable { scheme, counts, current }
+    }
+
+    /**
+        Records the position reached by playing a moveset from the current position: `boards` are the boards created by the moveset,
+        `info` is the `GameInfo` of the current position and `new_info` the resulting one. Returns the number of times that the new position was reached.
+    **/
+    pub fn apply(&mut self, boards: &[Board], info: &GameInfo, new_info: &GameInfo) -> u8 {
+        let key = self.scheme.update_key(self.current, boards, info, new_info);
+        self.visit(key)
+    }
+
+    /// Records that the position made of `game`, `virtual_boards` and `info` was reached, making it the current position; returns the number of times that it was reached
+    pub fn record(&mut self, game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> u8 {
+        let key = self.scheme.key(game, virtual_boards, info);
+        self.visit(key)
+    }
+
+    fn visit(&mut self, key: ZobristKey) -> u8 {
+        self.current = key;
+        let count = self.counts.entry(key).or_insert(0);
+        *count = count.saturating_add(1);
+        *count
+    }
+
+    /// Returns the number of times that the position whose Zobrist key is `key` was reached
+    pub fn count(&self, key: ZobristKey) -> u8 {
+        self.counts.get(&key).copied().unwrap_or(0)
+    }
+
+    /// Returns the Zobrist key of the current position
+    pub fn current(&self) -> ZobristKey {
+        self.current
+    }
+
+    /// Returns whether the current position was reached three times or more
+    pub fn is_draw_by_repetition(&self) -> bool {
+        self.count(self.current) >= 3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moveset::{Moveset, TurnAssembler};
+    use crate::testing;
+
+    /// Returns a copy of `game` whose timelines are all `by` timelines higher
+    fn shifted(game: &Game, by: i32) -> Game {
+        let mut res = game.clone();
+        res.timelines = game
+            .timelines
+            .values()
+            .map(|tl| {
+                let mut tl = tl.clone();
+                tl.index += by;
+                tl.emerges_from = tl.emerges_from.map(|l| l + by);
+                tl.states.iter_mut().for_each(|b| b.l += by);
+                (tl.index, tl)
+            })
+            .collect();
+        res.info.min_timeline += by;
+        res.info.max_timeline += by;
+        res
+    }
+
+    #[test]
+    fn canonical_key_transpositions() {
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        testing::play(&mut game, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+        testing::play(&mut game, &[((1, 1, 6, 7), (0, 1, 6, 5))]);
+
+        // The same turn, with its moves on timelines -1 and 1 played in both orders
+        let moves = [((1, 2, 7, 2), (1, 2, 6, 4)), ((-1, 2, 4, 1), (-1, 2, 4, 3))];
+        let virtual_boards = Vec::new();
+        let keys: Vec<CanonicalKey> = [[0, 1], [1, 0]]
+            .iter()
+            .map(|order| {
+                let mut assembler = TurnAssembler::new(&game, &virtual_boards, &game.info);
+                for &i in order {
+                    assembler.try_push_coords(moves[i].0, moves[i].1).unwrap();
+                }
+                let boards: Vec<&Board> = assembler.boards.iter().collect();
+                canonical_key(&game, &boards, &assembler.info)
+            })
+            .collect();
+        assert_eq!(keys[0], keys[1]);
+        assert_ne!(keys[0], canonical_key(&game, &vec![], &game.info));
+
+        // The same position, set up three timelines higher
+        let shifted = shifted(&game, 3);
+        assert_ne!(zobrist_key(&shifted, &vec![], &shifted.info), zobrist_key(&game, &vec![], &game.info));
+        assert_eq!(canonical_key(&shifted, &vec![], &shifted.info), canonical_key(&game, &vec![], &game.info));
+        let normalized = shifted.normalize_timelines();
+        assert_eq!(zobrist_key(&normalized, &vec![], &normalized.info), zobrist_key(&game, &vec![], &game.info));
+    }
+
+    #[test]
+    fn canonical_key_of_shifted_games() {
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        testing::play(&mut game, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+        testing::play(&mut game, &[((1, 1, 6, 7), (0, 1, 6, 5))]);
+        let layout = |game: &Game| {
+            let mut res: Vec<(i32, isize, Option<i32>, usize)> =
+                game.timelines.values().map(|tl| (tl.index, tl.begins_at, tl.emerges_from, tl.states.len())).collect();
+            res.sort_unstable();
+            res
+        };
+        let normalized = game.normalize_timelines();
+
+        for &seed in &[Zobrist::DEFAULT_SEED, 42] {
+            let scheme = Zobrist::with_seed(seed);
+            for &by in &[-5, 3, 17] {
+                let shifted = shifted(&game, by);
+                assert_ne!(scheme.key(&shifted, &vec![], &shifted.info), scheme.key(&game, &vec![], &game.info));
+                assert_eq!(
+                    scheme.canonical_key(&shifted, &vec![], &shifted.info),
+                    scheme.canonical_key(&game, &vec![], &game.info)
+                );
+                let renormalized = shifted.normalize_timelines();
+                assert_eq!(layout(&renormalized), layout(&normalized));
+                assert_eq!(renormalized.info.present, normalized.info.present);
+                assert_eq!(scheme.key(&renormalized, &vec![], &renormalized.info), scheme.key(&normalized, &vec![], &normalized.info));
+
+                // Halfway through the same turn, played on both games
+                let turn = |game: &Game, by: i32| {
+                    let virtual_boards = Vec::new();
+                    let mut assembler = TurnAssembler::new(game, &virtual_boards, &game.info);
+                    assembler.try_push_coords((1 + by, 2, 7, 2), (1 + by, 2, 6, 4)).unwrap();
+                    let boards: Vec<&Board> = assembler.boards.iter().collect();
+                    scheme.canonical_key(game, &boards, &assembler.info)
+                };
+                assert_eq!(turn(&shifted, by), turn(&game, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn golden_keys() {
+        // Changing these values invalidates the books and tables persisted with either seed
+        let game = crate::variant::standard();
+        let board = game.get_board(0, 0).unwrap();
+        let keys = |seed| {
+            let scheme = Zobrist::with_seed(seed);
+            (scheme.key(&game, &vec![], &game.info), scheme.board_key(board))
+        };
+        assert_eq!(keys(Zobrist::DEFAULT_SEED), (0x7070_0a42_89b9_1b8a, 0xee47_73fb_f6f3_679f));
+        assert_eq!(keys(42), (0x7eaa_ef71_053a_6e16, 0xbf6a_0cd7_2e86_b139));
+        assert_eq!(zobrist_key(&game, &vec![], &game.info), keys(Zobrist::DEFAULT_SEED).0);
+        assert_eq!(Zobrist::default(), Zobrist::with_seed(Zobrist::DEFAULT_SEED));
+    }
+
+    #[test]
+    fn canonical_key_keeps_layout() {
+        let ranks_a = ["k...", "....", "...K"];
+        let ranks_b = ["k...", ".R..", "...K"];
+        let build = |first: &[&str], second: &[&str]| {
+            GameBuilder::new(4, 3)
+                .add_board(0, 0, testing::board(0, 0, &ranks_a))
+                .add_board(0, 1, testing::board(0, 1, &ranks_a))
+                .add_board(1, 1, testing::board(1, 1, first))
+                .emerges_from(1, 0)
+                .add_board(2, 1, testing::board(2, 1, second))
+                .emerges_from(2, 0)
+                .build()
+                .unwrap()
+        };
+
+        // Swapping the contents of the two created timelines changes their distance to the other timelines
+        let game = build(&ranks_a, &ranks_b);
+        let swapped = build(&ranks_b, &ranks_a);
+        assert_ne!(canonical_key(&game, &vec![], &game.info), canonical_key(&swapped, &vec![], &swapped.info));
+    }
+
+    #[test]
+    fn repetition_table_back_and_forth() {
+        let mut driver = crate::driver::GameDriver::new(crate::variant::standard());
+        let start = driver.game().clone();
+        let mut table = RepetitionTable::new(&start, &vec![], &start.info);
+        let start_key = table.current();
+
+        // Playing a turn updates the key incrementally
+        let moveset = {
+            let virtual_boards = Vec::new();
+            let mut assembler = TurnAssembler::new(&start, &virtual_boards, &start.info);
+            assembler.try_push_coords((0, 0, 6, 0), (0, 0, 5, 2)).unwrap();
+            let (moves, boards, mut info) = assembler.finalize().unwrap();
+            info.active_player = false;
+            assert_eq!(table.apply(&boards, &start.info, &info), 1);
+            Moveset(moves)
+        };
+        driver.submit(&moveset).unwrap();
+        assert_eq!(table.current(), zobrist_key(driver.game(), &vec![], &driver.game().info));
+        assert!(!table.is_draw_by_repetition());
+
+        // Going back and forth with undo and redo comes back to the starting position
+        driver.undo().unwrap();
+        assert_eq!(table.record(driver.game(), &vec![], &driver.game().info), 2);
+        assert_eq!(table.current(), start_key);
+        driver.redo().unwrap();
+        assert_eq!(table.record(driver.game(), &vec![], &driver.game().info), 2);
+        assert!(!table.is_draw_by_repetition());
+        driver.undo().unwrap();
+        assert_eq!(table.record(driver.game(), &vec![], &driver.game().info), 3);
+        assert_eq!(table.count(start_key), 3);
+        assert!(table.is_draw_by_repetition());
+    }
+}
@@ -14,3 +14,14 @@ pub mod resolve;
 pub mod tree;
 pub mod parse;
 pub mod vboard;
+pub mod hash;
+pub mod book;
+pub mod random;
+pub mod variant;
+pub mod driver;
+pub mod selfplay;
+pub mod puzzle;
+pub mod prelude;
+
+#[cfg(test)]
+mod testing;
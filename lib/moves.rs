@@ -1,8 +1,12 @@
 // Handles moves
-use super::{game::*, moveset::*, resolve::*};
+use super::{game::*, hash::*, moveset::*, random::*, resolve::*};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::iter::FusedIterator;
 
-// Generate permutations for the basic, symmetric piece movements
+// Generate permutations for the basic, symmetric piece movements; see `Direction5D`
 lazy_static! {
     pub static ref PERMUTATIONS: Vec<Vec<(isize, isize, isize, isize)>> = {
         [
@@ -67,9 +71,84 @@ lazy_static! {
         })
         .collect()
     };
+    static ref DIRECTIONS: Vec<Vec<Direction5D>> = PERMUTATIONS
+        .iter()
+        .map(|group| group.iter().map(|d| Direction5D::from(*d)).collect())
+        .collect();
+}
+
+/**
+    A direction in which pieces move, as an offset along the four axes of the game: timelines (`l`), time (`t`, counted in full turns), files (`x`) and ranks (`y`).
+    The directions of the symmetric pieces are enumerated by `rook`, `bishop`, `unicorn`, `dragon` and `knight`; sliding pieces move any number of times along their direction, knights only once.
+**/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Direction5D {
+    pub l: isize,
+    pub t: isize,
+    pub x: isize,
+    pub y: isize,
+}
+
+impl Direction5D {
+    /// Creates a new direction, `t` being counted in full turns
+    pub fn new(l: isize, t: isize, x: isize, y: isize) -> Self {
+        Direction5D { l, t, x, y }
+    }
+
+    /// Returns the directions of the `n`-th n-gonal: `0` for the knight, then `1` to `4` for the rook, bishop, unicorn and dragon
+    pub fn n_gonal(n: usize) -> &'static [Direction5D] {
+        &DIRECTIONS[n]
+    }
+
+    /// The 48 directions of knights
+    pub fn knight() -> &'static [Direction5D] {
+        Self::n_gonal(0)
+    }
+
+    /// The 8 directions of rooks, along a single axis
+    pub fn rook() -> &'static [Direction5D] {
+        Self::n_gonal(1)
+    }
+
+    /// The 24 directions of bishops, along two axes
+    pub fn bishop() -> &'static [Direction5D] {
+        Self::n_gonal(2)
+    }
+
+    /// The 32 directions of unicorns, along three axes
+    pub fn unicorn() -> &'static [Direction5D] {
+        Self::n_gonal(3)
+    }
+
+    /// The 16 directions of dragons, along all four axes
+    pub fn dragon() -> &'static [Direction5D] {
+        Self::n_gonal(4)
+    }
+
+    /// The 80 directions of queens and kings: those of the rook, bishop, unicorn and dragon
+    pub fn queen() -> impl Iterator<Item = Direction5D> {
+        (1..=4).flat_map(|n| Self::n_gonal(n).iter().copied())
+    }
+
+    /// Returns the number of axes along which this direction moves
+    pub fn axes(&self) -> usize {
+        [self.l, self.t, self.x, self.y].iter().filter(|d| **d != 0).count()
+    }
+}
+
+impl From<(isize, isize, isize, isize)> for Direction5D {
+    fn from((l, t, x, y): (isize, isize, isize, isize)) -> Self {
+        Direction5D { l, t, x, y }
+    }
+}
+
+impl From<Direction5D> for (isize, isize, isize, isize) {
+    fn from(direction: Direction5D) -> Self {
+        (direction.l, direction.t, direction.x, direction.y)
+    }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Move {
     pub src: (i32, isize, u8, u8), // l, t, x, y
     pub dst: (i32, isize, u8, u8), // l, t, x, y
@@ -81,6 +160,33 @@ pub struct Move {
     pub noop: bool,
 }
 
+/**
+    The information needed to revert a board to its state before a move was played on it, see `Move::inverse`.
+    Only the squares touched by the move are stored, rather than a snapshot of the whole board.
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct InverseMove {
+    pub l: i32,
+    pub t: isize,
+    pub squares: Vec<(u8, u8, Piece)>, // x, y, piece before the move
+    /// The castling rights of the board before the move, which may lose some of them (see `Board::revoke_castling_rights`)
+    pub castle_w: (bool, bool),
+    pub castle_b: (bool, bool),
+}
+
+impl InverseMove {
+    /// Reverts `board`, one of the boards generated by the move, to the board that `inverse` was given
+    pub fn undo(&self, board: &mut Board) {
+        board.l = self.l;
+        board.t = self.t;
+        board.castle_w = self.castle_w;
+        board.castle_b = self.castle_b;
+        for (x, y, piece) in self.squares.iter().rev() {
+            board.set_unsafe(*x, *y, *piece);
+        }
+    }
+}
+
 impl fmt::Debug for Move {
     /// Prints out a move in semi-readable format; does not match any of the existing and known notations
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -190,6 +296,39 @@ impl fmt::Debug for Move {
     }
 }
 
+/**
+    Writes a move in long notation: the board it is played from as `(<l>T<turn>)` (with `turn = t / 2 + 1`), the letter of the piece (omitted for pawns),
+    its source square and its destination square, preceded by `x` if the move takes a piece; eg. `(0T1)Ng1f3` or `(0T3)e4xd5`.
+    Moves to another board write the destination board after `>>`, whether they create a new timeline or not, eg. `(0T2)Nf3>>(0T1)h3`.
+    Castling is written `(0T5)O-O` or `(0T5)O-O-O`, noops `_`.
+**/
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.noop {
+            return write!(f, "_");
+        }
+        write!(f, "({}T{})", self.src.0, self.src.1 / 2 + 1)?;
+        if self.castle {
+            return write!(f, "{}", if self.castle_long { "O-O-O" } else { "O-O" });
+        }
+
+        if !self.src_piece.is_pawn() {
+            write!(f, "{}", self.src_piece.as_uppercase())?;
+        }
+        write!(f, "{}{}", write_file(self.src.2), self.src.3 + 1)?;
+        if self.src.0 != self.dst.0 || self.src.1 != self.dst.1 {
+            write!(f, ">>")?;
+        }
+        if self.captured().is_some() {
+            write!(f, "x")?;
+        }
+        if self.src.0 != self.dst.0 || self.src.1 != self.dst.1 {
+            write!(f, "({}T{})", self.dst.0, self.dst.1 / 2 + 1)?;
+        }
+        write!(f, "{}{}", write_file(self.dst.2), self.dst.3 + 1)
+    }
+}
+
 impl Move {
     /// Creates a new normal move; extracts piece information from `game` and `virtual_boards`
     pub fn new(
@@ -205,12 +344,13 @@ impl Move {
             dst,
             castle: false,
             castle_long: false,
-            en_passant: if dst.3 != src.3 || !src_piece.is_pawn() || !dst_piece.is_blank() {
+            // A pawn taking on an empty square of its own board can only take en passant
+            en_passant: if (src.0, src.1) != (dst.0, dst.1) || dst.2 == src.2 || !src_piece.is_pawn() || !dst_piece.is_blank() {
                 None
             } else {
                 Some((
                     dst.2,
-                    if src_piece.is_blank() {
+                    if src_piece.is_white() {
                         dst.3 - 1
                     } else {
                         dst.3 + 1
@@ -238,12 +378,13 @@ impl Move {
             dst,
             castle: false,
             castle_long: false,
-            en_passant: if dst.3 != src.3 || !src_piece.is_pawn() || !dst_piece.is_blank() {
+            // A pawn taking on an empty square of its own board can only take en passant
+            en_passant: if (src.0, src.1) != (dst.0, dst.1) || dst.2 == src.2 || !src_piece.is_pawn() || !dst_piece.is_blank() {
                 None
             } else {
                 Some((
                     dst.2,
-                    if src_piece.is_blank() {
+                    if src_piece.is_white() {
                         dst.3 - 1
                     } else {
                         dst.3 + 1
@@ -290,6 +431,70 @@ impl Move {
         }
     }
 
+    /// Returns the piece taken by this move, if any; unlike `dst_piece`, this accounts for castling (where `dst_piece` is the rook) and en passant
+    pub fn captured(&self) -> Option<Piece> {
+        if self.noop || self.castle {
+            None
+        } else if self.en_passant.is_some() {
+            Some(if self.src_piece.is_white() {
+                Piece::PawnB
+            } else {
+                Piece::PawnW
+            })
+        } else if self.dst_piece.is_blank() {
+            None
+        } else {
+            Some(self.dst_piece)
+        }
+    }
+
+    /// Returns the squares that the king and the rook are moved to when castling
+    fn castle_squares(&self, width: u8) -> ((u8, u8), (u8, u8)) {
+        (
+            (if self.castle_long { 2 } else { width - 2 }, self.src.3),
+            (if self.castle_long { 3 } else { width - 3 }, self.dst.3),
+        )
+    }
+
+    /**
+        Returns the `InverseMove` reverting the changes made by this move to `board_before`, which must either be its source or its target board.
+        The squares that the move touches (including the piece captured by it, if any) are recorded with their content prior to the move.
+    **/
+    pub fn inverse(&self, board_before: &Board) -> InverseMove {
+        let mut squares: Vec<(u8, u8)> = Vec::new();
+
+        if !self.noop {
+            let on_src = board_before.l == self.src.0 && board_before.t == self.src.1;
+            let on_dst = board_before.l == self.dst.0 && board_before.t == self.dst.1;
+
+            if on_src {
+                squares.push((self.src.2, self.src.3));
+            }
+            if self.castle && on_src {
+                let (king, rook) = self.castle_squares(board_before.width);
+                squares.push((self.dst.2, self.dst.3));
+                squares.push(king);
+                squares.push(rook);
+            } else if on_dst {
+                if let Some(en_passant) = self.en_passant {
+                    squares.push(en_passant);
+                }
+                squares.push((self.dst.2, self.dst.3));
+            }
+        }
+
+        InverseMove {
+            l: board_before.l,
+            t: board_before.t,
+            castle_w: board_before.castle_w,
+            castle_b: board_before.castle_b,
+            squares: squares
+                .into_iter()
+                .filter_map(|(x, y)| board_before.get(x, y).map(|piece| (x, y, piece)))
+                .collect(),
+        }
+    }
+
     /// Generate the boards that are created as a result of the move being played out. The target and source boards must be present in either `game`, `virtual_boards` or `already_generated`
     pub fn generate_vboards(
         &self,
@@ -297,13 +502,37 @@ impl Move {
         info: &GameInfo,
         virtual_boards: &Vec<&Board>,
         already_generated: &Vec<Board>,
+    ) -> Option<(GameInfo, Vec<Board>)> {
+        self.generate_vboards_with(game, info, virtual_boards, already_generated, Board::clone)
+    }
+
+    /// Same as `generate_vboards`, but the new boards' piece arrays are taken from `pool`; they may be handed back to it once the boards are no longer needed
+    pub fn generate_vboards_pooled(
+        &self,
+        game: &Game,
+        info: &GameInfo,
+        virtual_boards: &Vec<&Board>,
+        already_generated: &Vec<Board>,
+        pool: &mut BoardPool,
+    ) -> Option<(GameInfo, Vec<Board>)> {
+        self.generate_vboards_with(game, info, virtual_boards, already_generated, |board| pool.clone_board(board))
+    }
+
+    /// Generates the boards created by the move, `clone_board` being used to copy the source and target boards
+    fn generate_vboards_with<F: FnMut(&Board) -> Board>(
+        &self,
+        game: &Game,
+        info: &GameInfo,
+        virtual_boards: &Vec<&Board>,
+        already_generated: &Vec<Board>,
+        mut clone_board: F,
     ) -> Option<(GameInfo, Vec<Board>)> {
         // TODO: properly handle Board::set's result
         if self.noop {
             return Some((info.clone(), vec![]));
         }
 
-        let mut new_board = get_board(game, virtual_boards, (self.src.0, self.src.1))?.clone();
+        let mut new_board = clone_board(get_board(game, virtual_boards, (self.src.0, self.src.1))?);
 
         if !is_last(game, virtual_boards, &new_board)
             || already_generated
@@ -316,12 +545,14 @@ impl Move {
 
         if self.castle {
             new_board.t += 1;
+            new_board.revoke_castling_rights(self.src.2, self.src.3, self.src_piece);
             new_board.set(self.src.2, self.src.3, Piece::Blank).unwrap();
             new_board.set(self.dst.2, self.dst.3, Piece::Blank).unwrap();
 
+            let (king, rook) = self.castle_squares(game.width);
             new_board.set(
-                self.src.2,
-                if self.castle_long { 2 } else { game.width - 2 },
+                king.0,
+                king.1,
                 if new_board.active_player() {
                     Piece::KingB
                 } else {
@@ -329,8 +560,8 @@ impl Move {
                 },
             ).unwrap();
             new_board.set(
-                self.dst.2,
-                if self.castle_long { 3 } else { game.width - 3 },
+                rook.0,
+                rook.1,
                 if new_board.active_player() {
                     Piece::RookB
                 } else {
@@ -348,19 +579,14 @@ impl Move {
             if self.src.0 == self.dst.0 && self.src.1 == self.dst.1 {
                 // Non-branching move
                 new_board.t += 1;
+                new_board.revoke_castling_rights(self.src.2, self.src.3, self.src_piece);
+                new_board.revoke_castling_rights(self.dst.2, self.dst.3, self.dst_piece);
                 new_board.set(self.src.2, self.src.3, Piece::Blank).unwrap();
                 new_board.set(self.dst.2, self.dst.3, self.src_piece).unwrap();
 
                 let info = info.clone();
 
-                if self.src_piece.is_pawn()
-                    && self.dst.3
-                        == if self.src_piece.is_white() {
-                            new_board.height - 1
-                        } else {
-                            0
-                        }
-                {
+                if self.src_piece.is_pawn() && self.dst.3 == game.promotion_rank(self.src_piece.is_white()) {
                     new_board.set(
                         self.dst.2,
                         self.dst.3,
@@ -390,8 +616,7 @@ impl Move {
                 Some((info, vec![new_board]))
             } else {
                 let mut new_src_board = new_board;
-                let mut new_dst_board =
-                    get_board(game, virtual_boards, (self.dst.0, self.dst.1))?.clone();
+                let mut new_dst_board = clone_board(get_board(game, virtual_boards, (self.dst.0, self.dst.1))?);
 
                 let mut new_info = info.clone();
                 if !is_last(game, virtual_boards, &new_dst_board)
@@ -412,8 +637,9 @@ impl Move {
                 new_src_board.t += 1;
                 new_dst_board.t += 1;
                 // TODO: timeline reactivation
-                if new_dst_board.t < new_info.present && new_dst_board.is_active(&new_info) {
-                    new_info.present = new_dst_board.t;
+                // `present` is the time of the active player's boards until the turn is over: a new timeline moves it back to the board it branches off
+                if new_dst_board.t <= new_info.present && new_dst_board.is_active(&new_info) {
+                    new_info.present = new_dst_board.t - 1;
                 }
 
                 if if self.src_piece.is_white() {
@@ -421,9 +647,13 @@ impl Move {
                 } else {
                     -info.min_timeline < info.max_timeline
                 } {
-                    new_info.present = find_present(game, virtual_boards, info);
+                    // The new timeline reactivates one of the opponent's; using `new_info` keeps the present that the new board may have moved back,
+                    // so that the boards it leaves behind (eg. one whose king is in check) no longer have to be played on
+                    new_info.present = find_present(game, virtual_boards, &new_info);
                 }
 
+                new_src_board.revoke_castling_rights(self.src.2, self.src.3, self.src_piece);
+                new_dst_board.revoke_castling_rights(self.dst.2, self.dst.3, self.dst_piece);
                 new_src_board.set(self.src.2, self.src.3, Piece::Blank).unwrap();
                 new_dst_board.set(self.dst.2, self.dst.3, self.src_piece).unwrap();
 
@@ -433,25 +663,171 @@ impl Move {
     }
 }
 
-/// Returns the set of moves that can be made from `board`; does not check for the legality of said move (ie. if it puts the player in check)
+/**
+    Returns the set of moves that can be made from `board`; does not check for the legality of said move (ie. if it puts the player in check).
+    These pseudo-legal moves may thus include illegal moves; the (more expensive) legality check is done when combining them into movesets, see `legal_movesets`.
+**/
 pub fn probable_moves(game: &Game, board: &Board, virtual_boards: &Vec<&Board>) -> Vec<Move> {
+    probable_moves_filtered(game, board, virtual_boards, |_, _| true)
+}
+
+/**
+    Statistics about move generation, per piece kind (as given by `Piece::as_uppercase`): the number of moves generated and the time spent generating them.
+    Only available with the `gen-stats` feature, see `probable_moves_with_stats`.
+**/
+#[cfg(feature = "gen-stats")]
+#[derive(Debug, Clone, Default)]
+pub struct GenStats {
+    pub per_kind: HashMap<&'static str, (usize, std::time::Duration)>,
+    /// The number of squares looked at, to find the piece kinds and to generate their moves
+    pub squares_scanned: usize,
+}
+
+#[cfg(feature = "gen-stats")]
+impl GenStats {
+    /// Creates empty statistics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of moves generated, all kinds included
+    pub fn total_moves(&self) -> usize {
+        self.per_kind.values().map(|(n, _)| n).sum()
+    }
+
+    /// Returns the time spent generating moves, all kinds included
+    pub fn total_time(&self) -> std::time::Duration {
+        self.per_kind.values().map(|(_, d)| *d).sum()
+    }
+}
+
+#[cfg(feature = "gen-stats")]
+impl fmt::Display for GenStats {
+    /// Writes one line per piece kind, the kinds which produced the most moves first
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut kinds: Vec<_> = self.per_kind.iter().collect();
+        kinds.sort_by_key(|(kind, (n, _))| (std::cmp::Reverse(*n), **kind));
+        for (kind, (n, duration)) in kinds {
+            writeln!(f, "{}: {} moves in {:?}", kind, n, duration)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+    Same as `probable_moves`, but the moves of each piece kind are generated separately and tallied in `stats`; castling moves are counted as king moves.
+    The moves are grouped by piece kind, and as such are not in the same order as the ones of `probable_moves`. Only available with the `gen-stats` feature.
+**/
+#[cfg(feature = "gen-stats")]
+pub fn probable_moves_with_stats(
+    game: &Game,
+    board: &Board,
+    virtual_boards: &Vec<&Board>,
+    stats: &mut GenStats,
+) -> Vec<Move> {
+    if !board.has_pieces_of(board.active_player()) {
+        return Vec::new();
+    }
+    let n_squares = board.width as usize * board.height as usize;
+    stats.squares_scanned += n_squares;
+    let mut kinds: Vec<&'static str> = board
+        .pieces
+        .iter()
+        .filter(|p| p.is_own_piece(board.active_player()))
+        .map(|p| p.as_uppercase())
+        .collect();
+    kinds.sort_unstable();
+    kinds.dedup();
+
     let mut res: Vec<Move> = Vec::new();
+    for kind in kinds {
+        let start = std::time::Instant::now();
+        stats.squares_scanned += n_squares;
+        let mut moves = probable_moves_filtered(game, board, virtual_boards, |piece, _| piece.as_uppercase() == kind);
+        let entry = stats.per_kind.entry(kind).or_default();
+        entry.0 += moves.len();
+        entry.1 += start.elapsed();
+        res.append(&mut moves);
+    }
+    res
+}
 
-    for y in 0..board.height {
-        for x in 0..board.width {
-            if let Some(piece) = board.get(x, y) {
-                if if board.active_player() {
-                    piece.is_white()
-                } else {
-                    piece.is_black()
-                } {
-                    probable_moves_for(game, board, virtual_boards, &mut res, piece, x, y).unwrap();
+/**
+    Same as `probable_moves`, but only the pieces for which `predicate` returns true are moved; castling moves are generated if the king is selected.
+    Useful to only look at some pieces, eg. at the king and the pieces that could block a check.
+    Boards on which the active player has no piece left, such as the boards of timelines where every piece was taken, yield no move without their squares being looked up (see `Board::has_pieces_of`).
+**/
+pub fn probable_moves_filtered<F>(
+    game: &Game,
+    board: &Board,
+    virtual_boards: &Vec<&Board>,
+    predicate: F,
+) -> Vec<Move>
+where
+    F: Fn(Piece, Coords) -> bool,
+{
+    probable_moves_scanned_filtered(game, board, virtual_boards, ScanOrder::RowMajor, predicate)
+}
+
+/**
+    Same as `probable_moves_filtered`, with the pieces of `board` looked at in the given `order` (see `ScanOrder`).
+    The castling moves are still generated last.
+**/
+fn probable_moves_scanned_filtered<F>(
+    game: &Game,
+    board: &Board,
+    virtual_boards: &Vec<&Board>,
+    order: ScanOrder,
+    predicate: F,
+) -> Vec<Move>
+where
+    F: Fn(Piece, Coords) -> bool,
+{
+    let mut res: Vec<Move> = Vec::new();
+    if !board.has_pieces_of(board.active_player()) {
+        return res;
+    }
+
+    let mut visit = |x: u8, y: u8| {
+        if let Some(piece) = board.get(x, y) {
+            if if board.active_player() {
+                piece.is_white()
+            } else {
+                piece.is_black()
+            } && predicate(piece, (board.l, board.t, x, y))
+            {
+                probable_moves_for(game, board, virtual_boards, &mut res, piece, x, y).unwrap();
+            }
+        }
+    };
+    match order {
+        ScanOrder::RowMajor => {
+            for y in 0..board.height {
+                for x in 0..board.width {
+                    visit(x, y);
                 }
             }
         }
+        _ => {
+            for (x, y) in order.squares(board) {
+                visit(x, y);
+            }
+        }
+    }
+    if game.travel_rules != TravelRules::default() {
+        res.retain(|mv| is_travel_allowed(game, virtual_boards, mv));
     }
 
-    if board.active_player() && board.width > 5 {
+    let king_selected = |king: Piece, position: Option<(u8, u8)>| {
+        position.is_some_and(|(x, y)| predicate(king, (board.l, board.t, x, y)))
+    };
+    // The king may neither castle out of check nor through or into an attacked square
+    let path_safe = |king: (u8, u8), long: bool, white: bool| {
+        let king_dst = if long { 2 } else { board.width - 2 };
+        !is_castling_path_attacked(game, board, virtual_boards, king, king_dst, white)
+    };
+
+    if board.active_player() && board.width > 5 && king_selected(Piece::KingW, board.king_w) {
         if board.castle_w.0 {
             // TODO: check the b and c file
             let king_w = board.king_w.unwrap();
@@ -460,10 +836,12 @@ pub fn probable_moves(game: &Game, board: &Board, virtual_boards: &Vec<&Board>)
                 x -= 1;
                 while let Some(piece) = board.get(x, y) {
                     if let Piece::RookW = piece {
-                        res.push(
-                            Move::castle(true, (board.l, board.t, king_w.0, king_w.1), (x, y), true)
-                                .unwrap(),
-                        );
+                        if path_safe(king_w, true, true) {
+                            res.push(
+                                Move::castle(true, (board.l, board.t, king_w.0, king_w.1), (x, y), true)
+                                    .unwrap(),
+                            );
+                        }
                         break;
                     } else if let Piece::Blank = piece {
                         if x == 0 {
@@ -484,10 +862,12 @@ pub fn probable_moves(game: &Game, board: &Board, virtual_boards: &Vec<&Board>)
             x += 1;
             while let Some(piece) = board.get(x, y) {
                 if let Piece::RookW = piece {
-                    res.push(
-                        Move::castle(false, (board.l, board.t, king_w.0, king_w.1), (x, y), true)
-                            .unwrap(),
-                    );
+                    if path_safe(king_w, false, true) {
+                        res.push(
+                            Move::castle(false, (board.l, board.t, king_w.0, king_w.1), (x, y), true)
+                                .unwrap(),
+                        );
+                    }
                     break;
                 } else if let Piece::Blank = piece {
                     x += 1;
@@ -498,7 +878,7 @@ pub fn probable_moves(game: &Game, board: &Board, virtual_boards: &Vec<&Board>)
             }
         }
     }
-    if !board.active_player() && board.width > 5 {
+    if !board.active_player() && board.width > 5 && king_selected(Piece::KingB, board.king_b) {
         if board.castle_b.0 {
             // TODO: check the b and c file
             let king_b = board.king_b.unwrap();
@@ -507,10 +887,12 @@ pub fn probable_moves(game: &Game, board: &Board, virtual_boards: &Vec<&Board>)
                 x -= 1;
                 while let Some(piece) = board.get(x, y) {
                     if let Piece::RookB = piece {
-                        res.push(
-                            Move::castle(true, (board.l, board.t, king_b.0, king_b.1), (x, y), false)
-                                .unwrap(),
-                        );
+                        if path_safe(king_b, true, false) {
+                            res.push(
+                                Move::castle(true, (board.l, board.t, king_b.0, king_b.1), (x, y), false)
+                                    .unwrap(),
+                            );
+                        }
                         break;
                     } else if let Piece::Blank = piece {
                         if x == 0 {
@@ -531,10 +913,12 @@ pub fn probable_moves(game: &Game, board: &Board, virtual_boards: &Vec<&Board>)
             x += 1;
             while let Some(piece) = board.get(x, y) {
                 if let Piece::RookB = piece {
-                    res.push(
-                        Move::castle(false, (board.l, board.t, king_b.0, king_b.1), (x, y), false)
-                            .unwrap(),
-                    );
+                    if path_safe(king_b, false, false) {
+                        res.push(
+                            Move::castle(false, (board.l, board.t, king_b.0, king_b.1), (x, y), false)
+                                .unwrap(),
+                        );
+                    }
                     break;
                 } else if let Piece::Blank = piece {
                     x += 1;
@@ -549,7 +933,246 @@ pub fn probable_moves(game: &Game, board: &Board, virtual_boards: &Vec<&Board>)
     res
 }
 
-/// Returns whether or not a moveset is legal (ie. if it doesn't put the player in check).
+/**
+    Returns whether one of the squares of the king's rank between the file of `king`, a king of `white`, and `king_dst` (both included) is attacked by the opponent.
+    As in `checkers_of`, the last boards of `white` are first passed on, and a king of `white` is put on each of these squares of the copy of `board`:
+    the path is attacked if a piece of one of the opponent's last boards (on any timeline, the copy of `board` included) can take one of these kings.
+    The opponent's castling moves are left out, as they take nothing.
+**/
+fn is_castling_path_attacked(game: &Game, board: &Board, virtual_boards: &Vec<&Board>, king: (u8, u8), king_dst: u8, white: bool) -> bool {
+    let (king_x, y) = king;
+    let files = king_x.min(king_dst)..=king_x.max(king_dst);
+    let king_piece = if white { Piece::KingW } else { Piece::KingB };
+
+    let passed_boards: Vec<Board> = last_boards_of(game, virtual_boards, white)
+        .into_iter()
+        .cloned()
+        .map(|mut b| {
+            if b.l == board.l && b.t == board.t {
+                for x in files.clone() {
+                    b.set(x, y, king_piece).unwrap();
+                }
+            }
+            b.t += 1;
+            b
+        })
+        .collect();
+    let merged_vboards: Vec<&Board> = virtual_boards.iter().copied().chain(passed_boards.iter()).collect();
+
+    last_boards_of(game, &merged_vboards, !white).into_iter().any(|b| {
+        let mut moves = Vec::new();
+        for ((_, _, px, py), piece) in b.coords().filter(|(_, piece)| piece.is_opponent_piece(white)) {
+            probable_moves_for(game, b, &merged_vboards, &mut moves, piece, px, py);
+        }
+        moves.into_iter().any(|mv| {
+            mv.dst.0 == board.l
+                && mv.dst.1 == board.t + 1
+                && mv.dst.3 == y
+                && files.contains(&mv.dst.2)
+                && (game.travel_rules == TravelRules::default() || is_travel_allowed(game, &merged_vboards, &mv))
+        })
+    })
+}
+
+/**
+    The pieces of a board's active player that are pinned to one of their royal pieces (see `Game::check_rule`) by an enemy rook, bishop, queen or princess of that same board.
+    Such a piece may only move along the line between its royal piece and the pinning piece, onto which it must stay (taking the pinning piece or not): whatever the rest of the moveset,
+    moving it anywhere else, be it on the board or to another board, would let the pinning piece take the royal piece on the next board of that timeline.
+    With `CheckRule::AnyKingSafe`, a king may be left in check as long as another one is safe; no piece is thus considered to be pinned.
+**/
+#[derive(Debug, Clone, Default)]
+pub struct PinMask {
+    /// The squares onto which each pinned piece may move, indexed by the square of that piece
+    pins: HashMap<(u8, u8), Vec<(u8, u8)>>,
+}
+
+impl PinMask {
+    /// Finds the pieces of `board`'s active player that are pinned
+    pub fn new(game: &Game, board: &Board) -> Self {
+        let mut pins = HashMap::new();
+        let white = board.active_player();
+        if game.check_rule == CheckRule::AnyKingSafe {
+            return PinMask { pins };
+        }
+
+        for (index, piece) in board.pieces.iter().enumerate() {
+            if !game.check_rule.is_royal(*piece, white) {
+                continue;
+            }
+            let (kx, ky) = match board.square_of(index) {
+                Some((x, y)) => (x as isize, y as isize),
+                None => break,
+            };
+            for (dx, dy) in [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)] {
+                let diagonal = dx != 0 && dy != 0;
+                let mut ray: Vec<(u8, u8)> = Vec::new();
+                let mut pinned: Option<(u8, u8)> = None;
+                let (mut x, mut y) = (kx + dx, ky + dy);
+                while x >= 0 && y >= 0 {
+                    let square = (x as u8, y as u8);
+                    match board.tile(square.0, square.1) {
+                        Tile::Void => break,
+                        Tile::Empty => ray.push(square),
+                        Tile::Piece(piece) if piece.is_own_piece(white) => {
+                            if pinned.is_some() || game.check_rule.is_royal(piece, white) {
+                                break;
+                            }
+                            ray.push(square);
+                            pinned = Some(square);
+                        }
+                        Tile::Piece(piece) => {
+                            ray.push(square);
+                            let pins_along = piece.is_queen()
+                                || piece.is_princess()
+                                || if diagonal { piece.is_bishop() } else { piece.is_rook() };
+                            if let (Some(pinned), true) = (pinned, pins_along) {
+                                ray.retain(|sq| *sq != pinned);
+                                pins.insert(pinned, ray);
+                            }
+                            break;
+                        }
+                    }
+                    x += dx;
+                    y += dy;
+                }
+            }
+        }
+
+        PinMask { pins }
+    }
+
+    /// Returns whether the piece at `(x, y)` is pinned
+    pub fn is_pinned(&self, x: u8, y: u8) -> bool {
+        self.pins.contains_key(&(x, y))
+    }
+
+    /// Returns whether `mv` keeps its piece on its pin line, which is always the case for the pieces that are not pinned
+    pub fn allows(&self, mv: &Move) -> bool {
+        match self.pins.get(&(mv.src.2, mv.src.3)) {
+            Some(squares) => mv.src.0 == mv.dst.0 && mv.src.1 == mv.dst.1 && squares.contains(&(mv.dst.2, mv.dst.3)),
+            None => true,
+        }
+    }
+
+    /// Returns whether `piece`, at `(x, y)`, may have moves along its pin line (if it is pinned); eg. a knight cannot and a rook cannot along a diagonal
+    fn may_move(&self, piece: Piece, x: u8, y: u8) -> bool {
+        match self.pins.get(&(x, y)) {
+            Some(squares) => {
+                let (px, py) = squares[0];
+                let diagonal = px != x && py != y;
+                piece.is_queen()
+                    || piece.is_princess()
+                    || piece.is_pawn() && (diagonal || px == x)
+                    || if diagonal { piece.is_bishop() } else { piece.is_rook() }
+            }
+            None => true,
+        }
+    }
+}
+
+/**
+    Same as `probable_moves`, but the moves of the pinned pieces that would leave their pin line (see `PinMask`), which cannot be part of a legal moveset, are not returned.
+    The pinned pieces that cannot move along their pin line at all (eg. knights) are not even looked at. The other moves are still only pseudo-legal.
+**/
+pub fn probable_moves_pinned(game: &Game, board: &Board, virtual_boards: &Vec<&Board>, pins: &PinMask) -> Vec<Move> {
+    let mut res = probable_moves_filtered(game, board, virtual_boards, |piece, (_, _, x, y)| pins.may_move(piece, x, y));
+    res.retain(|mv| pins.allows(mv));
+    res
+}
+
+/**
+    Returns the moves that can be made on each board of `boards` (see `probable_moves`), together with the index of the board in `boards` that they are made from.
+    The moves are yielded board after board, lazily; eg. `probable_moves_all(game, &get_own_boards(game, virtual_boards, info), virtual_boards)` lists the moves of the active player.
+**/
+pub fn probable_moves_all<'a>(
+    game: &'a Game,
+    boards: &'a [&'a Board],
+    virtual_boards: &'a Vec<&'a Board>,
+) -> impl Iterator<Item = (usize, Move)> + 'a {
+    boards.iter().enumerate().flat_map(move |(index, board)| {
+        probable_moves(game, board, virtual_boards)
+            .into_iter()
+            .map(move |mv| (index, mv))
+    })
+}
+
+/// The order in which `probable_moves_ordered` yields moves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GenerationOrder {
+    /// Moves staying on their board come first, followed by the moves jumping to other boards
+    PhysicalFirst,
+    /// Moves jumping to other boards come first, followed by the moves staying on their board
+    JumpsFirst,
+}
+
+/// Same as `probable_moves`, with the moves sorted according to `order`; the relative order within physical moves and within jumps is kept
+pub fn probable_moves_ordered(
+    game: &Game,
+    board: &Board,
+    virtual_boards: &Vec<&Board>,
+    order: GenerationOrder,
+) -> Vec<Move> {
+    let mut res = probable_moves(game, board, virtual_boards);
+    res.sort_by_key(|mv| {
+        let jumps = mv.src.0 != mv.dst.0 || mv.src.1 != mv.dst.1;
+        match order {
+            GenerationOrder::PhysicalFirst => jumps,
+            GenerationOrder::JumpsFirst => !jumps,
+        }
+    });
+    res
+}
+
+/// The order in which `probable_moves_scanned` looks at the squares of a board, and thus the order in which it yields the moves of its pieces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOrder {
+    /// Row after row, from white's back rank, and from the a-file within each row; the order of `probable_moves`
+    RowMajor,
+    /// From the center of the board outwards, ties being broken in row-major order
+    CenterOut,
+    /// The most valuable pieces first (see `piece_value`), ties being broken in row-major order; kings, which are worth nothing, come last
+    PieceValue,
+}
+
+impl ScanOrder {
+    /// Returns the squares of `board`, in that order
+    fn squares(self, board: &Board) -> Vec<(u8, u8)> {
+        let mut res: Vec<(u8, u8)> = (0..board.height)
+            .flat_map(|y| (0..board.width).map(move |x| (x, y)))
+            .collect();
+        match self {
+            ScanOrder::RowMajor => {}
+            ScanOrder::CenterOut => res.sort_by_key(|(x, y)| {
+                // Distances are doubled, so that the center of even-sized boards falls on integer coordinates
+                let dx = 2 * *x as i32 + 1 - board.width as i32;
+                let dy = 2 * *y as i32 + 1 - board.height as i32;
+                dx * dx + dy * dy
+            }),
+            ScanOrder::PieceValue => res.sort_by(|(ax, ay), (bx, by)| {
+                let value = |x: u8, y: u8| board.get(x, y).map_or(0.0, |piece| piece_value(piece).abs());
+                value(*bx, *by).total_cmp(&value(*ax, *ay))
+            }),
+        }
+        res
+    }
+}
+
+/**
+    Same as `probable_moves`, with the pieces of `board` looked at in the given `order`: every order yields the same moves, only their order differs.
+    Looking at the most promising pieces first lets a search that keeps the generated order, or that stops early, try their moves sooner.
+**/
+pub fn probable_moves_scanned(game: &Game, board: &Board, virtual_boards: &Vec<&Board>, order: ScanOrder) -> Vec<Move> {
+    probable_moves_scanned_filtered(game, board, virtual_boards, order, |_, _| true)
+}
+
+/**
+    Returns whether or not a moveset is legal (ie. if it doesn't put the player in check).
+    The opponent's moves are generated on the resulting boards, so pins are handled the same way for every kind of piece, fairy pieces included;
+    eg. a princess pinned along a diagonal may only move along that diagonal.
+
+    The royal pieces that must be safe are given by `game.check_rule`. With `CheckRule::AnyKingSafe`, whether one of the kings is safe depends on every board,
+    so the opponent's moves are then generated on all of their boards rather than only on `boards`.
+**/
 pub fn is_moveset_legal<'a, U>(
     game: &Game,
     virtual_boards: &Vec<&Board>,
@@ -560,13 +1183,16 @@ where
     U: Iterator<Item = &'a Board>,
 {
     let opponent = !info.active_player;
+    let any_king_safe = game.check_rule == CheckRule::AnyKingSafe;
 
     for board in boards {
         if is_last(game, virtual_boards, board) {
             if board.active_player() == opponent {
-                for m in probable_moves(game, board, virtual_boards) {
-                    if m.dst_piece == (if opponent { Piece::KingB } else { Piece::KingW }) {
-                        return false;
+                if !any_king_safe {
+                    for m in probable_moves(game, board, virtual_boards) {
+                        if game.check_rule.is_royal(m.dst_piece, !opponent) {
+                            return false;
+                        }
                     }
                 }
             } else {
@@ -577,7 +1203,94 @@ where
         }
     }
 
-    true
+    !any_king_safe || has_safe_king(game, virtual_boards, info)
+}
+
+/// Returns whether none of the active player's kings can be taken by the opponent, or whether one of their kings on the last boards cannot be
+fn has_safe_king(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> bool {
+    let white = info.active_player;
+    let attacked: HashSet<Coords> = get_opponent_boards(game, virtual_boards, info)
+        .into_iter()
+        .flat_map(|b| probable_moves(game, b, virtual_boards))
+        .filter(|mv| mv.dst_piece.is_king() && mv.dst_piece.is_own_piece(white))
+        .map(|mv| mv.dst)
+        .collect();
+    if attacked.is_empty() {
+        return true;
+    }
+
+    last_boards_of(game, virtual_boards, true)
+        .into_iter()
+        .chain(last_boards_of(game, virtual_boards, false))
+        .any(|b| {
+            b.pieces.iter().enumerate().any(|(index, piece)| {
+                piece.is_king()
+                    && piece.is_own_piece(white)
+                    && b.square_of(index).is_some_and(|(x, y)| !attacked.contains(&(b.l, b.t, x, y)))
+            })
+        })
+}
+
+/**
+    Returns whether `mv` can be played by the active player: its source board must be one of their playable boards, it must be one of the moves of that board
+    and it must not put its player in check on the boards that it creates (as in `score_moves`, regardless of the other moves of the moveset).
+**/
+pub fn is_move_valid(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, mv: &Move) -> bool {
+    match get_board(game, virtual_boards, (mv.src.0, mv.src.1)) {
+        Some(board) if board.active_player() == info.active_player && is_last(game, virtual_boards, board) => {
+            probable_moves(game, board, virtual_boards).contains(mv)
+                && is_move_legal(game, virtual_boards, info, mv)
+        }
+        _ => false,
+    }
+}
+
+/// Same as calling `is_move_valid` on each move of `moves`, but the moves of each source board are only generated once
+pub fn validate_moves(
+    game: &Game,
+    virtual_boards: &Vec<&Board>,
+    info: &GameInfo,
+    moves: &[Move],
+) -> Vec<bool> {
+    let mut cache: HashMap<(i32, isize), Vec<Move>> = HashMap::new();
+
+    moves
+        .iter()
+        .map(|mv| {
+            let board_moves = cache.entry((mv.src.0, mv.src.1)).or_insert_with(|| {
+                match get_board(game, virtual_boards, (mv.src.0, mv.src.1)) {
+                    Some(board) if board.active_player() == info.active_player && is_last(game, virtual_boards, board) => {
+                        probable_moves(game, board, virtual_boards)
+                    }
+                    _ => vec![],
+                }
+            });
+            board_moves.contains(mv) && is_move_legal(game, virtual_boards, info, mv)
+        })
+        .collect()
+}
+
+/**
+    Returns whether the boards created by `mv` do not leave its player in check.
+    The opponent's moves are generated on the created boards and, if one of the opponent's boards (created or not) holds a royal piece of the player, on the opponent's other boards:
+    a piece travelling from another timeline or from another board may otherwise take a king that `mv` exposed, even one on a board that `mv` did not create
+    (eg. by moving a piece out of the way of a bishop aiming across the timelines). See `TurnAssembler::finalize`, which does the same for whole turns.
+**/
+fn is_move_legal(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, mv: &Move) -> bool {
+    match mv.generate_vboards(game, info, virtual_boards, &vec![]) {
+        Some((new_info, boards)) => {
+            let merged_vboards: Vec<&Board> = virtual_boards.iter().copied().chain(boards.iter()).collect();
+            let opponent_boards = get_opponent_boards(game, virtual_boards, info);
+            let holds_royal = boards
+                .iter()
+                .chain(opponent_boards.iter().copied())
+                .any(|b| b.pieces.iter().any(|piece| game.check_rule.is_royal(*piece, info.active_player)));
+
+            is_moveset_legal(game, &merged_vboards, &new_info, boards.iter())
+                && (!holds_royal || is_moveset_legal(game, &merged_vboards, &new_info, opponent_boards.into_iter()))
+        }
+        None => false,
+    }
 }
 
 /// Returns whether or not every mandatory boards were played on (accepts time travel escapes)
@@ -610,7 +1323,7 @@ pub fn get_opponent_boards<'a>(
     res
 }
 
-/// Returns the set of board on which the active player can make a move
+/// Returns the set of board on which the active player can make a move: the last boards on which it is their turn, whichever player created their timeline (see `GameInfo::timeline_owner`)
 pub fn get_own_boards<'a>(
     game: &'a Game,
     virtual_boards: &'a Vec<&'a Board>,
@@ -638,18 +1351,99 @@ pub fn legal_movesets<'a>(
     max_moves_considered: usize,
     max_movesets_considered: usize,
 ) -> impl Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)> + 'a {
-    let ranked_moves = get_own_boards(&game, &virtual_boards, &info)
-        .into_iter()
-        .map(|board| {
-            let lore = Lore::new(
-                game,
-                virtual_boards,
+    legal_movesets_filtered(
+        game,
+        info,
+        virtual_boards,
+        max_moves_considered,
+        max_movesets_considered,
+        |_| true,
+    )
+}
+
+/**
+    Returns a lazy iterator over the legal movesets of the active player whose moves all start from the timeline `l`, like `legal_movesets`; the moves may still land on other timelines.
+    No move is made from the boards of the other timelines: these movesets thus only exist if none of those boards must be played on (see `Board::is_active`), or if the moves landing on them are enough;
+    a turn that cannot be completed from the timeline `l` alone yields no moveset rather than an incomplete one.
+**/
+pub fn legal_movesets_on_timeline<'a>(
+    game: &'a Game,
+    info: &'a GameInfo,
+    virtual_boards: &'a Vec<&'a Board>,
+    l: i32,
+    max_moves_considered: usize,
+    max_movesets_considered: usize,
+) -> impl Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)> + 'a {
+    legal_movesets_filtered(
+        game,
+        info,
+        virtual_boards,
+        max_moves_considered,
+        max_movesets_considered,
+        move |mv| mv.src.0 == l,
+    )
+}
+
+/// Same as `legal_movesets`, but only the moves for which `filter` returns true are combined into movesets
+fn legal_movesets_filtered<'a, F: Fn(&Move) -> bool>(
+    game: &'a Game,
+    info: &'a GameInfo,
+    virtual_boards: &'a Vec<&'a Board>,
+    max_moves_considered: usize,
+    max_movesets_considered: usize,
+    filter: F,
+) -> impl Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)> + 'a {
+    let ranked_moves = ranked_moves(game, info, virtual_boards, filter);
+
+    let mut iter = MovesetIter::new(&game, &virtual_boards, &info, ranked_moves);
+
+    iter.max_moves_considered = max_moves_considered;
+    iter.max_movesets_considered = max_movesets_considered;
+
+    iter.score()
+}
+
+/**
+    Same as `legal_movesets`, but the moves are combined by a `PrunedMovesetIter`, which gives up on a combination as soon as the moves chosen so far leave the player in check.
+    This is faster when there are many boards to play on; the same movesets are yielded, but in depth-first order and without repeating the movesets whose jumps may be played in either order.
+**/
+pub fn legal_movesets_pruned<'a>(
+    game: &'a Game,
+    info: &'a GameInfo,
+    virtual_boards: &'a Vec<&'a Board>,
+    max_moves_considered: usize,
+    max_movesets_considered: usize,
+) -> impl Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)> + 'a {
+    let ranked_moves = ranked_moves(game, info, virtual_boards, |_| true);
+
+    let mut iter = PrunedMovesetIter::new(game, virtual_boards, info, ranked_moves);
+
+    iter.max_moves_considered = max_moves_considered;
+    iter.max_movesets_considered = max_movesets_considered;
+
+    iter.score()
+}
+
+/// Lists the moves of each of the active player's boards that satisfy `filter`, scored and sorted by `score_moves`, as expected by `MovesetIter::new` and `MovesetIter::reset`
+pub fn ranked_moves<F: Fn(&Move) -> bool>(
+    game: &Game,
+    info: &GameInfo,
+    virtual_boards: &Vec<&Board>,
+    filter: F,
+) -> Vec<RankedMoves> {
+    get_own_boards(&game, &virtual_boards, &info)
+        .into_iter()
+        .map(|board| {
+            let lore = Lore::new(
+                game,
+                virtual_boards,
                 board,
                 get_opponent_boards(&game, &virtual_boards, &info).into_iter(),
                 &info,
             );
             let probables = probable_moves(&game, board, &virtual_boards)
                 .into_iter()
+                .filter(|mv| filter(mv))
                 .map(|mv| {
                     let (new_info, new_vboards) = mv
                         .generate_vboards(&game, &info, &virtual_boards, &vec![])
@@ -659,17 +1453,143 @@ pub fn legal_movesets<'a>(
                 .collect::<Vec<_>>();
             score_moves(&game, &virtual_boards, board, &lore, probables, &info)
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
 
-    let mut iter = MovesetIter::new(&game, &virtual_boards, &info, ranked_moves);
+/**
+    Picks a random legal moveset, using reservoir sampling over `legal_movesets` (with the same `max_moves_considered` and `max_movesets_considered` limits).
+    Each moveset is weighted by `1 + capture_weight * n`, with `n` the number of pieces it takes; a `capture_weight` of `0` makes the choice uniform.
+    Weights below `0`, given by a negative `capture_weight`, are clamped to `0`: these movesets are only picked if every moveset has a weight of `0`, in which case the first one is returned.
+    Returns None if there are no legal movesets.
+**/
+pub fn random_legal_moveset<R: Rng>(
+    game: &Game,
+    info: &GameInfo,
+    virtual_boards: &Vec<&Board>,
+    max_moves_considered: usize,
+    max_movesets_considered: usize,
+    capture_weight: f32,
+    rng: &mut R,
+) -> Option<(Vec<Move>, Vec<Board>, GameInfo, f32)> {
+    let mut best: Option<(Vec<Move>, Vec<Board>, GameInfo, f32)> = None;
+    let mut best_key = f32::NEG_INFINITY;
 
-    iter.max_moves_considered = max_moves_considered;
-    iter.max_movesets_considered = max_movesets_considered;
+    for node in legal_movesets(game, info, virtual_boards, max_moves_considered, max_movesets_considered) {
+        let captures = node.0.iter().filter(|mv| mv.captured().is_some()).count();
+        let weight = (1.0 + capture_weight * captures as f32).max(0.0);
+        // Weighted reservoir sampling (A-Res): keep the element with the highest u^(1/w)
+        let key = rng.next_f32().powf(1.0 / weight);
+        if key > best_key {
+            best = Some(node);
+            best_key = key;
+        }
+    }
 
-    iter.score()
+    best
+}
+
+/// Adapters for the iterators over movesets returned by `legal_movesets` and its variants
+pub trait MovesetIteratorExt: Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)> + Sized {
+    /**
+        Skips the movesets that lead to the same position as a moveset yielded before, such as the same moves played in another order.
+        Positions are compared by the Zobrist key of the boards that the movesets create (see `hash::board_key`); the first moveset reaching a position is kept.
+    **/
+    fn dedup_by_result(self) -> DedupByResult<Self> {
+        DedupByResult { iter: self, seen: HashSet::new() }
+    }
+
+    /**
+        Only keeps the quiet movesets: those that neither take a piece nor leave the opponent in check, as classified by `capture_movesets` and `checking_movesets`.
+        A moveset is not quiet as soon as one of its moves is a capture or gives check; `game` and `virtual_boards` must be the position that the movesets are played from.
+    **/
+    fn quiets_only<'a>(self, game: &'a Game, virtual_boards: &'a Vec<&'a Board>) -> QuietsOnly<'a, Self> {
+        QuietsOnly { iter: self, game, virtual_boards }
+    }
+}
+
+impl<T: Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)>> MovesetIteratorExt for T {}
+
+/// An iterator over movesets that skips the ones that transpose into an already yielded position, see `MovesetIteratorExt::dedup_by_result`
+pub struct DedupByResult<T> {
+    iter: T,
+    seen: HashSet<ZobristKey>,
+}
+
+impl<T: Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)>> Iterator for DedupByResult<T> {
+    type Item = (Vec<Move>, Vec<Board>, GameInfo, f32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let seen = &mut self.seen;
+        self.iter.find(|(_, boards, _, _)| seen.insert(boards.iter().fold(0, |key, b| key ^ board_key(b))))
+    }
+}
+
+impl<T: FusedIterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)>> FusedIterator for DedupByResult<T> {}
+
+/// An iterator over movesets that skips the captures and checks, see `MovesetIteratorExt::quiets_only`
+pub struct QuietsOnly<'a, T> {
+    iter: T,
+    game: &'a Game,
+    virtual_boards: &'a Vec<&'a Board>,
+}
+
+impl<'a, T: Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)>> Iterator for QuietsOnly<'a, T> {
+    type Item = (Vec<Move>, Vec<Board>, GameInfo, f32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (game, virtual_boards) = (self.game, self.virtual_boards);
+        self.iter.find(|(moves, boards, new_info, _)| {
+            !is_capture(moves) && !is_check(game, virtual_boards, boards, new_info)
+        })
+    }
+}
+
+impl<'a, T: FusedIterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)>> FusedIterator for QuietsOnly<'a, T> {}
+
+/// Adapters for the iterators over moves, be it over owned moves (eg. `legal_moves_from_streaming`) or over borrowed ones (eg. `probable_moves(..).iter()`)
+pub trait MoveIteratorExt: Iterator + Sized
+where
+    Self::Item: Borrow<Move>,
+{
+    /**
+        Only keeps the moves whose source and destination both lie within the bounding box going from `min` to `max`, bounds included, on each of the `l`, `t`, `x` and `y` axes.
+        The moves are still generated before being filtered; to avoid generating the moves of the boards outside of the region, filter these boards beforehand.
+    **/
+    fn within_region(self, min: Coords, max: Coords) -> WithinRegion<Self> {
+        WithinRegion { iter: self, min, max }
+    }
+}
+
+impl<T: Iterator> MoveIteratorExt for T where T::Item: Borrow<Move> {}
+
+/// An iterator over moves that skips the ones leaving or entering a region, see `MoveIteratorExt::within_region`
+pub struct WithinRegion<T> {
+    iter: T,
+    min: Coords,
+    max: Coords,
+}
+
+impl<T: Iterator> Iterator for WithinRegion<T>
+where
+    T::Item: Borrow<Move>,
+{
+    type Item = T::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (min, max) = (self.min, self.max);
+        let within = |c: Coords| {
+            (min.0..=max.0).contains(&c.0)
+                && (min.1..=max.1).contains(&c.1)
+                && (min.2..=max.2).contains(&c.2)
+                && (min.3..=max.3).contains(&c.3)
+        };
+        self.iter.find(|mv| within(mv.borrow().src) && within(mv.borrow().dst))
+    }
 }
 
-/// Returns the `(l, t)` board within `game` or `virtual_boards`
+impl<T: FusedIterator> FusedIterator for WithinRegion<T> where T::Item: Borrow<Move> {}
+
+/// Returns the `(l, t)` board within `game` or `virtual_boards`, looking at `virtual_boards` first
 pub fn get_board<'a, 'b, 'd>(
     game: &'a Game,
     virtual_boards: &'b Vec<&'b Board>,
@@ -738,9 +1658,26 @@ pub fn probable_moves_for(
     piece: Piece,
     x: u8,
     y: u8,
+) -> Option<()> {
+    probable_moves_as(game, board, virtual_boards, res, piece, x, y, board.active_player())
+}
+
+/**
+    Same as `probable_moves_for`, but `piece` moves as if it belonged to `active_player` rather than to the player whose turn it is on `board`.
+    Only used for pieces that are not actually on the board (see `Board::hypothetical_attacks`).
+**/
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn probable_moves_as(
+    game: &Game,
+    board: &Board,
+    virtual_boards: &Vec<&Board>,
+    res: &mut Vec<Move>,
+    piece: Piece,
+    x: u8,
+    y: u8,
+    active_player: bool,
 ) -> Option<()> {
     let src = (board.l, board.t, x, y);
-    let active_player = board.active_player();
     if piece.is_pawn() {
         let dy: isize = if piece.is_white() { 1 } else { -1 };
         let y1 = ((y as isize) + dy) as u8;
@@ -946,6 +1883,215 @@ pub fn probable_moves_for(
     Some(())
 }
 
+/**
+    Returns the set of moves that the piece at `(x, y)` on `board` can make; returns an empty set if that square is empty or does not exist.
+    Like `probable_moves`, the moves are only pseudo-legal: they are not checked for whether they leave their player in check and may thus include illegal moves.
+    This is much cheaper than filtering them, which is useful when the legality of the moves doesn't matter (eg. when counting a piece's mobility).
+    Castling moves are only generated by `probable_moves`.
+**/
+pub fn probable_moves_at(
+    game: &Game,
+    board: &Board,
+    virtual_boards: &Vec<&Board>,
+    x: u8,
+    y: u8,
+) -> Vec<Move> {
+    let mut res: Vec<Move> = Vec::new();
+    if let Some(piece) = board.get(x, y) {
+        if !piece.is_blank() {
+            probable_moves_for(game, board, virtual_boards, &mut res, piece, x, y);
+        }
+    }
+    if game.travel_rules != TravelRules::default() {
+        res.retain(|mv| is_travel_allowed(game, virtual_boards, mv));
+    }
+    res
+}
+
+/**
+    Returns a lazy iterator over the legal moves of the piece at `coords`, which must be on one of the active player's playable boards (the iterator is empty otherwise).
+    Castling moves are included and each move is checked for legality (as in `is_move_valid`) only as the iterator is advanced, so that the first destinations can be shown before the others are checked.
+
+    The iterator owns a copy of the position, so that it does not borrow from `game` and can be sent to another thread; for instance, to feed a channel that a UI polls:
+
+    ```
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let moves = legal_moves_from_streaming(&game, &virtual_boards, &info, coords);
+    std::thread::spawn(move || {
+        for mv in moves {
+            if sender.send(mv).is_err() {
+                break; // The UI is no longer listening
+            }
+        }
+    });
+    ```
+**/
+pub fn legal_moves_from_streaming(
+    game: &Game,
+    virtual_boards: &Vec<&Board>,
+    info: &GameInfo,
+    coords: Coords,
+) -> Box<dyn Iterator<Item = Move> + Send> {
+    let moves = match get_board(game, virtual_boards, (coords.0, coords.1)) {
+        Some(board) if board.active_player() == info.active_player && is_last(game, virtual_boards, board) => {
+            probable_moves_filtered(game, board, virtual_boards, |_, c| c == coords)
+        }
+        _ => vec![],
+    };
+    let game = game.clone();
+    let virtual_boards: Vec<Board> = virtual_boards.iter().map(|b| (*b).clone()).collect();
+    let info = *info;
+
+    Box::new(moves.into_iter().filter(move |mv| {
+        let virtual_boards: Vec<&Board> = virtual_boards.iter().collect();
+        is_move_legal(&game, &virtual_boards, &info, mv)
+    }))
+}
+
+/// Returns the legal moves of the piece at `from`, castling included, checked as in `is_move_valid`; `from` must be on one of the active player's playable boards (the set is empty otherwise)
+fn legal_moves_from(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, from: Coords) -> Vec<Move> {
+    match get_board(game, virtual_boards, (from.0, from.1)) {
+        Some(board) if board.active_player() == info.active_player && is_last(game, virtual_boards, board) => {
+            probable_moves_filtered(game, board, virtual_boards, |_, c| c == from)
+                .into_iter()
+                .filter(|mv| is_move_legal(game, virtual_boards, info, mv))
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+/**
+    Returns the boards, as `(l, t)`, that the piece at `from` can legally jump to this turn, sorted and without duplicates; `from` must be on one of the active player's playable boards (the set is empty otherwise).
+    Jumps onto a past board, which would create a new timeline, are listed under the board jumped to; moves within `from`'s own board are left out. Each jump is checked for legality as in `is_move_valid`.
+**/
+pub fn jumpable_boards(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, from: Coords) -> Vec<(i32, isize)> {
+    let mut res: Vec<(i32, isize)> = legal_moves_from(game, virtual_boards, info, from)
+        .into_iter()
+        .filter(|mv| (mv.dst.0, mv.dst.1) != (mv.src.0, mv.src.1))
+        .map(|mv| (mv.dst.0, mv.dst.1))
+        .collect();
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+/// A square that a piece can move to, see `move_targets_from`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MoveTarget {
+    pub x: u8,
+    pub y: u8,
+    /// Whether the move takes a piece (en passant included)
+    pub capture: bool,
+    /// Whether the move lands on a board that was already played on, thus creating a new timeline
+    pub branch: bool,
+    /// The move itself, to be played if that square is picked
+    pub mv: Move,
+}
+
+/// The squares that a piece can move to, grouped by board, see `move_targets_from`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MoveTargets {
+    /// The boards, as `(l, t)`, along with the squares of each of them that can be moved to; sorted by board, then by `(y, x)`
+    pub boards: Vec<((i32, isize), Vec<MoveTarget>)>,
+}
+
+impl MoveTargets {
+    /// Returns the squares of the `(l, t)` board that can be moved to, None if there is none
+    pub fn on_board(&self, l: i32, t: isize) -> Option<&[MoveTarget]> {
+        self.boards
+            .binary_search_by_key(&(l, t), |(board, _)| *board)
+            .ok()
+            .map(|index| self.boards[index].1.as_slice())
+    }
+
+    /// Returns the number of squares that can be moved to, over every board
+    pub fn len(&self) -> usize {
+        self.boards.iter().map(|(_, targets)| targets.len()).sum()
+    }
+
+    /// Returns whether the piece cannot move at all
+    pub fn is_empty(&self) -> bool {
+        self.boards.is_empty()
+    }
+}
+
+/**
+    Returns the squares that the piece at `from` can legally move to this turn, grouped by board and flagged as captures and as moves creating a new timeline:
+    what a frontend needs to show the destinations of a picked piece across every board. `from` must be on one of the active player's playable boards (the set is empty otherwise).
+    Castling moves are listed under the rook's square, as their destination; each move is checked for legality as in `is_move_valid`.
+**/
+pub fn move_targets_from(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, from: Coords) -> MoveTargets {
+    let mut res = MoveTargets::default();
+    let mut moves = legal_moves_from(game, virtual_boards, info, from);
+    moves.sort_unstable_by_key(|mv| (mv.dst.0, mv.dst.1, mv.dst.3, mv.dst.2));
+    for mv in moves {
+        let board = (mv.dst.0, mv.dst.1);
+        let target = MoveTarget {
+            x: mv.dst.2,
+            y: mv.dst.3,
+            capture: mv.captured().is_some(),
+            branch: get_board(game, virtual_boards, board).is_some_and(|b| !is_last(game, virtual_boards, b)),
+            mv,
+        };
+        match res.boards.last_mut() {
+            Some((last, targets)) if *last == board => targets.push(target),
+            _ => res.boards.push((board, vec![target])),
+        }
+    }
+    res
+}
+
+/**
+    Returns the legal moves of the active player that land on `target`, from any of their playable boards: the pieces that could take or recapture on that square,
+    or the candidates for an ambiguous move entered by a user. Each move is checked for legality as in `is_move_valid`; castling moves, whose destination is the rook's square, are left out.
+
+    Rather than generating every move, only the pieces that could reach `target` are moved: those aligned with it along a 5D diagonal (as sliding pieces move)
+    and those within two squares of it on each axis (as knights, kings and pawns move).
+**/
+pub fn movers_to(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, target: Coords) -> Vec<Move> {
+    if get_board(game, virtual_boards, (target.0, target.1)).is_none() {
+        return vec![];
+    }
+
+    let may_reach = |src: Coords| {
+        if (target.1 - src.1) % 2 != 0 {
+            return false;
+        }
+        let distances = [
+            (target.0 - src.0).unsigned_abs() as isize,
+            (target.1 - src.1).abs() / 2,
+            (target.2 as isize - src.2 as isize).abs(),
+            (target.3 as isize - src.3 as isize).abs(),
+        ];
+        let max = distances.iter().copied().max().unwrap_or(0);
+        max <= 2 || distances.iter().all(|d| *d == 0 || *d == max)
+    };
+
+    get_own_boards(game, virtual_boards, info)
+        .into_iter()
+        .flat_map(|board| probable_moves_filtered(game, board, virtual_boards, |_, src| may_reach(src)))
+        .filter(|mv| mv.dst == target && !mv.castle && is_move_legal(game, virtual_boards, info, mv))
+        .collect()
+}
+
+/// Returns whether `mv` follows `game.travel_rules`
+pub(crate) fn is_travel_allowed(game: &Game, virtual_boards: &Vec<&Board>, mv: &Move) -> bool {
+    if let Some(max_time_jump) = game.travel_rules.max_time_jump {
+        if mv.src.1 - mv.dst.1 > 2 * max_time_jump as isize {
+            return false;
+        }
+    }
+    if !game.travel_rules.allow_branching && (mv.src.0 != mv.dst.0 || mv.src.1 != mv.dst.1) {
+        match get_board(game, virtual_boards, (mv.dst.0, mv.dst.1)) {
+            Some(board) => is_last(game, virtual_boards, board),
+            None => false,
+        }
+    } else {
+        true
+    }
+}
+
 /// Returns if the `x, y` square in `board` can be taken as en-passant (`[ɑ̃ pasɑ̃]`)
 fn may_en_passant(
     game: &Game,
@@ -985,52 +2131,123 @@ fn n_gonal(
     n: usize,
     active_player: bool,
 ) -> Option<()> {
-    for permutation in &PERMUTATIONS[n] {
-        let mut length: isize = 1;
-        loop {
-            let l0 = (src.0 as isize + permutation.0 * length) as i32;
-            let t0 = src.1 as isize + permutation.1 * length * 2;
-            let x0 = src.2 as isize + permutation.2 * length;
-            let y0 = src.3 as isize + permutation.3 * length;
-            if x0 < 0 || x0 >= game.width as isize || y0 < 0 || y0 >= game.height as isize
-            {
-                break;
-            }
-            let dst = (l0, t0, x0 as u8, y0 as u8);
-            let piece = get_with_board(game, board, virtual_boards, dst);
+    for direction in Direction5D::n_gonal(n) {
+        let ray = Ray::new(game, board, virtual_boards, src, (*direction).into(), active_player);
+        for dst in ray.take(if n == 0 { 1 } else { usize::MAX }) {
+            res.push(Move::new_with_board(src, dst, game, board, virtual_boards)?);
+        }
+    }
+    Some(())
+}
 
-            if let Some(true) = piece.map(|piece| piece.is_takable_piece(active_player)) {
-                res.push(Move::new_with_board(src, dst, game, board, virtual_boards)?);
-                if piece.unwrap().is_opponent_piece(active_player) {
-                    break;
+/**
+    Iterator over the squares that a sliding piece on `src` can reach in a given direction, `(dl, dt, dx, dy)` (`dt` is counted in full turns).
+    It stops before the first of its own pieces, after the first of the opponent's pieces and when there is no board further down the ray.
+    `board` does not need to be within `game` or `virtual_boards`.
+**/
+pub struct Ray<'a> {
+    game: &'a Game,
+    board: &'a Board,
+    virtual_boards: &'a Vec<&'a Board>,
+    src: Coords,
+    direction: (isize, isize, isize, isize),
+    active_player: bool,
+    length: isize,
+    done: bool,
+}
+
+impl<'a> Ray<'a> {
+    /// Creates a new ray, for a piece belonging to `active_player`
+    pub fn new(
+        game: &'a Game,
+        board: &'a Board,
+        virtual_boards: &'a Vec<&'a Board>,
+        src: Coords,
+        direction: (isize, isize, isize, isize),
+        active_player: bool,
+    ) -> Self {
+        Ray {
+            game,
+            board,
+            virtual_boards,
+            src,
+            direction,
+            active_player,
+            length: 1,
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Ray<'a> {
+    type Item = Coords;
+
+    fn next(&mut self) -> Option<Coords> {
+        if self.done {
+            return None;
+        }
+        let l0 = (self.src.0 as isize + self.direction.0 * self.length) as i32;
+        let t0 = self.src.1 + self.direction.1 * self.length * 2;
+        let x0 = self.src.2 as isize + self.direction.2 * self.length;
+        let y0 = self.src.3 as isize + self.direction.3 * self.length;
+        if x0 < 0 || x0 >= self.game.width as isize || y0 < 0 || y0 >= self.game.height as isize {
+            self.done = true;
+            return None;
+        }
+        let dst = (l0, t0, x0 as u8, y0 as u8);
+
+        match get_with_board(self.game, self.board, self.virtual_boards, dst) {
+            Some(piece) if piece.is_takable_piece(self.active_player) => {
+                if piece.is_opponent_piece(self.active_player) {
+                    self.done = true;
                 }
-            } else {
-                break;
+                self.length += 1;
+                Some(dst)
             }
-            if n == 0 {
-                break;
+            _ => {
+                self.done = true;
+                None
             }
-            length += 1;
         }
     }
-    Some(())
 }
 
-/// Re-calculate the present
+impl<'a> FusedIterator for Ray<'a> {}
+
+/// Returns the ray from `src` in the direction `(dl, dt, dx, dy)`, for the piece on `src` (or for the player whose turn it is on `board` if `src` is empty); see `Ray`
+pub fn ray<'a>(
+    game: &'a Game,
+    board: &'a Board,
+    virtual_boards: &'a Vec<&'a Board>,
+    src: Coords,
+    direction: (isize, isize, isize, isize),
+) -> Ray<'a> {
+    let active_player = match get_with_board(game, board, virtual_boards, src) {
+        Some(piece) if !piece.is_blank() => piece.is_white(),
+        _ => board.active_player(),
+    };
+    Ray::new(game, board, virtual_boards, src, direction, active_player)
+}
+
+/**
+    Re-calculate the present, as the time of the boards of `info.active_player`: the last board of an active timeline that is the opponent's
+    (eg. one that the active player just played on) counts as the time step before it.
+**/
 pub fn find_present(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> isize {
+    let time = |b: &Board| if b.active_player() == info.active_player { b.t } else { b.t - 1 };
     let mut min = info.present;
     game.timelines
         .values()
         .map(|tl| &tl.states[tl.states.len() - 1])
         .filter(|b| is_last(game, virtual_boards, b) && b.is_active(info))
         .for_each(|b| {
-            if b.t < min {
-                min = b.t;
+            if time(b) < min {
+                min = time(b);
             }
         });
     for b in virtual_boards {
-        if is_last(game, virtual_boards, b) && b.t < min && b.is_active(info) {
-            min = b.t;
+        if is_last(game, virtual_boards, b) && time(b) < min && b.is_active(info) {
+            min = time(b);
         }
     }
 
@@ -1050,6 +2267,21 @@ pub fn is_optional(info: &GameInfo, mv: &Move) -> bool {
     }
 }
 
+/**
+    Returns whether every legal moveset of the active player creates at least one new timeline, ie. whether they must branch to stay out of check (or because no other turn is left).
+    This enumerates the legal movesets (see `legal_movesets`) until one that doesn't branch is found, which may take a while on large positions; returns false if there is no legal moveset at all.
+**/
+pub fn is_forced_to_branch(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> bool {
+    let mut any = false;
+    for (_moves, _boards, new_info, _score) in legal_movesets(game, info, virtual_boards, 0, 0) {
+        if new_info.min_timeline == info.min_timeline && new_info.max_timeline == info.max_timeline {
+            return false;
+        }
+        any = true;
+    }
+    any
+}
+
 /// Returns whether or not the game is a draw; assumes that no move can be made
 pub fn is_draw(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> bool {
     let opponent_boards = get_opponent_boards(game, virtual_boards, info).into_iter().filter(|b| b.is_active(info)).collect::<Vec<_>>();
@@ -1090,3 +2322,1770 @@ pub fn is_draw(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> bo
 
     true
 }
+
+/**
+    Passes the turn to the opponent without moving, for null-move pruning: every board that the active player must play on is copied unchanged to the next time step,
+    after which it is the opponent's turn. Returns the new boards and the resulting `GameInfo`, or None if the active player is in check.
+    As boards are mandatory, this is never a legal move: it is only meant as a search heuristic, assuming that a position is at least as good as it would be after passing.
+**/
+pub fn null_move(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> Option<(Vec<Board>, GameInfo)> {
+    // `is_draw` verifies that passing on every board would not leave the player in check
+    if !is_draw(game, virtual_boards, info) {
+        return None;
+    }
+
+    let mut new_info = *info;
+    new_info.present += 1;
+    new_info.active_player = !info.active_player;
+
+    Some((pass_boards(game, virtual_boards, info, info.active_player), new_info))
+}
+
+/**
+    Returns the coordinates of a board that the active player must play on but on which they cannot make any legal move, while not being in check.
+    Variants differ in whether such a "forced pass" is a loss, so this lets the caller apply its own rule. Returns None if there is no such board.
+    As in `score_moves`, each move is checked for legality on its own.
+**/
+pub fn has_unmovable_required_board(
+    game: &Game,
+    virtual_boards: &Vec<&Board>,
+    info: &GameInfo,
+) -> Option<(i32, isize)> {
+    // `is_draw` verifies that passing on every board would not leave the player in check
+    if !is_draw(game, virtual_boards, info) {
+        return None;
+    }
+
+    get_own_boards(game, virtual_boards, info)
+        .into_iter()
+        .filter(|board| board.is_active(info))
+        .find(|board| {
+            !probable_moves(game, board, virtual_boards)
+                .iter()
+                .any(|mv| is_move_legal(game, virtual_boards, info, mv))
+        })
+        .map(|board| (board.l, board.t))
+}
+
+/**
+    Returns the enemy pieces attacking the king at `king`, together with the move that would take it.
+
+    If it is the king owner's turn on the king's board, then that player's active boards are first passed on (as `is_draw` does), so that the returned moves target the `t + 1` copy of the king's board.
+    Returns an empty vector if there is no king at `king`.
+**/
+pub fn checkers_of(
+    game: &Game,
+    virtual_boards: &Vec<&Board>,
+    info: &GameInfo,
+    king: Coords,
+) -> Vec<(Coords, Move)> {
+    let white = match get(game, virtual_boards, king) {
+        Some(piece) if piece.is_king() => piece.is_white(),
+        _ => return vec![],
+    };
+
+    let passed_boards = pass_boards(game, virtual_boards, info, white);
+
+    let target = if passed_boards.iter().any(|b| b.l == king.0 && b.t == king.1 + 1) {
+        (king.0, king.1 + 1, king.2, king.3)
+    } else {
+        king
+    };
+
+    let merged_vboards = virtual_boards
+        .iter()
+        .copied()
+        .chain(passed_boards.iter())
+        .collect::<Vec<_>>();
+
+    let mut res: Vec<(Coords, Move)> = Vec::new();
+    for b in last_boards_of(game, &merged_vboards, !white) {
+        for mv in probable_moves(game, b, &merged_vboards) {
+            if mv.dst == target && !res.iter().any(|(_, m)| *m == mv) {
+                res.push((mv.src, mv));
+            }
+        }
+    }
+
+    res
+}
+
+/**
+    Returns a lazy iterator over the legal movesets of the active player, like `legal_movesets`, pruning the moves that cannot get their king out of check.
+
+    On the boards where one of the active player's kings is in check (see `checkers_of`) by pieces of that same board, only the following moves are considered: king moves,
+    moves leaving the board (which may branch off and change what the opponent can reach), captures of the checking pieces and moves onto the squares between a checking piece and the king.
+    A check from a piece of one of the opponent's boards, on another board or timeline, that has no square in between to be blocked on (a knight, a pawn or a piece next to the king) can only be escaped by moving the king
+    or by leaving the board, as no move of the active player can take that piece; the checked board is then restricted to these moves.
+    Checks by any other piece of another board may be parried by a move of another board, so that the checked board is left unrestricted (every yielded moveset is still checked for legality).
+    The moves of the other boards are not restricted, and the checked board may still be left unplayed: a new timeline created in the past may move the present back, after which that board no longer has to be played on
+    and, being the active player's, cannot be played on by the opponent. If the active player is not in check, this yields the same movesets as `legal_movesets`.
+**/
+pub fn check_evasions<'a>(
+    game: &'a Game,
+    info: &'a GameInfo,
+    virtual_boards: &'a Vec<&'a Board>,
+    max_moves_considered: usize,
+    max_movesets_considered: usize,
+) -> impl Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)> + 'a {
+    // Squares that a move on a checked board may target, per board; `None` if that board cannot be restricted
+    let mut targets: HashMap<(i32, isize), Option<Vec<Coords>>> = HashMap::new();
+    let opponent_boards: HashSet<(i32, isize)> =
+        get_opponent_boards(game, virtual_boards, info).into_iter().map(|b| (b.l, b.t)).collect();
+    for board in get_own_boards(game, virtual_boards, info) {
+        for (index, piece) in board.pieces.iter().enumerate() {
+            if !piece.is_king() || piece.is_white() != info.active_player {
+                continue;
+            }
+            let (x, y) = match board.square_of(index) {
+                Some(square) => square,
+                None => break,
+            };
+            for (src, mv) in checkers_of(game, virtual_boards, info, (board.l, board.t, x, y)) {
+                let board_targets = targets.entry((board.l, board.t)).or_insert(Some(vec![]));
+                if src.0 != board.l || src.1 != board.t + 1 {
+                    // A checking piece on another board may be taken or blocked by a move of another board, unless it sits on one of the opponent's boards and has no square to be blocked on
+                    if !opponent_boards.contains(&(src.0, src.1)) || !squares_between(src, mv.dst).is_empty() {
+                        *board_targets = None;
+                    }
+                } else if let Some(board_targets) = board_targets {
+                    board_targets.push(src);
+                    board_targets.append(&mut squares_between(src, mv.dst));
+                }
+            }
+        }
+    }
+
+    legal_movesets_filtered(
+        game,
+        info,
+        virtual_boards,
+        max_moves_considered,
+        max_movesets_considered,
+        move |mv| match targets.get(&(mv.src.0, mv.src.1)) {
+            Some(Some(board_targets)) => {
+                mv.src_piece.is_king()
+                    || mv.src.0 != mv.dst.0
+                    || mv.src.1 != mv.dst.1
+                    // The targets may lie on the board created by the move
+                    || board_targets.iter().any(|c| {
+                        c.0 == mv.dst.0
+                            && (c.1 == mv.dst.1 || c.1 == mv.dst.1 + 1)
+                            && c.2 == mv.dst.2
+                            && c.3 == mv.dst.3
+                    })
+            }
+            _ => true,
+        },
+    )
+}
+
+/**
+    Returns a lazy iterator over the legal movesets of the active player that take at least one piece, like `legal_movesets`.
+    As every board that must be played on still has to be, the other moves of those movesets need not be captures.
+**/
+pub fn capture_movesets<'a>(
+    game: &'a Game,
+    info: &'a GameInfo,
+    virtual_boards: &'a Vec<&'a Board>,
+    max_moves_considered: usize,
+    max_movesets_considered: usize,
+) -> impl Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)> + 'a {
+    legal_movesets(game, info, virtual_boards, max_moves_considered, max_movesets_considered)
+        .filter(|(moves, _, _, _)| is_capture(moves))
+}
+
+/// Returns whether one of the moves of `moveset` takes a piece
+fn is_capture(moveset: &[Move]) -> bool {
+    moveset.iter().any(|mv| mv.captured().is_some())
+}
+
+/// Returns whether the opponent is in check once the boards created by a moveset, `boards`, are played; `new_info` is the `GameInfo` of the opponent's turn
+fn is_check(game: &Game, virtual_boards: &Vec<&Board>, boards: &[Board], new_info: &GameInfo) -> bool {
+    let merged_vboards = virtual_boards.iter().copied().chain(boards.iter()).collect::<Vec<_>>();
+    !is_draw(game, &merged_vboards, new_info)
+}
+
+/**
+    Returns a lazy iterator over the legal movesets of the active player that leave the opponent in check, like `legal_movesets`, for forcing-move search and puzzle generation.
+    The opponent is in check if one of their kings could be taken were they to pass on every board that they must play on (see `is_draw`).
+**/
+pub fn checking_movesets<'a>(
+    game: &'a Game,
+    info: &'a GameInfo,
+    virtual_boards: &'a Vec<&'a Board>,
+    max_moves_considered: usize,
+    max_movesets_considered: usize,
+) -> impl Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)> + 'a {
+    legal_movesets(game, info, virtual_boards, max_moves_considered, max_movesets_considered)
+        .filter(move |(_, boards, new_info, _)| is_check(game, virtual_boards, boards, new_info))
+}
+
+/// Returns the squares strictly between `a` and `b` if they are aligned along a straight line (as a rook, a bishop, a unicorn or a dragon would move), an empty vector otherwise
+fn squares_between(a: Coords, b: Coords) -> Vec<Coords> {
+    let delta = [
+        (b.0 - a.0) as isize,
+        (b.1 - a.1) / 2,
+        b.2 as isize - a.2 as isize,
+        b.3 as isize - a.3 as isize,
+    ];
+    let n = delta.iter().map(|d| d.abs()).max().unwrap_or(0);
+    if (b.1 - a.1) % 2 != 0 || delta.iter().any(|d| *d != 0 && d.abs() != n) {
+        return vec![];
+    }
+
+    (1..n)
+        .map(|k| {
+            (
+                a.0 + (k * delta[0].signum()) as i32,
+                a.1 + 2 * k * delta[1].signum(),
+                (a.2 as isize + k * delta[2].signum()) as u8,
+                (a.3 as isize + k * delta[3].signum()) as u8,
+            )
+        })
+        .collect()
+}
+
+/**
+    Returns, for every piece of `white`'s color on the last board of each timeline, the number of enemy moves that could take it.
+    Boards on which it is `white`'s turn are first passed on, as in `checkers_of`; the returned coordinates are those of the boards before passing.
+**/
+pub fn threat_map(
+    game: &Game,
+    virtual_boards: &Vec<&Board>,
+    info: &GameInfo,
+    white: bool,
+) -> HashMap<Coords, u8> {
+    let passed_boards = pass_boards(game, virtual_boards, info, white);
+    let merged_vboards = virtual_boards
+        .iter()
+        .copied()
+        .chain(passed_boards.iter())
+        .collect::<Vec<_>>();
+
+    let mut res: HashMap<Coords, u8> = HashMap::new();
+    for b in last_boards_of(game, virtual_boards, white)
+        .into_iter()
+        .chain(last_boards_of(game, virtual_boards, !white))
+    {
+        for (index, piece) in b.pieces.iter().enumerate() {
+            if piece.is_own_piece(white) {
+                res.insert((b.l, b.t, (index % b.width as usize) as u8, (index / b.width as usize) as u8), 0);
+            }
+        }
+    }
+
+    for b in last_boards_of(game, &merged_vboards, !white) {
+        for mv in probable_moves(game, b, &merged_vboards) {
+            if !mv.dst_piece.is_own_piece(white) {
+                continue;
+            }
+            let dst = if passed_boards.iter().any(|p| p.l == mv.dst.0 && p.t == mv.dst.1) {
+                (mv.dst.0, mv.dst.1 - 1, mv.dst.2, mv.dst.3)
+            } else {
+                mv.dst
+            };
+            if let Some(n) = res.get_mut(&dst) {
+                *n = n.saturating_add(1);
+            }
+        }
+    }
+
+    res
+}
+
+/**
+    Returns the number of distinct squares that `white`'s pieces attack on the active boards, ie. the squares that they could move to or take on; a simple measure of space.
+    Pawns attack the squares diagonally in front of them (whether or not there is a piece to take there) but not the squares in front of them, castling moves are ignored
+    and squares holding `white`'s own pieces are not counted. Boards on which it is the other player's turn are first passed on, as in `threat_map`.
+**/
+pub fn controlled_squares(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, white: bool) -> usize {
+    let passed_boards = pass_boards(game, virtual_boards, info, !white);
+    let merged_vboards = virtual_boards
+        .iter()
+        .copied()
+        .chain(passed_boards.iter())
+        .collect::<Vec<_>>();
+    // Squares are counted with the coordinates of the boards before passing
+    let unpass = |(l, t, x, y): Coords| {
+        if passed_boards.iter().any(|p| p.l == l && p.t == t) {
+            (l, t - 1, x, y)
+        } else {
+            (l, t, x, y)
+        }
+    };
+
+    let mut res: HashSet<Coords> = HashSet::new();
+    let boards = last_boards_of(game, virtual_boards, white)
+        .into_iter()
+        .filter(|b| b.is_active(info))
+        .chain(passed_boards.iter());
+    for b in boards {
+        for mv in probable_moves(game, b, &merged_vboards) {
+            if !mv.castle && !mv.src_piece.is_pawn() {
+                res.insert(unpass(mv.dst));
+            }
+        }
+        for (index, piece) in b.pieces.iter().enumerate() {
+            if !piece.is_pawn() || !piece.is_own_piece(white) {
+                continue;
+            }
+            let x = (index % b.width as usize) as isize;
+            let y = (index / b.width as usize) as isize + if white { 1 } else { -1 };
+            for x in [x - 1, x + 1] {
+                if x >= 0 && x < b.width as isize && y >= 0 && y < b.height as isize {
+                    let (x, y) = (x as u8, y as u8);
+                    if b.get(x, y).is_some_and(|p| !p.is_own_piece(white)) {
+                        res.insert(unpass((b.l, b.t, x, y)));
+                    }
+                }
+            }
+        }
+    }
+
+    res.len()
+}
+
+/**
+    Returns the attack units that the opponent of `white` has on the squares around `white`'s kings, on the last board of every timeline: a king safety term, higher when the kings are more exposed.
+    Every enemy move reaching the square of a king or one of the squares next to it counts for the units of its piece (see `resolve::king_attack_units`), once per square;
+    this includes the pieces of other boards that can travel onto the king's board. Pawns attack the squares diagonally in front of them and castling moves are ignored, as in `controlled_squares`.
+    Boards on which it is `white`'s turn are first passed on, as in `threat_map`.
+**/
+pub fn king_attack_score(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, white: bool) -> i32 {
+    let passed_boards = pass_boards(game, virtual_boards, info, white);
+    let merged_vboards = virtual_boards
+        .iter()
+        .copied()
+        .chain(passed_boards.iter())
+        .collect::<Vec<_>>();
+    let unpass = |(l, t, x, y): Coords| {
+        if passed_boards.iter().any(|p| p.l == l && p.t == t) {
+            (l, t - 1, x, y)
+        } else {
+            (l, t, x, y)
+        }
+    };
+
+    // The squares of `white`'s kings and their neighbours, with the coordinates of the boards before passing
+    let mut zone: HashSet<Coords> = HashSet::new();
+    for b in last_boards_of(game, virtual_boards, true)
+        .into_iter()
+        .chain(last_boards_of(game, virtual_boards, false))
+    {
+        for (index, piece) in b.pieces.iter().enumerate() {
+            if !piece.is_king() || !piece.is_own_piece(white) {
+                continue;
+            }
+            let (x, y) = match b.square_of(index) {
+                Some((x, y)) => (x as isize, y as isize),
+                None => break,
+            };
+            for nx in (x - 1).max(0)..=(x + 1).min(b.width as isize - 1) {
+                for ny in (y - 1).max(0)..=(y + 1).min(b.height as isize - 1) {
+                    zone.insert((b.l, b.t, nx as u8, ny as u8));
+                }
+            }
+        }
+    }
+    if zone.is_empty() {
+        return 0;
+    }
+
+    let mut res: i32 = 0;
+    for b in last_boards_of(game, &merged_vboards, !white) {
+        for mv in probable_moves(game, b, &merged_vboards) {
+            if !mv.castle && !mv.src_piece.is_pawn() && zone.contains(&unpass(mv.dst)) {
+                res += king_attack_units(mv.src_piece);
+            }
+        }
+        for (index, piece) in b.pieces.iter().enumerate() {
+            if !piece.is_pawn() || !piece.is_opponent_piece(white) {
+                continue;
+            }
+            let (x, y) = match b.square_of(index) {
+                Some((x, y)) => (x as isize, y as isize + if white { -1 } else { 1 }),
+                None => break,
+            };
+            for x in [x - 1, x + 1] {
+                if x >= 0 && x < b.width as isize && y >= 0 && y < b.height as isize
+                    && zone.contains(&unpass((b.l, b.t, x as u8, y as u8)))
+                {
+                    res += king_attack_units(*piece);
+                }
+            }
+        }
+    }
+
+    res
+}
+
+/**
+    Returns the coordinates of `white`'s passed pawns on the last board of every timeline, sorted.
+
+    On its board, a pawn is passed if no enemy pawn stands in front of it on its file or on one of the adjacent files, as in regular chess.
+    Its superphysical extension is a heuristic: the pawn must also be passed when put on the last boards of the neighbouring timelines (`l - 1` and `l + 1`), as the pieces that stop it may travel from there,
+    and neighbouring timelines often share most of their pawn structure. Pawns themselves do not leave their board, so the boards of other times and of further timelines are not considered.
+**/
+pub fn passed_pawns(game: &Game, virtual_boards: &Vec<&Board>, white: bool) -> Vec<Coords> {
+    let last_boards: HashMap<i32, &Board> = last_boards_of(game, virtual_boards, true)
+        .into_iter()
+        .chain(last_boards_of(game, virtual_boards, false))
+        .map(|b| (b.l, b))
+        .collect();
+    // Whether an enemy pawn of `board` stands in front of (x, y), on the same or an adjacent file
+    let is_stopped_on = |board: &Board, x: u8, y: u8| {
+        board.pieces.iter().enumerate().any(|(index, piece)| {
+            let (px, py) = match board.square_of(index) {
+                Some(square) => square,
+                None => return false,
+            };
+            piece.is_pawn()
+                && piece.is_opponent_piece(white)
+                && px.abs_diff(x) <= 1
+                && if white { py > y } else { py < y }
+        })
+    };
+
+    let mut res: Vec<Coords> = Vec::new();
+    for board in last_boards.values() {
+        for (index, piece) in board.pieces.iter().enumerate() {
+            if !piece.is_pawn() || !piece.is_own_piece(white) {
+                continue;
+            }
+            let (x, y) = match board.square_of(index) {
+                Some(square) => square,
+                None => break,
+            };
+            let is_passed = [board.l, board.l - 1, board.l + 1]
+                .iter()
+                .filter_map(|l| last_boards.get(l))
+                .all(|b| !is_stopped_on(b, x, y));
+            if is_passed {
+                res.push((board.l, board.t, x, y));
+            }
+        }
+    }
+
+    res.sort_unstable();
+    res
+}
+
+/// Returns a copy of the active boards on which it is `white`'s turn, with their `t` coordinate increased, as if `white` passed on them
+fn pass_boards(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, white: bool) -> Vec<Board> {
+    last_boards_of(game, virtual_boards, white)
+        .into_iter()
+        .filter(|b| b.is_active(info))
+        .cloned()
+        .map(|mut b| {
+            b.t += 1;
+            b
+        })
+        .collect()
+}
+
+/// Returns the last boards of every timeline, within `game` and `virtual_boards`, on which it is `white`'s turn
+fn last_boards_of<'a>(game: &'a Game, virtual_boards: &Vec<&'a Board>, white: bool) -> Vec<&'a Board> {
+    game.timelines
+        .values()
+        .map(|tl| &tl.states[tl.states.len() - 1])
+        .chain(virtual_boards.iter().copied())
+        .filter(|b| b.active_player() == white && is_last(game, virtual_boards, b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moveset::{Moveset, TurnError};
+    use crate::random::SplitMix64;
+    use crate::testing;
+
+    /// Returns the notation of the moves of the piece at `src` in `game` that go to `dst`
+    fn notation(game: &Game, src: Coords, dst: Coords) -> String {
+        let board = game.get_board(src.0, src.1).unwrap();
+        probable_moves(game, board, &vec![])
+            .into_iter()
+            .find(|mv| mv.src == src && mv.dst == dst)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn move_notation() {
+        let mut game = crate::variant::standard();
+        assert_eq!(notation(&game, (0, 0, 6, 0), (0, 0, 5, 2)), "(0T1)Ng1f3");
+        assert_eq!(notation(&game, (0, 0, 4, 1), (0, 0, 4, 3)), "(0T1)e2e4");
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        assert_eq!(notation(&game, (0, 2, 5, 2), (0, 0, 7, 2)), "(0T2)Nf3>>(0T1)h3");
+        testing::play(&mut game, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+        assert_eq!(notation(&game, (1, 1, 6, 7), (1, 1, 5, 5)), "(1T1)Ng8f6");
+
+        let game = testing::game(4, &[
+            "....k...",
+            "........",
+            "........",
+            "...p....",
+            "....P...",
+            "........",
+            "........",
+            "....K...",
+        ]);
+        assert_eq!(notation(&game, (0, 4, 4, 3), (0, 4, 3, 4)), "(0T3)e4xd5");
+
+        let castle = Move {
+            src: (-1, 8, 4, 0),
+            dst: (-1, 8, 6, 0),
+            castle: true,
+            castle_long: false,
+            en_passant: None,
+            src_piece: Piece::KingW,
+            dst_piece: Piece::RookW,
+            noop: false,
+        };
+        assert_eq!(castle.to_string(), "(-1T5)O-O");
+    }
+
+    /// Returns the pseudo-legal and the legal moves of the piece at `(x, y)` on the single board of `game`
+    fn moves_at(game: &Game, x: u8, y: u8) -> (Vec<Move>, Vec<Move>) {
+        let board = game.get_board(0, game.info.present).unwrap();
+        let moves = probable_moves_at(game, board, &vec![], x, y);
+        let legal = moves.iter().copied().filter(|mv| is_move_valid(game, &vec![], &game.info, mv)).collect();
+        (moves, legal)
+    }
+
+    #[test]
+    fn princess_pinned_diagonally() {
+        let game = testing::game(0, &[
+            ".......k",
+            "........",
+            "........",
+            "....b...",
+            "........",
+            "..S.....",
+            "........",
+            "K.......",
+        ]);
+        let (moves, legal) = moves_at(&game, 2, 2);
+        assert!(moves.len() > 3);
+
+        // Only the moves along the bishop's diagonal keep the king covered
+        let mut targets: Vec<(u8, u8)> = legal.iter().map(|mv| (mv.dst.2, mv.dst.3)).collect();
+        targets.sort_unstable();
+        assert_eq!(targets, vec![(1, 1), (3, 3), (4, 4)]);
+    }
+
+    #[test]
+    fn princess_irrelevant_pin() {
+        // The rook on her file has no king behind her to attack
+        let game = testing::game(0, &[
+            "..r....k",
+            "........",
+            "........",
+            "........",
+            "........",
+            "..S.....",
+            "........",
+            "K.......",
+        ]);
+        let (moves, legal) = moves_at(&game, 2, 2);
+        assert_eq!(legal, moves);
+        assert!(legal.iter().any(|mv| mv.dst.3 == 2 && mv.dst.2 == 7));
+    }
+
+    #[test]
+    fn branch_out_of_check() {
+        // The king on timeline 0 is in check and cannot escape on its board; the knight on timeline 1 may jump back to the first board of its timeline
+        let checked = [
+            "...rrr.k",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "....K...",
+        ];
+        let knight = [
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "......N.",
+        ];
+        let rook = [
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "R.......",
+        ];
+        let mut builder = GameBuilder::new(8, 8);
+        for t in 0..=2 {
+            builder = builder
+                .add_board(-1, t, testing::board(-1, t, &rook))
+                .add_board(0, t, testing::board(0, t, &checked))
+                .add_board(1, t, testing::board(1, t, &knight));
+        }
+        let game = builder.build().unwrap();
+        let virtual_boards = Vec::new();
+        assert_eq!(game.info.present, 2);
+        assert!(!checkers_of(&game, &virtual_boards, &game.info, (0, 2, 4, 0)).is_empty());
+
+        // Moving the knight on its board leaves the checked board to be played
+        let quiet = probable_moves_at(&game, game.get_board(1, 2).unwrap(), &virtual_boards, 6, 0)
+            .into_iter()
+            .find(|mv| mv.dst == (1, 2, 5, 2))
+            .unwrap();
+        assert_eq!(Moveset(vec![quiet]).is_legal(&game, &virtual_boards, &game.info), Err(TurnError::UnplayedBoards));
+
+        // Branching off moves the present back, so that the checked board no longer has to be played on
+        let branch = probable_moves_at(&game, game.get_board(1, 2).unwrap(), &virtual_boards, 6, 0)
+            .into_iter()
+            .find(|mv| mv.dst == (1, 0, 6, 2))
+            .unwrap();
+        assert_eq!(Moveset(vec![branch]).is_legal(&game, &virtual_boards, &game.info), Ok(()));
+        // The boards left unplayed are noops in the movesets yielded
+        let played = |node: (Vec<Move>, Vec<Board>, GameInfo, f32)| node.0.into_iter().filter(|mv| !mv.noop).collect::<Vec<_>>();
+        assert!(check_evasions(&game, &game.info, &virtual_boards, 0, 0).map(played).any(|moves| moves == vec![branch]));
+        assert!(!is_draw(&game, &virtual_boards, &game.info));
+    }
+
+    #[test]
+    fn null_move_passes_every_board() {
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["k...", "....", "....", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &["...k", "....", ".N..", "...K"]))
+            .build()
+            .unwrap();
+        let virtual_boards = Vec::new();
+        let (boards, info) = null_move(&game, &virtual_boards, &game.info).unwrap();
+        assert_eq!((info.present, info.active_player), (1, false));
+        let mut passed: Vec<(i32, isize)> = boards.iter().map(|b| (b.l, b.t)).collect();
+        passed.sort_unstable();
+        assert_eq!(passed, vec![(0, 1), (1, 1)]);
+        for board in boards.iter() {
+            assert_eq!(board.pieces, game.get_board(board.l, 0).unwrap().pieces);
+        }
+        // It is black's turn on every board after passing
+        let merged_vboards: Vec<&Board> = boards.iter().collect();
+        assert_eq!(get_own_boards(&game, &merged_vboards, &info).len(), 2);
+
+        // No passing out of check
+        let game = testing::game(0, &["r..k", "....", "....", "K..."]);
+        assert!(null_move(&game, &virtual_boards, &game.info).is_none());
+    }
+
+    #[test]
+    fn unmovable_required_boards() {
+        // The king on (0T0) is stalemated, while white's rook can move on (1T0)
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["k...", "....", "..q.", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &[".k.K", "....", "..q.", "R..."]))
+            .build()
+            .unwrap();
+        let virtual_boards = Vec::new();
+        assert_eq!(has_unmovable_required_board(&game, &virtual_boards, &game.info), Some((0, 0)));
+        let standard = crate::variant::standard();
+        assert_eq!(has_unmovable_required_board(&standard, &virtual_boards, &standard.info), None);
+
+        // A checkmated player is not merely stuck
+        let mated = testing::game(0, &["rr.k", "....", "....", "K..."]);
+        assert_eq!(legal_movesets(&mated, &mated.info, &virtual_boards, 0, 0).count(), 0);
+        assert_eq!(has_unmovable_required_board(&mated, &virtual_boards, &mated.info), None);
+    }
+
+    #[test]
+    fn filtered_probable_moves() {
+        let game = crate::variant::standard();
+        let virtual_boards = Vec::new();
+        let board = game.get_board(0, 0).unwrap();
+        let all = probable_moves(&game, board, &virtual_boards);
+        assert_eq!(probable_moves_filtered(&game, board, &virtual_boards, |_, _| true), all);
+        assert!(probable_moves_filtered(&game, board, &virtual_boards, |_, _| false).is_empty());
+
+        let knights = probable_moves_filtered(&game, board, &virtual_boards, |piece, _| piece == Piece::KnightW);
+        assert_eq!(knights.len(), 4);
+        assert_eq!(knights, all.iter().filter(|mv| mv.src_piece == Piece::KnightW).copied().collect::<Vec<_>>());
+        let queenside = probable_moves_filtered(&game, board, &virtual_boards, |_, (_, _, x, _)| x < 4);
+        assert_eq!(queenside, all.iter().filter(|mv| mv.src.2 < 4).copied().collect::<Vec<_>>());
+
+        // Castling moves come with the king, not with the rook
+        let game = castling_game(&["....k...", "........", "........", "........", "........", "........", "........", "R...K..R"]);
+        let board = game.get_board(0, 0).unwrap();
+        let castles_of = |predicate: &dyn Fn(Piece, Coords) -> bool| {
+            probable_moves_filtered(&game, board, &virtual_boards, predicate).iter().filter(|mv| mv.castle).count()
+        };
+        assert_eq!(castles_of(&|piece, _| piece.is_king()), 2);
+        assert_eq!(castles_of(&|piece, _| piece == Piece::RookW), 0);
+    }
+
+    #[test]
+    fn move_validity() {
+        // White's rook is pinned on the d file
+        let game = testing::game(0, &["...k", "...r", "...R", "...K"]);
+        let virtual_boards = Vec::new();
+        let moves = probable_moves(&game, game.get_board(0, 0).unwrap(), &virtual_boards);
+        let find = |src: (u8, u8), dst: (u8, u8)| *moves.iter().find(|mv| (mv.src.2, mv.src.3, mv.dst.2, mv.dst.3) == (src.0, src.1, dst.0, dst.1)).unwrap();
+        let take = find((3, 1), (3, 2));
+        let unpin = find((3, 1), (0, 1));
+        let king = find((3, 0), (2, 0));
+        // Not a rook move, and a move from a board that does not exist
+        let bent = Move::new((0, 0, 3, 1), (0, 0, 2, 2), &game, &virtual_boards).unwrap();
+        let mut missing = take;
+        missing.src.1 = 2;
+
+        assert!(is_move_valid(&game, &virtual_boards, &game.info, &take));
+        assert!(is_move_valid(&game, &virtual_boards, &game.info, &king));
+        assert!(!is_move_valid(&game, &virtual_boards, &game.info, &unpin));
+        assert!(!is_move_valid(&game, &virtual_boards, &game.info, &bent));
+        assert!(!is_move_valid(&game, &virtual_boards, &game.info, &missing));
+
+        let mut black = game.info;
+        black.active_player = false;
+        assert!(!is_move_valid(&game, &virtual_boards, &black, &take));
+
+        let all = [take, unpin, king, bent, missing, take];
+        assert_eq!(validate_moves(&game, &virtual_boards, &game.info, &all), vec![true, false, true, false, false, true]);
+        assert_eq!(
+            validate_moves(&game, &virtual_boards, &game.info, &moves),
+            moves.iter().map(|mv| is_move_valid(&game, &virtual_boards, &game.info, mv)).collect::<Vec<_>>()
+        );
+        assert_eq!(validate_moves(&game, &virtual_boards, &black, &all), vec![false; all.len()]);
+    }
+
+    #[test]
+    fn travel_rules_restrict_jumps() {
+        // 1. Nf3 / Nf6: the knight of f3 may jump back to 1w
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        let virtual_boards = Vec::new();
+        let jumps = |game: &Game| {
+            probable_moves(game, game.get_board(0, 2).unwrap(), &virtual_boards)
+                .into_iter()
+                .filter(|mv| (mv.src.0, mv.src.1) != (mv.dst.0, mv.dst.1))
+                .count()
+        };
+        let n_moves = |game: &Game| probable_moves(game, game.get_board(0, 2).unwrap(), &virtual_boards).len();
+        let (all_jumps, all_moves) = (jumps(&game), n_moves(&game));
+        assert!(all_jumps > 0);
+
+        game.travel_rules.max_time_jump = Some(1);
+        assert_eq!((jumps(&game), n_moves(&game)), (all_jumps, all_moves));
+        game.travel_rules.max_time_jump = Some(0);
+        assert_eq!((jumps(&game), n_moves(&game)), (0, all_moves - all_jumps));
+        game.travel_rules = TravelRules { max_time_jump: None, allow_branching: false };
+        assert_eq!((jumps(&game), n_moves(&game)), (0, all_moves - all_jumps));
+
+        // Jumping to the last board of another timeline does not branch
+        let mut game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["k...", "....", "....", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &["...k", "....", "....", "...K"]))
+            .build()
+            .unwrap();
+        let jumps = |game: &Game| {
+            probable_moves(game, game.get_board(0, 0).unwrap(), &virtual_boards)
+                .into_iter()
+                .filter(|mv| mv.dst.0 == 1)
+                .count()
+        };
+        let all_jumps = jumps(&game);
+        assert!(all_jumps > 0);
+        game.travel_rules = TravelRules { max_time_jump: Some(0), allow_branching: false };
+        assert_eq!(jumps(&game), all_jumps);
+        // Once the king of (0T1) moved, jumping to its board would branch
+        assert!(legal_movesets(&game, &game.info, &virtual_boards, 0, 0).all(|(_, _, info, _)| info.max_timeline == 1));
+        let mut assembler = TurnAssembler::new(&game, &virtual_boards, &game.info);
+        assert!(assembler.try_push_coords((0, 0, 0, 0), (0, 0, 1, 1)).is_some());
+        assert!(assembler.try_push_coords((1, 0, 3, 0), (0, 0, 2, 0)).is_none());
+        assert!(assembler.try_push_coords((1, 0, 3, 0), (1, 0, 2, 0)).is_some());
+        assert!(assembler.finalize().is_ok());
+    }
+
+    #[test]
+    fn legal_movesets_from_one_timeline() {
+        let standard = crate::variant::standard();
+        let virtual_boards = Vec::new();
+        assert_eq!(legal_movesets_on_timeline(&standard, &standard.info, &virtual_boards, 0, 0, 0).count(), 20);
+        assert_eq!(legal_movesets_on_timeline(&standard, &standard.info, &virtual_boards, 1, 0, 0).count(), 0);
+
+        // Both boards must be played on: from (0T1) alone, only the king jumping onto (1T1) completes the turn
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["k...", "....", "....", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &["...k", "....", "....", "...K"]))
+            .build()
+            .unwrap();
+        // The movesets hold a noop for the board that the jump lands on
+        let played = |(moves, _, _, _): (Vec<Move>, Vec<Board>, GameInfo, f32)| moves.into_iter().filter(|mv| !mv.noop).collect::<Vec<_>>();
+        let legal: Vec<Vec<Move>> = legal_movesets(&game, &game.info, &virtual_boards, 0, 0).map(played).collect();
+        let on_timeline: Vec<Vec<Move>> = legal_movesets_on_timeline(&game, &game.info, &virtual_boards, 0, 0, 0).map(played).collect();
+        assert!(!on_timeline.is_empty());
+        for moves in on_timeline.iter() {
+            assert_eq!(moves.len(), 1);
+            assert_eq!((moves[0].src.0, moves[0].dst.0), (0, 1));
+            assert!(legal.contains(moves));
+        }
+        let jumps = legal.iter().filter(|moves| moves.len() == 1 && moves[0].src.0 == 0).count();
+        assert_eq!(on_timeline.len(), jumps);
+    }
+
+    #[test]
+    fn moves_within_a_region() {
+        // 1. Nf3 / Nf6: the knight of f3 may also jump back to 1w
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        let virtual_boards = Vec::new();
+        let moves = probable_moves(&game, game.get_board(0, 2).unwrap(), &virtual_boards);
+
+        // The kingside of the present board
+        let (min, max) = ((0, 2, 4, 0), (0, 2, 7, 7));
+        let kingside: Vec<Move> = moves.iter().copied().within_region(min, max).collect();
+        assert!(!kingside.is_empty());
+        assert!(kingside.iter().all(|mv| mv.src.2 >= 4 && mv.dst.2 >= 4 && mv.dst.1 == 2));
+        // The pawn of e2 moves within the region, that of d2 and the jumps of the knight do not
+        assert!(kingside.iter().any(|mv| mv.src == (0, 2, 4, 1)));
+        assert!(!kingside.iter().any(|mv| mv.src == (0, 2, 3, 1) || mv.dst.1 != 2));
+        assert!(moves.iter().any(|mv| mv.src == (0, 2, 5, 2) && mv.dst.1 == 0));
+
+        // Widening the region to the past keeps the jumps, which borrowed moves also allow
+        let with_jumps: Vec<&Move> = moves.iter().within_region((0, 0, 4, 0), max).collect();
+        assert_eq!(with_jumps.len(), kingside.len() + moves.iter().filter(|mv| mv.dst.1 == 0 && mv.dst.2 >= 4).count());
+        assert_eq!(moves.iter().within_region(max, min).count(), 0);
+    }
+
+    #[test]
+    fn jumpable_boards_of_pieces() {
+        // 1. Nf3 / Nf6: the knight of f3 may jump back to 1w, branching
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        let virtual_boards = Vec::new();
+        assert_eq!(jumpable_boards(&game, &virtual_boards, &game.info, (0, 2, 5, 2)), vec![(0, 0)]);
+        assert!(jumpable_boards(&game, &virtual_boards, &game.info, (0, 2, 4, 1)).is_empty());
+        // Neither black's knight nor an empty square
+        assert!(jumpable_boards(&game, &virtual_boards, &game.info, (0, 2, 5, 5)).is_empty());
+        assert!(jumpable_boards(&game, &virtual_boards, &game.info, (0, 2, 4, 4)).is_empty());
+
+        // The king of (0T1) may jump to the other timeline, unless the queen of that timeline covers every square it could land on
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["k...", "....", "....", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &["...k", "....", "....", "...K"]))
+            .build()
+            .unwrap();
+        assert_eq!(jumpable_boards(&game, &virtual_boards, &game.info, (0, 0, 0, 0)), vec![(1, 0)]);
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["k...", "....", "....", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &[".k.K", "....", "..q.", "R..."]))
+            .build()
+            .unwrap();
+        assert!(jumpable_boards(&game, &virtual_boards, &game.info, (0, 0, 0, 0)).is_empty());
+        // The rook of (1T1) would land on its own king
+        assert!(jumpable_boards(&game, &virtual_boards, &game.info, (1, 0, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn move_targets_of_pieces() {
+        // The knight of a1 may take the pawn of b3, or jump back to 1w, where it may take the pawn of a3
+        let ranks = ["k...", "pp..", "....", "N..K"];
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &ranks))
+            .add_board(0, 1, testing::board(0, 1, &ranks))
+            .add_board(0, 2, testing::board(0, 2, &ranks))
+            .build()
+            .unwrap();
+        let virtual_boards = Vec::new();
+        let targets = move_targets_from(&game, &virtual_boards, &game.info, (0, 2, 0, 0));
+        let flags = |l, t| -> Vec<(u8, u8, bool, bool)> {
+            targets.on_board(l, t).unwrap().iter().map(|target| (target.x, target.y, target.capture, target.branch)).collect()
+        };
+        assert_eq!(targets.boards.iter().map(|(board, _)| *board).collect::<Vec<_>>(), vec![(0, 0), (0, 2)]);
+        assert_eq!(flags(0, 0), vec![(2, 0, false, true), (0, 2, true, true)]);
+        assert_eq!(flags(0, 2), vec![(2, 1, false, false), (1, 2, true, false)]);
+        assert_eq!(targets.on_board(0, 1), None);
+        assert_eq!(targets.len(), 4);
+        for (board, board_targets) in &targets.boards {
+            for target in board_targets {
+                assert_eq!((target.mv.src, target.mv.dst), ((0, 2, 0, 0), (board.0, board.1, target.x, target.y)));
+                assert!(is_move_valid(&game, &virtual_boards, &game.info, &target.mv));
+            }
+        }
+        // Neither black's pieces nor an empty square may be moved
+        assert!(move_targets_from(&game, &virtual_boards, &game.info, (0, 2, 0, 2)).is_empty());
+        assert!(move_targets_from(&game, &virtual_boards, &game.info, (0, 2, 1, 1)).is_empty());
+
+        // Castling is listed under the rook's square
+        let game = castling_game(&["....k...", "........", "........", "........", "........", "........", "........", "R...K..R"]);
+        let targets = move_targets_from(&game, &virtual_boards, &game.info, (0, 0, 4, 0));
+        let castles: Vec<(u8, u8, bool, bool)> = targets
+            .on_board(0, 0)
+            .unwrap()
+            .iter()
+            .filter(|target| target.mv.castle)
+            .map(|target| (target.x, target.y, target.capture, target.branch))
+            .collect();
+        assert_eq!(castles, vec![(0, 0, false, false), (7, 0, false, false)]);
+    }
+
+    #[test]
+    fn probable_moves_of_all_boards() {
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["k...", "....", "....", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &["...k", "....", ".N..", "...K"]))
+            .build()
+            .unwrap();
+        let virtual_boards = Vec::new();
+        let boards = get_own_boards(&game, &virtual_boards, &game.info);
+        assert_eq!(boards.len(), 2);
+
+        let expected: Vec<(usize, Move)> = boards
+            .iter()
+            .enumerate()
+            .flat_map(|(index, board)| probable_moves(&game, board, &virtual_boards).into_iter().map(move |mv| (index, mv)))
+            .collect();
+        let all: Vec<(usize, Move)> = probable_moves_all(&game, &boards, &virtual_boards).collect();
+        assert_eq!(all, expected);
+        assert!(all.iter().all(|(index, mv)| (mv.src.0, mv.src.1) == (boards[*index].l, boards[*index].t)));
+        assert!(all.iter().any(|(index, _)| *index == 0) && all.iter().any(|(index, _)| *index == 1));
+        assert_eq!(probable_moves_all(&game, &[], &virtual_boards).count(), 0);
+    }
+
+    #[test]
+    fn checking_movesets_of_a_rook() {
+        // The rook of b1 checks from a1 and b4; the king cannot give check
+        let game = testing::game(0, &["k...", "....", "....", ".R.K"]);
+        let virtual_boards = Vec::new();
+        let checks: Vec<String> = checking_movesets(&game, &game.info, &virtual_boards, 0, 0)
+            .map(|(moves, _, info, _)| {
+                assert!(!info.active_player);
+                moves.iter().map(|mv| mv.to_string()).collect::<Vec<_>>().join(" ")
+            })
+            .collect();
+        assert_eq!(checks.len(), 2);
+        assert!(checks.contains(&"(0T1)Rb1a1".to_string()) && checks.contains(&"(0T1)Rb1b4".to_string()));
+
+        // Each position reached gives check exactly when black's king can be taken once black passes
+        for (moves, boards, info, _) in legal_movesets(&game, &game.info, &virtual_boards, 0, 0) {
+            let merged_vboards: Vec<&Board> = boards.iter().collect();
+            let notation = moves.iter().map(|mv| mv.to_string()).collect::<Vec<_>>().join(" ");
+            assert_eq!(checks.contains(&notation), !is_draw(&game, &merged_vboards, &info), "{}", notation);
+        }
+        let standard = crate::variant::standard();
+        assert_eq!(checking_movesets(&standard, &standard.info, &virtual_boards, 0, 0).count(), 0);
+    }
+
+    #[test]
+    fn passed_pawns_across_timelines() {
+        // Black's pawn on c4 stops the pawns of b2 and d3, but not the one of a2
+        let board = testing::board(0, 0, &["k...", "....", "..p.", "...P", "PP..", "...K"]);
+        let game = GameBuilder::new(4, 6).add_board(0, 0, board.clone()).build().unwrap();
+        let virtual_boards = Vec::new();
+        assert_eq!(passed_pawns(&game, &virtual_boards, true), vec![(0, 0, 0, 1)]);
+        assert_eq!(passed_pawns(&game, &virtual_boards, false), vec![]);
+
+        // A black pawn on b5 of the next timeline may travel in front of it
+        let game = GameBuilder::new(4, 6)
+            .add_board(0, 0, board)
+            .add_board(1, 0, testing::board(1, 0, &["...k", ".p..", "....", "....", "....", "K..."]))
+            .build()
+            .unwrap();
+        assert_eq!(passed_pawns(&game, &virtual_boards, true), vec![]);
+        assert_eq!(passed_pawns(&game, &virtual_boards, false), vec![]);
+
+        // Only the last board of each timeline counts
+        let mut game = crate::variant::standard();
+        assert!(passed_pawns(&game, &virtual_boards, true).is_empty());
+        let moved = testing::board(0, 1, &["rnbqkbnr", "........", "........", "........", "........", "........", "PPPPPPPP", "RNBQKBNR"]);
+        assert_eq!(passed_pawns(&game, &vec![&moved], true).len(), 8);
+        testing::play(&mut game, &[((0, 0, 4, 1), (0, 0, 4, 3))]);
+        assert!(passed_pawns(&game, &virtual_boards, true).is_empty());
+    }
+
+    #[test]
+    fn movers_converging_on_a_square() {
+        // Six white pieces may take the knight of (0T1)d5, among which the rook of the other timeline; the knight of c3 is pinned
+        let game = GameBuilder::new(8, 8)
+            .add_board(0, 0, testing::board(0, 0, &[
+                "k.......",
+                "........",
+                "........",
+                "b..n...Q",
+                "....PN..",
+                "..N.....",
+                "B.......",
+                "...RK...",
+            ]))
+            .add_board(1, 0, testing::board(1, 0, &[
+                "k.......",
+                "........",
+                "........",
+                "...R....",
+                "........",
+                "........",
+                "........",
+                "....K...",
+            ]))
+            .build()
+            .unwrap();
+        let virtual_boards = Vec::new();
+        let target = (0, 0, 3, 4);
+        let mut sources: Vec<Coords> = movers_to(&game, &virtual_boards, &game.info, target).iter().map(|mv| mv.src).collect();
+        sources.sort_unstable();
+        assert_eq!(sources, vec![(0, 0, 0, 1), (0, 0, 3, 0), (0, 0, 4, 3), (0, 0, 5, 3), (0, 0, 7, 4), (1, 0, 3, 4)]);
+
+        // Same moves as when generating every move
+        for target in [target, (0, 0, 2, 2), (0, 0, 4, 1), (1, 0, 3, 7), (0, 0, 6, 6)] {
+            let mut expected: Vec<Move> = get_own_boards(&game, &virtual_boards, &game.info)
+                .into_iter()
+                .flat_map(|board| probable_moves(&game, board, &virtual_boards))
+                .filter(|mv| mv.dst == target && !mv.castle && is_move_legal(&game, &virtual_boards, &game.info, mv))
+                .collect();
+            let mut movers = movers_to(&game, &virtual_boards, &game.info, target);
+            expected.sort_by_key(|mv| mv.src);
+            movers.sort_by_key(|mv| mv.src);
+            assert_eq!(movers, expected, "{:?}", target);
+        }
+        assert!(movers_to(&game, &virtual_boards, &game.info, (2, 0, 3, 4)).is_empty());
+    }
+
+    #[test]
+    fn check_rules_decide_which_pieces_must_be_safe() {
+        // White has two kings, that of d1 being checked by the rook
+        let mut game = testing::game(0, &[".k.r", "....", "....", "KQ.K"]);
+        let virtual_boards = Vec::new();
+        let moves = probable_moves(&game, game.get_board(0, 0).unwrap(), &virtual_boards);
+        let find = |src: (u8, u8), dst: (u8, u8)| *moves.iter().find(|mv| (mv.src.2, mv.src.3, mv.dst.2, mv.dst.3) == (src.0, src.1, dst.0, dst.1)).unwrap();
+        // The other king steps away, the queen blocks the check, the checked king steps away
+        let movesets = [find((0, 0), (0, 1)), find((1, 0), (3, 2)), find((3, 0), (2, 0))];
+
+        for (rule, expected) in [
+            (CheckRule::AllKingsSafe, [false, true, true]),
+            (CheckRule::AnyKingSafe, [true, true, true]),
+            (CheckRule::SpecificRoyal(Piece::QueenB), [true, false, true]),
+        ] {
+            game.check_rule = rule;
+            let legal: Vec<bool> = movesets
+                .iter()
+                .map(|mv| match Moveset(vec![*mv]).is_legal(&game, &virtual_boards, &game.info) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        assert_eq!(e, TurnError::Check);
+                        false
+                    }
+                })
+                .collect();
+            assert_eq!(legal, expected, "{:?}", rule);
+            let generated = legal_movesets(&game, &game.info, &virtual_boards, 0, 0).count();
+            assert_eq!(generated, moves.iter().filter(|mv| Moveset(vec![**mv]).is_legal(&game, &virtual_boards, &game.info).is_ok()).count());
+        }
+
+        assert!(CheckRule::AnyKingSafe.is_royal(Piece::KingW, true));
+        assert!(!CheckRule::AnyKingSafe.is_royal(Piece::KingB, true));
+        assert!(CheckRule::SpecificRoyal(Piece::QueenB).is_royal(Piece::QueenW, true));
+        assert!(!CheckRule::SpecificRoyal(Piece::QueenB).is_royal(Piece::KingW, true));
+    }
+
+    #[test]
+    fn forced_to_branch_out_of_check() {
+        // The king of timeline 0 is in check, and the rooks of timelines -1 and 1 cover the squares it could travel to on their boards;
+        // only the knight's jumps to the past move the present back, so that the board of the king no longer has to be played on
+        let checked = [
+            "...rrr.k",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "....K...",
+        ];
+        let covered = [
+            "...rrr.k",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "......N.",
+        ];
+        let watching = [
+            "...rrr..",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+            "........",
+        ];
+        let mut builder = GameBuilder::new(8, 8);
+        for t in 0..=2 {
+            builder = builder
+                .add_board(-1, t, testing::board(-1, t, &watching))
+                .add_board(0, t, testing::board(0, t, &checked))
+                .add_board(1, t, testing::board(1, t, &covered));
+        }
+        let game = builder.build().unwrap();
+        let virtual_boards = Vec::new();
+        assert!(is_forced_to_branch(&game, &virtual_boards, &game.info));
+        assert!(legal_movesets(&game, &game.info, &virtual_boards, 0, 0).all(|(_, _, info, _)| info.max_timeline > 1));
+
+        let standard = crate::variant::standard();
+        assert!(!is_forced_to_branch(&standard, &virtual_boards, &standard.info));
+        // Without any legal moveset, there is nothing to branch with
+        let mated = testing::game(0, &["rr.k", "....", "....", "K..."]);
+        assert!(!is_forced_to_branch(&mated, &virtual_boards, &mated.info));
+    }
+
+    #[test]
+    fn checkers_of_king() {
+        // The rook checks along the first rank and the knight from b2; the rook of c2 does not reach d1
+        let game = testing::game(0, &["k...", "....", ".nr.", "r..K"]);
+        let virtual_boards = Vec::new();
+        let mut checkers: Vec<(u8, u8)> = checkers_of(&game, &virtual_boards, &game.info, (0, 0, 3, 0))
+            .into_iter()
+            .map(|(src, mv)| {
+                assert_eq!(mv.src, src);
+                assert_eq!((mv.dst.2, mv.dst.3, mv.dst_piece), (3, 0, Piece::KingW));
+                (src.2, src.3)
+            })
+            .collect();
+        checkers.sort_unstable();
+        assert_eq!(checkers, vec![(0, 0), (1, 1)]);
+
+        // Black's king is not attacked, and there is no king on a4
+        assert!(checkers_of(&game, &virtual_boards, &game.info, (0, 0, 0, 3)).is_empty());
+        assert!(checkers_of(&game, &virtual_boards, &game.info, (0, 0, 0, 2)).is_empty());
+    }
+
+    #[test]
+    fn king_attack_score_of_nearby_attackers() {
+        use crate::resolve::king_attack_units;
+        let virtual_boards = Vec::new();
+        let score = |game: &Game, white| king_attack_score(game, &virtual_boards, &game.info, white);
+
+        let game = testing::game(0, &["...k", "....", "....", "K..."]);
+        assert_eq!((score(&game, true), score(&game, false)), (0, 0));
+        // The rook reaches b2 and a2, next to white's king
+        let game = testing::game(0, &["...k", "....", "..r.", "K..."]);
+        assert_eq!(score(&game, true), 2 * king_attack_units(Piece::RookB));
+        // The knight adds a2 and b1
+        let game = testing::game(0, &["...k", "..n.", "..r.", "K..."]);
+        assert_eq!(score(&game, true), 2 * king_attack_units(Piece::RookB) + 2 * king_attack_units(Piece::KnightB));
+        assert_eq!(score(&game, false), 0);
+
+        // A rook of another timeline may travel onto b2
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["...k", "....", "....", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &["...k", "....", ".r..", "...."]))
+            .build()
+            .unwrap();
+        assert_eq!(score(&game, true), king_attack_units(Piece::RookB));
+    }
+
+    #[test]
+    fn controlled_squares_of_both_sides() {
+        // The pawns control the third (sixth) rank, which the knights add nothing to; black's boards are passed on first
+        let game = crate::variant::standard();
+        let virtual_boards = Vec::new();
+        assert_eq!(controlled_squares(&game, &virtual_boards, &game.info, true), 8);
+        assert_eq!(controlled_squares(&game, &virtual_boards, &game.info, false), 8);
+
+        // The rook reaches a2, a3, b1, c1 and takes on a4, the king adds c2 and d2
+        let game = testing::game(0, &["k...", "....", "....", "R..K"]);
+        assert_eq!(controlled_squares(&game, &virtual_boards, &game.info, true), 7);
+        assert_eq!(controlled_squares(&game, &virtual_boards, &game.info, false), 3);
+
+        // The pawn of b1 controls a2 and c2, but not b2
+        let game = testing::game(0, &["k...", "....", "....", ".P.K"]);
+        assert_eq!(controlled_squares(&game, &virtual_boards, &game.info, true), 4);
+    }
+
+    #[test]
+    fn threat_map_counts_attackers() {
+        let game = testing::game(0, &["k..Q", "....", ".nr.", "r..K"]);
+        let virtual_boards = Vec::new();
+
+        // White's king is attacked by the rook of a1 and the knight, its queen by nothing
+        let threats = threat_map(&game, &virtual_boards, &game.info, true);
+        let expected: HashMap<Coords, u8> = vec![((0, 0, 3, 0), 2), ((0, 0, 3, 3), 0)].into_iter().collect();
+        assert_eq!(threats, expected);
+
+        // The queen attacks black's king and knight, and the king the rook of c2
+        let threats = threat_map(&game, &virtual_boards, &game.info, false);
+        let expected: HashMap<Coords, u8> =
+            vec![((0, 0, 0, 3), 1), ((0, 0, 1, 1), 1), ((0, 0, 2, 1), 1), ((0, 0, 0, 0), 0)].into_iter().collect();
+        assert_eq!(threats, expected);
+    }
+
+    /// Returns the moves of the movesets yielded by `check_evasions` (without their noops), sorted, after checking that each moveset is legal and leaves none of white's kings in check
+    fn evasions(game: &Game) -> Vec<Vec<Move>> {
+        let virtual_boards = Vec::new();
+        let mut res: Vec<Vec<Move>> = check_evasions(game, &game.info, &virtual_boards, 0, 0)
+            .map(|(moves, boards, new_info, _)| {
+                let moves: Vec<Move> = moves.into_iter().filter(|mv| !mv.noop).collect();
+                assert_eq!(Moveset(moves.clone()).is_legal(game, &virtual_boards, &game.info), Ok(()));
+                let merged_vboards: Vec<&Board> = boards.iter().collect();
+                for board in get_opponent_boards(game, &merged_vboards, &new_info) {
+                    assert!(probable_moves(game, board, &merged_vboards).iter().all(|mv| mv.dst_piece != Piece::KingW));
+                }
+                moves
+            })
+            .collect();
+        res.sort_by_key(|moves| format!("{:?}", moves));
+        res
+    }
+
+    /// Same as `evasions`, with the movesets of `legal_movesets`
+    fn legal(game: &Game) -> Vec<Vec<Move>> {
+        let virtual_boards = Vec::new();
+        let mut res: Vec<Vec<Move>> = legal_movesets(game, &game.info, &virtual_boards, 0, 0)
+            .map(|(moves, _, _, _)| moves.into_iter().filter(|mv| !mv.noop).collect())
+            .collect();
+        res.sort_by_key(|moves| format!("{:?}", moves));
+        res
+    }
+
+    #[test]
+    fn evasions_of_checks_from_other_timelines() {
+        let empty = ["........", "........", "........", "........", "........", "........", "........", "........"];
+        let checked = ["........", "........", "........", "........", "........", "........", "......P.", "....K..."];
+        let with_first_rank = |first_rank: &'static str| {
+            let mut ranks = empty;
+            ranks[7] = first_rank;
+            ranks
+        };
+
+        // A black knight of timeline 0 checks the king of timeline 1, on the board that white creates: only king moves or moves leaving that board may parry it
+        let game = GameBuilder::new(8, 8)
+            .add_board(0, 1, testing::board(0, 1, &with_first_rank("..n.....")))
+            .add_board(1, 0, testing::board(1, 0, &checked))
+            .present(0)
+            .build()
+            .unwrap();
+        assert_eq!(checkers_of(&game, &vec![], &game.info, (1, 0, 4, 0)).len(), 1);
+        let movesets = evasions(&game);
+        assert!(!movesets.is_empty());
+        assert_eq!(movesets, legal(&game));
+        for moves in &movesets {
+            assert!(moves.iter().all(|mv| mv.src_piece.is_king() || (mv.src.0, mv.src.1) != (mv.dst.0, mv.dst.1)));
+        }
+
+        // A black rook of timeline -1 checks it through timeline 0, where white may block it: the pawn of timeline 1 may then move
+        let game = GameBuilder::new(8, 8)
+            .add_board(-1, 1, testing::board(-1, 1, &with_first_rank("....r...")))
+            .add_board(0, 0, testing::board(0, 0, &with_first_rank("R.......")))
+            .add_board(1, 0, testing::board(1, 0, &checked))
+            .present(0)
+            .build()
+            .unwrap();
+        assert_eq!(checkers_of(&game, &vec![], &game.info, (1, 0, 4, 0)).len(), 1);
+        let movesets = evasions(&game);
+        assert_eq!(movesets, legal(&game));
+        assert!(movesets.iter().any(|moves| {
+            moves.iter().any(|mv| mv.src_piece == Piece::RookW && mv.dst == (0, 0, 4, 0))
+                && moves.iter().any(|mv| mv.src_piece == Piece::PawnW && mv.src.0 == 1 && mv.dst.0 == 1)
+        }));
+    }
+
+    /// Builds a game made of a single board at `(0T1)` (see `testing::board`), in which white may castle on either side
+    fn castling_game(ranks: &[&str]) -> Game {
+        let mut board = testing::board(0, 0, ranks);
+        board.castle_w = (true, true);
+        GameBuilder::new(8, 8).add_board(0, 0, board).build().unwrap()
+    }
+
+    /// Returns the notation of the castling moves of white's board
+    fn castles(game: &Game) -> Vec<String> {
+        let board = game.get_board(0, game.info.present).unwrap();
+        let mut res: Vec<String> = probable_moves(game, board, &vec![])
+            .into_iter()
+            .filter(|mv| mv.castle)
+            .map(|mv| mv.to_string())
+            .collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn castling_through_attacked_squares() {
+        let with_rook_on = |file: usize| {
+            let mut back_rank = String::from("k.......");
+            if file > 0 {
+                back_rank.replace_range(file..=file, "r");
+            }
+            castling_game(&[&back_rank, "........", "........", "........", "........", "........", "........", "R...K..R"])
+        };
+        assert_eq!(castles(&with_rook_on(0)), vec!["(0T1)O-O", "(0T1)O-O-O"]);
+        // Out of check
+        assert!(castles(&with_rook_on(4)).is_empty());
+        // Through f1 or d1
+        assert_eq!(castles(&with_rook_on(5)), vec!["(0T1)O-O-O"]);
+        assert_eq!(castles(&with_rook_on(3)), vec!["(0T1)O-O"]);
+        // Into g1, while b1 is not crossed by the king
+        assert_eq!(castles(&with_rook_on(6)), vec!["(0T1)O-O-O"]);
+        assert_eq!(castles(&with_rook_on(1)), vec!["(0T1)O-O", "(0T1)O-O-O"]);
+    }
+
+    #[test]
+    fn castling_through_squares_attacked_from_another_timeline() {
+        // A black rook on the first rank of the board of timeline 1 where it is black's turn, which can reach the same square of the board that castling creates
+        let with_rook_on = |file: usize| {
+            let mut board = testing::board(0, 0, &["k.......", "........", "........", "........", "........", "........", "........", "R...K..R"]);
+            board.castle_w = (true, true);
+            let mut first_rank = String::from("........");
+            first_rank.replace_range(file..=file, "r");
+            let other = testing::board(1, 1, &["........", "........", "........", "........", "........", "........", "........", &first_rank]);
+            GameBuilder::new(8, 8).add_board(0, 0, board).add_board(1, 1, other).present(0).build().unwrap()
+        };
+        assert_eq!(castles(&with_rook_on(0)), vec!["(0T1)O-O", "(0T1)O-O-O"]);
+        assert_eq!(castles(&with_rook_on(5)), vec!["(0T1)O-O-O"]);
+        assert_eq!(castles(&with_rook_on(3)), vec!["(0T1)O-O"]);
+        assert!(castles(&with_rook_on(4)).is_empty());
+    }
+
+    #[test]
+    fn inverse_moves_restore_boards() {
+        let mut jumps = crate::variant::standard();
+        testing::play(&mut jumps, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut jumps, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        let games = [
+            jumps,
+            castling_game(&["k.......", "........", "........", "........", "........", "........", "........", "R...K..R"]),
+            testing::game(0, &["..k.", "....", ".r..", "Q..K"]),
+            testing::game(0, &["...k", "P...", "....", "K..."]),
+        ];
+        let mut kinds = HashSet::new();
+
+        for game in games.iter() {
+            let virtual_boards = Vec::new();
+            let board = game.get_board(0, game.info.present).unwrap();
+            for mv in probable_moves(game, board, &virtual_boards) {
+                let (_, boards) = mv.generate_vboards(game, &game.info, &virtual_boards, &vec![]).unwrap();
+                for after in boards {
+                    // The board moved from, or the board that a jump lands on
+                    let before = if (after.l, after.t) == (mv.src.0, mv.src.1 + 1) {
+                        game.get_board(mv.src.0, mv.src.1).unwrap()
+                    } else {
+                        game.get_board(mv.dst.0, mv.dst.1).unwrap()
+                    };
+                    let mut undone = after.clone();
+                    mv.inverse(before).undo(&mut undone);
+                    assert_eq!((undone.l, undone.t), (before.l, before.t), "{}", mv);
+                    assert_eq!(undone.pieces, before.pieces, "{}", mv);
+                    assert_eq!((undone.castle_w, undone.castle_b), (before.castle_w, before.castle_b), "{}", mv);
+                }
+                kinds.insert((mv.castle, mv.captured().is_some(), (mv.src.0, mv.src.1) != (mv.dst.0, mv.dst.1)));
+            }
+        }
+        // Castling, captures and jumps were all undone
+        assert!(kinds.iter().any(|k| k.0) && kinds.iter().any(|k| k.1) && kinds.iter().any(|k| k.2));
+    }
+
+    #[test]
+    fn captured_pieces() {
+        let virtual_boards = Vec::new();
+        let game = castling_game(&["....k...", "........", "........", "........", "........", "........", "........", "....K..R"]);
+        let white_moves = probable_moves(&game, game.get_board(0, 0).unwrap(), &virtual_boards);
+        let castle = white_moves.iter().find(|mv| mv.castle).unwrap();
+        assert_eq!(castle.dst_piece, Piece::RookW);
+        assert_eq!(castle.captured(), None);
+        assert!(white_moves.iter().all(|mv| mv.captured().is_none()));
+        assert_eq!(Move::noop((0, 0)).captured(), None);
+
+        // 1. e4 d5
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 4, 1), (0, 0, 4, 3))]);
+        testing::play(&mut game, &[((0, 1, 3, 6), (0, 1, 3, 4))]);
+        let moves = probable_moves(&game, game.get_board(0, 2).unwrap(), &virtual_boards);
+        let capture = moves.iter().find(|mv| mv.src == (0, 2, 4, 3) && mv.dst == (0, 2, 3, 4)).unwrap();
+        assert_eq!(capture.en_passant, None);
+        assert_eq!(capture.captured(), Some(Piece::PawnB));
+
+        // 1. e4 a6 2. e5 d5, after which white may take en passant on d6
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 4, 1), (0, 0, 4, 3))]);
+        testing::play(&mut game, &[((0, 1, 0, 6), (0, 1, 0, 5))]);
+        testing::play(&mut game, &[((0, 2, 4, 3), (0, 2, 4, 4))]);
+        testing::play(&mut game, &[((0, 3, 3, 6), (0, 3, 3, 4))]);
+        let moves = probable_moves(&game, game.get_board(0, 4).unwrap(), &virtual_boards);
+        let en_passant = moves.iter().find(|mv| mv.en_passant.is_some()).unwrap();
+        assert_eq!(en_passant.dst, (0, 4, 3, 5));
+        assert_eq!(en_passant.dst_piece, Piece::Blank);
+        assert_eq!(en_passant.captured(), Some(Piece::PawnB));
+        let (_, boards) = en_passant.generate_vboards(&game, &game.info, &virtual_boards, &vec![]).unwrap();
+        assert_eq!((boards[0].get(3, 5), boards[0].get(3, 4), boards[0].get(4, 4)), (Some(Piece::PawnW), Some(Piece::Blank), Some(Piece::Blank)));
+    }
+
+    #[test]
+    fn pawns_promote_on_the_promotion_ranks() {
+        // Returns the piece landing on the square in front of the pawn of `(x, y)`
+        let promoted = |game: &Game, x: u8, y: u8| {
+            let virtual_boards = Vec::new();
+            let board = game.get_board(0, game.info.present).unwrap();
+            let mv = probable_moves(game, board, &virtual_boards)
+                .into_iter()
+                .find(|mv| (mv.src.2, mv.src.3) == (x, y) && mv.dst.2 == x && mv.dst.1 == board.t)
+                .unwrap();
+            let (_, boards) = mv.generate_vboards(game, &game.info, &virtual_boards, &vec![]).unwrap();
+            boards[0].get(mv.dst.2, mv.dst.3).unwrap()
+        };
+
+        let white = testing::game(0, &["...k", "P...", "....", "K..."]);
+        let black = testing::game(1, &["...k", "....", "...p", "K..."]);
+        assert_eq!(white.promotion_rank(true), 3);
+        assert_eq!(black.promotion_rank(false), 0);
+        assert_eq!(promoted(&white, 0, 2), Piece::QueenW);
+        assert_eq!(promoted(&black, 3, 1), Piece::QueenB);
+
+        // With the promotion ranks moved towards the middle, the same pawns promote one rank earlier
+        let mut white = testing::game(0, &["...k", "....", "P...", "K..."]);
+        let mut black = testing::game(1, &["...k", "...p", "....", "K..."]);
+        assert_eq!(promoted(&white, 0, 1), Piece::PawnW);
+        white.promotion_ranks = Some((2, 1));
+        black.promotion_ranks = Some((2, 1));
+        assert_eq!((white.promotion_rank(true), white.promotion_rank(false)), (2, 1));
+        assert_eq!(promoted(&white, 0, 1), Piece::QueenW);
+        assert_eq!(promoted(&black, 3, 2), Piece::QueenB);
+    }
+
+    #[test]
+    fn castling_rights_after_king_and_rook_moves() {
+        // The rook on b1 is the one castling long, not the one on a1
+        let mut game = castling_game(&["........", "........", "...k....", "........", "........", "........", "........", "RR..K..R"]);
+        let rights = |game: &Game| game.get_board(0, game.info.present).unwrap().castle_w;
+
+        testing::play(&mut game, &[((0, 0, 0, 0), (0, 0, 0, 1))]);
+        testing::play(&mut game, &[((0, 1, 3, 5), (0, 1, 3, 4))]);
+        assert_eq!(rights(&game), (true, true));
+        assert_eq!(castles(&game), vec!["(0T2)O-O", "(0T2)O-O-O"]);
+
+        testing::play(&mut game, &[((0, 2, 1, 0), (0, 2, 1, 2))]);
+        testing::play(&mut game, &[((0, 3, 3, 4), (0, 3, 3, 5))]);
+        assert_eq!(rights(&game), (false, true));
+        // The rook coming back does not give the right back
+        testing::play(&mut game, &[((0, 4, 1, 2), (0, 4, 1, 0))]);
+        testing::play(&mut game, &[((0, 5, 3, 5), (0, 5, 3, 4))]);
+        assert_eq!(rights(&game), (false, true));
+        assert_eq!(castles(&game), vec!["(0T4)O-O"]);
+
+        // Neither does the king
+        testing::play(&mut game, &[((0, 6, 4, 0), (0, 6, 4, 1))]);
+        testing::play(&mut game, &[((0, 7, 3, 4), (0, 7, 3, 5))]);
+        testing::play(&mut game, &[((0, 8, 4, 1), (0, 8, 4, 0))]);
+        testing::play(&mut game, &[((0, 9, 3, 5), (0, 9, 3, 4))]);
+        assert_eq!(rights(&game), (false, false));
+        assert!(castles(&game).is_empty());
+    }
+
+    #[test]
+    fn random_legal_moveset_is_legal() {
+        // The knight on b2 is pinned by the bishop; the rook can take the pawn on e5
+        let game = testing::game(0, &[
+            ".......k",
+            "........",
+            "........",
+            "R...p...",
+            "...b....",
+            "........",
+            ".N......",
+            "K.......",
+        ]);
+        let mut rng = SplitMix64::new(3);
+
+        for &capture_weight in &[0.0, 10.0, -1.0, -5.0] {
+            let mut captured = false;
+            for _ in 0..50 {
+                let (moves, _, info, _) =
+                    random_legal_moveset(&game, &game.info, &vec![], 0, 0, capture_weight, &mut rng).unwrap();
+                assert_eq!(Moveset(moves.clone()).is_legal(&game, &vec![], &game.info), Ok(()));
+                assert!(!info.active_player);
+                assert!(moves.iter().all(|mv| mv.src != (0, 0, 1, 1)));
+                captured |= moves.iter().any(|mv| mv.captured().is_some());
+            }
+            // Negative weights are clamped, rather than making the captures the most likely movesets
+            assert_eq!(captured, capture_weight >= 0.0, "capture_weight = {}", capture_weight);
+        }
+    }
+
+    #[test]
+    fn dedup_movesets_played_in_another_order() {
+        // White has a king to move on each of the two timelines
+        let game = GameBuilder::new(4, 4)
+            .add_board(0, 0, testing::board(0, 0, &["k...", "....", "....", "K..."]))
+            .add_board(1, 0, testing::board(1, 0, &["...k", "....", "....", "...K"]))
+            .build()
+            .unwrap();
+        let virtual_boards = Vec::new();
+        let king_move = |l: i32, src: (u8, u8), dst: (u8, u8)| {
+            *probable_moves(&game, game.get_board(l, 0).unwrap(), &virtual_boards)
+                .iter()
+                .find(|mv| mv.src == (l, 0, src.0, src.1) && mv.dst == (l, 0, dst.0, dst.1))
+                .unwrap()
+        };
+        let node = |moves: Vec<Move>| {
+            score_moveset(&game, &virtual_boards, &game.info, get_opponent_boards(&game, &virtual_boards, &game.info).into_iter(), moves).unwrap()
+        };
+        let (a, b, c) = (king_move(0, (0, 0), (1, 0)), king_move(1, (3, 0), (2, 0)), king_move(0, (0, 0), (0, 1)));
+
+        let deduped: Vec<Vec<Move>> = vec![node(vec![a, b]), node(vec![b, a]), node(vec![c, b]), node(vec![b, c])]
+            .into_iter()
+            .dedup_by_result()
+            .map(|(moves, _, _, _)| moves)
+            .collect();
+        assert_eq!(deduped, vec![vec![a, b], vec![c, b]]);
+
+        // Every position reached by `legal_movesets` is yielded once
+        let mut positions = HashSet::new();
+        let n_legal = legal_movesets(&game, &game.info, &virtual_boards, 0, 0)
+            .filter(|(_, boards, _, _)| positions.insert(boards.iter().fold(0, |key, b| key ^ board_key(b))))
+            .count();
+        assert_eq!(legal_movesets(&game, &game.info, &virtual_boards, 0, 0).dedup_by_result().count(), n_legal);
+    }
+
+    #[test]
+    fn quiets_captures_and_checks_partition_legal_movesets() {
+        // White's queen may take the rook on b2, check the king on b4, or both
+        let game = testing::game(0, &[".k..", "....", ".r..", "Q..K"]);
+        let virtual_boards = Vec::new();
+        let keys = |iter: &mut dyn Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)>| {
+            iter.map(|(moves, _, _, _)| format!("{:?}", moves)).collect::<HashSet<String>>()
+        };
+        let legal = keys(&mut legal_movesets(&game, &game.info, &virtual_boards, 0, 0));
+        let captures = keys(&mut capture_movesets(&game, &game.info, &virtual_boards, 0, 0));
+        let checks = keys(&mut checking_movesets(&game, &game.info, &virtual_boards, 0, 0));
+        let quiets = keys(&mut legal_movesets(&game, &game.info, &virtual_boards, 0, 0).quiets_only(&game, &virtual_boards));
+        assert!(!captures.is_empty() && !checks.is_empty() && !quiets.is_empty());
+        assert!(captures.intersection(&checks).next().is_some());
+
+        let union: HashSet<String> = quiets.iter().chain(captures.iter()).chain(checks.iter()).cloned().collect();
+        assert_eq!(union, legal);
+        assert!(quiets.is_disjoint(&captures));
+        assert!(quiets.is_disjoint(&checks));
+    }
+
+    #[test]
+    fn generation_orders() {
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        let board = game.get_board(0, 2).unwrap();
+        let virtual_boards = Vec::new();
+        let jumps = |mv: &Move| (mv.src.0, mv.src.1) != (mv.dst.0, mv.dst.1);
+        let moves = probable_moves(&game, board, &virtual_boards);
+        let (jumping, physical): (Vec<Move>, Vec<Move>) = moves.iter().partition(|mv| jumps(mv));
+        assert!(!jumping.is_empty() && !physical.is_empty());
+
+        let ordered = probable_moves_ordered(&game, board, &virtual_boards, GenerationOrder::PhysicalFirst);
+        assert_eq!(ordered, physical.iter().chain(jumping.iter()).copied().collect::<Vec<_>>());
+        let ordered = probable_moves_ordered(&game, board, &virtual_boards, GenerationOrder::JumpsFirst);
+        assert_eq!(ordered, jumping.iter().chain(physical.iter()).copied().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn directions_of_each_piece() {
+        let counts: Vec<usize> = (0..=4).map(|n| Direction5D::n_gonal(n).len()).collect();
+        assert_eq!(counts, vec![48, 8, 24, 32, 16]);
+        assert_eq!(Direction5D::queen().count(), 80);
+
+        for (n, directions) in [Direction5D::rook(), Direction5D::bishop(), Direction5D::unicorn(), Direction5D::dragon()].iter().enumerate() {
+            let distinct: HashSet<Direction5D> = directions.iter().copied().collect();
+            assert_eq!(distinct.len(), directions.len());
+            for d in directions.iter() {
+                assert_eq!(d.axes(), n + 1);
+                assert!([d.l, d.t, d.x, d.y].iter().all(|c| c.abs() <= 1));
+                // Every direction comes with its opposite
+                assert!(distinct.contains(&Direction5D::new(-d.l, -d.t, -d.x, -d.y)));
+            }
+        }
+        for d in Direction5D::knight() {
+            let mut steps: Vec<isize> = [d.l, d.t, d.x, d.y].iter().map(|c| c.abs()).collect();
+            steps.sort_unstable();
+            assert_eq!(steps, vec![0, 0, 1, 2]);
+        }
+
+        let d = Direction5D::new(1, -1, 0, 2);
+        assert_eq!(<(isize, isize, isize, isize)>::from(d), (1, -1, 0, 2));
+        assert_eq!(Direction5D::from((1, -1, 0, 2)), d);
+    }
+
+    #[test]
+    fn rays_stop_at_pieces_and_edges() {
+        let game = testing::game(0, &["k...", "....", ".p..", "R..K"]);
+        let board = game.get_board(0, 0).unwrap();
+        let virtual_boards = Vec::new();
+        let ray = |direction| Ray::new(&game, board, &virtual_boards, (0, 0, 0, 0), direction, true).collect::<Vec<_>>();
+
+        // Up to the king to take, before the own king, up to the pawn to take, and off the board
+        assert_eq!(ray((0, 0, 0, 1)), vec![(0, 0, 0, 1), (0, 0, 0, 2), (0, 0, 0, 3)]);
+        assert_eq!(ray((0, 0, 1, 0)), vec![(0, 0, 1, 0), (0, 0, 2, 0)]);
+        assert_eq!(ray((0, 0, 1, 1)), vec![(0, 0, 1, 1)]);
+        assert!(ray((0, 0, -1, 0)).is_empty());
+        // There is no board before the first one
+        assert!(ray((0, -1, 0, 0)).is_empty());
+        // For a black piece, the pawn of b2 blocks the diagonal
+        let black: Vec<Coords> = Ray::new(&game, board, &virtual_boards, (0, 0, 0, 0), (0, 0, 1, 1), false).collect();
+        assert!(black.is_empty());
+
+        // Back in time, the knight of f3 reaches the empty f3 of the first board
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        let board = game.get_board(0, 2).unwrap();
+        let mut ray = Ray::new(&game, board, &virtual_boards, (0, 2, 5, 2), (0, -1, 0, 0), true);
+        assert_eq!(ray.next(), Some((0, 0, 5, 2)));
+        assert_eq!(ray.next(), None);
+        assert_eq!(ray.next(), None);
+    }
+
+    #[test]
+    fn scan_orders_yield_the_same_moves() {
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        testing::play(&mut game, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+        let virtual_boards = Vec::new();
+        let sorted = |mut moves: Vec<Move>| {
+            moves.sort_by_key(|mv| format!("{:?}", mv));
+            moves
+        };
+
+        for board in get_own_boards(&game, &virtual_boards, &game.info) {
+            let expected = probable_moves(&game, board, &virtual_boards);
+            assert!(!expected.is_empty());
+            for &order in &[ScanOrder::RowMajor, ScanOrder::CenterOut, ScanOrder::PieceValue] {
+                let moves = probable_moves_scanned(&game, board, &virtual_boards, order);
+                if order == ScanOrder::RowMajor {
+                    assert_eq!(moves, expected);
+                }
+                assert_eq!(sorted(moves), sorted(expected.clone()), "{:?}", order);
+            }
+        }
+    }
+
+    #[test]
+    fn piece_counts_follow_moves() {
+        // White's queen takes black's last piece besides the king
+        let mut game = testing::game(0, &["..k.", "....", ".r..", "Q..K"]);
+        let board = game.get_board(0, 0).unwrap();
+        assert_eq!((board.piece_count(true), board.piece_count(false)), (2, 2));
+        testing::play(&mut game, &[((0, 0, 0, 0), (0, 0, 1, 1))]);
+        let board = game.get_board(0, 1).unwrap();
+        assert_eq!((board.piece_count(true), board.piece_count(false)), (2, 1));
+        let mut recounted = board.clone();
+        recounted.recount_pieces();
+        assert_eq!(recounted.piece_count(false), 1);
+
+        // A board without any of the active player's pieces, as on a timeline where they were all taken
+        let game = testing::game(0, &["..k.", "....", ".r..", "...."]);
+        let board = game.get_board(0, 0).unwrap();
+        assert!(!board.has_pieces_of(true));
+        assert!(probable_moves(&game, board, &vec![]).is_empty());
+    }
+
+    #[cfg(feature = "gen-stats")]
+    #[test]
+    fn no_squares_scanned_without_own_pieces() {
+        let game = testing::game(0, &["..k.", "....", ".r..", "...."]);
+        let mut stats = GenStats::new();
+        assert!(probable_moves_with_stats(&game, game.get_board(0, 0).unwrap(), &vec![], &mut stats).is_empty());
+        assert_eq!(stats.squares_scanned, 0);
+        assert_eq!(stats.total_moves(), 0);
+
+        let game = testing::game(0, &["..k.", "....", ".r..", "Q..K"]);
+        let moves = probable_moves_with_stats(&game, game.get_board(0, 0).unwrap(), &vec![], &mut stats);
+        assert_eq!(stats.total_moves(), moves.len());
+        assert_eq!(stats.squares_scanned, 3 * 16);
+    }
+
+    #[cfg(feature = "gen-stats")]
+    #[test]
+    fn stats_sum_to_the_moves_generated() {
+        let game = testing::fixture("standard-complex-2.json");
+        let virtual_boards = Vec::new();
+        let mut stats = GenStats::new();
+        let mut n_moves = 0;
+        let mut per_kind: HashMap<&'static str, usize> = HashMap::new();
+        for board in get_own_boards(&game, &virtual_boards, &game.info) {
+            let moves = probable_moves_with_stats(&game, board, &virtual_boards, &mut stats);
+            let mut expected = probable_moves(&game, board, &virtual_boards);
+            assert_eq!(moves.len(), expected.len());
+            expected.retain(|mv| !moves.contains(mv));
+            assert!(expected.is_empty());
+            for mv in moves.iter() {
+                *per_kind.entry(mv.src_piece.as_uppercase()).or_default() += 1;
+            }
+            n_moves += moves.len();
+        }
+
+        assert!(n_moves > 0);
+        assert_eq!(stats.total_moves(), n_moves);
+        assert_eq!(stats.per_kind.iter().map(|(kind, (n, _))| (*kind, *n)).filter(|(_, n)| *n > 0).collect::<HashMap<_, _>>(), per_kind);
+        assert_eq!(stats.total_time(), stats.per_kind.values().map(|(_, d)| *d).sum());
+        assert_eq!(stats.to_string().lines().count(), stats.per_kind.len());
+    }
+
+    #[test]
+    fn move_exposes_king_across_timelines() {
+        // The black bishop on (2T1) e3 looks at the white king on (0T1) e1 along the timelines and ranks, through e2 of the board that white plays on (1T0)
+        let game = GameBuilder::new(8, 8)
+            .add_board(0, 1, testing::board(0, 1, &[
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "....K...",
+            ]))
+            .add_board(1, 0, testing::board(1, 0, &[
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "....N...",
+                ".......R",
+            ]))
+            .add_board(2, 1, testing::board(2, 1, &[
+                "....k...",
+                "........",
+                "........",
+                "........",
+                "........",
+                "....b...",
+                "........",
+                "........",
+            ]))
+            .build()
+            .unwrap();
+        let board = game.get_board(1, 0).unwrap();
+        let moves = probable_moves(&game, board, &vec![]);
+        let valid = validate_moves(&game, &vec![], &game.info, &moves);
+
+        // The knight cannot leave e2, not even within its board, while the rook is free to move
+        for (mv, valid) in moves.iter().zip(valid) {
+            assert_eq!(valid, !mv.src_piece.is_knight(), "{}", mv);
+        }
+        assert!(moves.iter().any(|mv| mv.src_piece.is_knight() && mv.dst.0 == 1 && mv.dst.1 == 0));
+        assert!(moves.iter().any(|mv| mv.src_piece.is_rook()));
+
+        let knight = *moves.iter().find(|mv| mv.src_piece.is_knight() && mv.dst == (1, 0, 5, 3)).unwrap();
+        let rook = *moves.iter().find(|mv| mv.src_piece.is_rook() && mv.dst == (1, 0, 7, 1)).unwrap();
+        assert!(!is_move_valid(&game, &vec![], &game.info, &knight));
+        let mut game_after = game.clone();
+        assert!(game_after.advance_turn(&Moveset(vec![knight])).is_err());
+        assert!(game_after.advance_turn(&Moveset(vec![rook])).is_ok());
+    }
+}
@@ -1,4 +1,8 @@
 use crate::{game::*, moves::*, resolve::*};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FusedIterator;
 
 // TODO: optional boards
 
@@ -24,6 +28,8 @@ pub struct MovesetIter<'a> {
     pub max_movesets_considered: usize, // 0 for ∞
     /// The number of movesets that have been yielded already
     pub movesets_considered: usize,
+    /// Whether `next` returned None already, after which it keeps doing so without looking for more movesets
+    exhausted: bool,
 }
 
 impl<'a> Iterator for MovesetIter<'a> {
@@ -31,6 +37,20 @@ impl<'a> Iterator for MovesetIter<'a> {
 
     /// Yields a moveset, if there are still any to yield
     fn next(&mut self) -> Option<Vec<Move>> {
+        if self.exhausted {
+            return None;
+        }
+        let res = self.next_moveset();
+        self.exhausted = res.is_none();
+        res
+    }
+}
+
+impl<'a> FusedIterator for MovesetIter<'a> {}
+
+impl<'a> MovesetIter<'a> {
+    /// Yields a moveset, if there are still any to yield; see `next`
+    fn next_moveset(&mut self) -> Option<Vec<Move>> {
         self.movesets_considered += 1;
         if self.max_movesets_considered > 0
             && self.movesets_considered > self.max_movesets_considered
@@ -75,9 +95,7 @@ impl<'a> Iterator for MovesetIter<'a> {
             }
         }
     }
-}
 
-impl<'a> MovesetIter<'a> {
     /**
     Generates a new MovesetIter. Assumes that `moves` was already sorted.
     **/
@@ -87,25 +105,48 @@ impl<'a> MovesetIter<'a> {
         info: &'a GameInfo,
         moves: Vec<Vec<(Move, Vec<Board>, GameInfo, i32)>>,
     ) -> Self {
-        let moves = moves
-            .into_iter()
-            .map(|mut ms| {
-                ms.insert(0, (Move::noop((0, 0)), vec![], info.clone(), 0));
-                ms
-            })
-            .collect::<Vec<_>>();
-        MovesetIter {
+        let mut res = MovesetIter {
             game,
             virtual_boards,
             info: info.clone(),
-            max_moves: moves.iter().map(|m| m.len()).max().unwrap_or(0) + 1,
-            moves,
+            max_moves: 0,
+            moves: Vec::with_capacity(moves.len()),
             moves_considered: 1,
             permutation_stack: vec![],
             max_movesets_considered: 0,
             max_moves_considered: 0,
             movesets_considered: 0,
-        }
+            exhausted: false,
+        };
+        res.reset(game, virtual_boards, info, moves);
+        res
+    }
+
+    /**
+    Makes the iterator start over on another position, as if it was created anew by `new` but keeping `max_moves_considered` and `max_movesets_considered`.
+    The buffers holding the moves and the pending movesets are kept, so that a loop going through many positions of similar sizes
+    (eg. a benchmark or a search) can reuse one iterator rather than allocating those each time.
+    **/
+    pub fn reset(
+        &mut self,
+        game: &'a Game,
+        virtual_boards: &'a Vec<&'a Board>,
+        info: &'a GameInfo,
+        moves: Vec<RankedMoves>,
+    ) {
+        self.game = game;
+        self.virtual_boards = virtual_boards;
+        self.info = *info;
+        self.moves.clear();
+        self.moves.extend(moves.into_iter().map(|mut ms| {
+            ms.insert(0, (Move::noop((0, 0)), vec![], *info, 0));
+            ms
+        }));
+        self.max_moves = self.moves.iter().map(|m| m.len()).max().unwrap_or(0) + 1;
+        self.moves_considered = 1;
+        self.permutation_stack.clear();
+        self.movesets_considered = 0;
+        self.exhausted = false;
     }
 
     /**
@@ -132,6 +173,10 @@ impl<'a> MovesetIter<'a> {
             }
             for pre in pre_combinations.into_iter() {
                 for post in post_combinations.iter().cloned() {
+                    if self.is_stack_full() {
+                        // `commit_combination` would not push any further moveset
+                        return;
+                    }
                     self.commit_combination(
                         pre.iter()
                             .cloned()
@@ -216,6 +261,12 @@ impl<'a> MovesetIter<'a> {
         }
     }
 
+    /// Returns whether `permutation_stack` reached `max_movesets_considered`, in which case no more moveset is pushed onto it
+    #[inline]
+    fn is_stack_full(&self) -> bool {
+        self.max_movesets_considered > 0 && self.permutation_stack.len() > self.max_movesets_considered
+    }
+
     /**
     Appends a combination and its derived permutations to `permutation_stack`.
     **/
@@ -247,9 +298,7 @@ impl<'a> MovesetIter<'a> {
                 .chain(branching_moves.into_iter())
                 .collect::<Vec<_>>(),
         ) {
-            if self.max_movesets_considered > 0
-                && self.permutation_stack.len() > self.max_movesets_considered
-            {
+            if self.is_stack_full() {
                 break;
             }
 
@@ -288,3 +337,1228 @@ impl<'a> MovesetIter<'a> {
         .map(|x| x.unwrap())
     }
 }
+
+/// The moves of a board along with the `GameInfo` and the boards that each of them leads to, and their score, sorted as by `resolve::score_moves`
+pub type RankedMoves = Vec<(Move, Vec<Board>, GameInfo, i32)>;
+
+/**
+    An iterator over movesets, combining the same moves as `MovesetIter` but pruning the combinations that cannot become legal.
+    The moves staying on their board are decided first, board after board: each board is either left or one of its moves is played on top of the moves chosen so far (see `TurnAssembler`).
+    The jumps of the boards that were left are then played one after the other, in any order: every combination of jumps chosen so far is tried as a moveset, then extended by each of the jumps of the remaining boards.
+    As a move cannot shield a king from the pieces of the opponent's boards, a combination that leaves the player in check is given up on with all of the combinations that extend it;
+    so is leaving a board without jumps that must be played on, when none of the jumps could move the present back. Only the complete combinations that `TurnAssembler::finalize` would accept are yielded.
+
+    Like with `MovesetIter`, the moves staying on their board are played before the jumps. Two jumps are only played against the order of their boards if that order matters,
+    that is if both of them create a timeline or if one of them lands on the board of the other; movesets leading to the same boards through the same moves are yielded once.
+    The movesets are yielded in depth-first order rather than by the rank of their moves.
+**/
+pub struct PrunedMovesetIter<'a> {
+    assembler: TurnAssembler<'a>,
+    opponent_boards: Vec<&'a Board>,
+    /// The coordinates of each board, None if it has no moves
+    boards: Vec<Option<(i32, isize)>>,
+    /// The moves staying on each board, along with their rank among the moves of their board
+    physical: Vec<Vec<(usize, Move)>>,
+    /// The jumps of each board, along with their rank among the moves of their board and whether they create a timeline
+    jumps: Vec<Vec<(usize, Move, bool)>>,
+    /// For each list of `physical` decided so far, `0` if its board was left or `n` if its `n`-th move was played
+    choices: Vec<usize>,
+    /// The jumps played on top of the `choices`, in order, as the index of their board and their index in `jumps`
+    played_jumps: Vec<(usize, usize)>,
+    /// Whether one of the jumps creates a timeline
+    branches: bool,
+    /// The keys of the movesets yielded so far (see `MovesetKey`)
+    yielded: HashSet<MovesetKey>,
+    exhausted: bool,
+    /// The maximum number of moves per board that may be considered; 0 for infinite
+    pub max_moves_considered: usize, // 0 for ∞
+    /// The maximum number of movesets that may be yielded; 0 for infinite
+    pub max_movesets_considered: usize, // 0 for ∞
+    /// The number of movesets that have been yielded already
+    pub movesets_considered: usize,
+    /// The number of combinations given up on, complete or not
+    pub rejected: usize,
+}
+
+impl<'a> PrunedMovesetIter<'a> {
+    /// Generates a new PrunedMovesetIter, from the same moves as `MovesetIter::new`
+    pub fn new(
+        game: &'a Game,
+        virtual_boards: &'a Vec<&'a Board>,
+        info: &'a GameInfo,
+        moves: Vec<RankedMoves>,
+    ) -> Self {
+        let n_boards = moves.len();
+        let boards = moves.iter().map(|ms| ms.first().map(|(mv, _, _, _)| (mv.src.0, mv.src.1))).collect();
+        let mut physical: Vec<Vec<(usize, Move)>> = Vec::with_capacity(n_boards);
+        let mut jumps: Vec<Vec<(usize, Move, bool)>> = Vec::with_capacity(n_boards);
+        for board_moves in moves.into_iter() {
+            let mut p = Vec::new();
+            let mut j = Vec::new();
+            for (rank, (mv, _, new_info, _)) in board_moves.into_iter().enumerate() {
+                if mv.src.0 == mv.dst.0 && mv.src.1 == mv.dst.1 {
+                    p.push((rank, mv));
+                } else {
+                    let branches = new_info.min_timeline != info.min_timeline || new_info.max_timeline != info.max_timeline;
+                    j.push((rank, mv, branches));
+                }
+            }
+            physical.push(p);
+            jumps.push(j);
+        }
+        let branches = jumps.iter().flatten().any(|(_, _, branches)| *branches);
+
+        PrunedMovesetIter {
+            assembler: TurnAssembler::new(game, virtual_boards, info),
+            opponent_boards: get_opponent_boards(game, virtual_boards, info),
+            boards,
+            physical,
+            jumps,
+            choices: Vec::new(),
+            played_jumps: Vec::new(),
+            branches,
+            yielded: HashSet::new(),
+            exhausted: false,
+            max_moves_considered: 0,
+            max_movesets_considered: 0,
+            movesets_considered: 0,
+            rejected: 0,
+        }
+    }
+
+    /// Returns the number of moves of `moves` that may be considered, given their rank
+    fn considered<T>(&self, moves: &[T], rank: impl Fn(&T) -> usize) -> usize {
+        if self.max_moves_considered > 0 {
+            moves.partition_point(|mv| rank(mv) < self.max_moves_considered)
+        } else {
+            moves.len()
+        }
+    }
+
+    /// Returns the number of jumps of the `n`-th board that may be considered: none if a move stays on that board or if one of its jumps was played already
+    fn considered_jumps(&self, n: usize) -> usize {
+        if self.choices[n] > 0 || self.played_jumps.iter().any(|(board, _)| *board == n) {
+            0
+        } else {
+            self.considered(&self.jumps[n], |(rank, _, _)| *rank)
+        }
+    }
+
+    /// Returns whether the boards created so far do not leave the player in check
+    fn is_viable(&self) -> bool {
+        let game = self.assembler.game;
+        let merged_vboards: Vec<&Board> =
+            self.assembler.virtual_boards.iter().copied().chain(self.assembler.boards.iter()).collect();
+
+        is_moveset_legal(game, &merged_vboards, &self.assembler.info, self.assembler.boards.iter())
+            && is_moveset_legal(game, &merged_vboards, &self.assembler.info, self.opponent_boards.iter().copied())
+    }
+
+    /// Returns whether every board left by the moves staying on their board may still be left: either it has jumps to play, cannot be played on anymore, need not be played on or a jump may move the present back
+    fn may_leave_boards(&self) -> bool {
+        if self.branches {
+            return true;
+        }
+        let merged_vboards: Vec<&Board> =
+            self.assembler.virtual_boards.iter().copied().chain(self.assembler.boards.iter()).collect();
+
+        (0..self.boards.len()).all(|n| match self.boards[n] {
+            Some((l, t)) => {
+                self.choices[n] > 0
+                    || self.considered_jumps(n) > 0
+                    || t > self.assembler.info.present
+                    || !get_board(self.assembler.game, &merged_vboards, (l, t))
+                        .is_some_and(|board| is_last(self.assembler.game, &merged_vboards, board))
+            }
+            None => true,
+        })
+    }
+
+    /// Plays `mv` on top of the moves chosen so far, if its board can still be played on and if the player is not left in check
+    fn try_play(&mut self, mv: Move) -> bool {
+        let playable = {
+            let merged_vboards: Vec<&Board> =
+                self.assembler.virtual_boards.iter().copied().chain(self.assembler.boards.iter()).collect();
+            get_board(self.assembler.game, &merged_vboards, (mv.src.0, mv.src.1))
+                .is_some_and(|board| is_last(self.assembler.game, &merged_vboards, board))
+        };
+
+        if playable && self.assembler.try_push(mv) {
+            if self.is_viable() {
+                return true;
+            }
+            self.assembler.pop();
+        }
+        self.rejected += 1;
+        false
+    }
+
+    /**
+        Plays the first jump that can be played on top of the jumps played so far, starting from the `from`-th jump of the `board`-th board and going through the jumps of the following boards.
+        A jump is skipped if its board comes before the board of the last jump played and if playing them in either order gives the same moveset (see `jumps_commute`), as that moveset is reached in the order of their boards.
+    **/
+    fn play_next_jump(&mut self, board: usize, from: usize) -> bool {
+        for n in board..self.boards.len() {
+            let start = if n == board { from } else { 0 };
+            for index in start..self.considered_jumps(n) {
+                let (_, mv, branches) = self.jumps[n][index];
+                if let Some(&(last_board, last_index)) = self.played_jumps.last() {
+                    let (_, last_mv, last_branches) = self.jumps[last_board][last_index];
+                    if n < last_board && jumps_commute((last_mv, last_branches), (mv, branches)) {
+                        continue;
+                    }
+                }
+                if self.try_play(mv) {
+                    self.played_jumps.push((n, index));
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Moves on to the next combination: the next jump in place of the last one played, or once every jump was tried, the next choice of the last list of `physical` decided; returns false once every combination was tried
+    fn advance(&mut self) -> bool {
+        while let Some((board, index)) = self.played_jumps.pop() {
+            self.assembler.pop();
+            if self.play_next_jump(board, index + 1) {
+                return true;
+            }
+        }
+        while let Some(choice) = self.choices.pop() {
+            let n = self.choices.len();
+            if choice > 0 {
+                self.assembler.pop();
+            }
+            for next in (choice + 1)..=self.considered(&self.physical[n], |(rank, _)| *rank) {
+                if self.try_play(self.physical[n][next - 1].1) {
+                    self.choices.push(next);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /**
+    Lazily gives a score to the movesets, as `MovesetIter::score` does; as they are already known to be legal, they need not be played again (see `score_legal_moveset`)
+    **/
+    pub fn score(mut self) -> impl Iterator<Item = (Vec<Move>, Vec<Board>, GameInfo, f32)> + 'a {
+        let white = self.assembler.initial_info.active_player;
+
+        std::iter::from_fn(move || self.next_turn())
+            .map(move |(moves, boards, info)| score_legal_moveset(moves, boards, info, white))
+    }
+
+    /// Looks for the next legal moveset, returning it along with the boards it creates and the `GameInfo` for the opponent's turn, as `TurnAssembler::finalize` does
+    fn next_turn(&mut self) -> Option<(Vec<Move>, Vec<Board>, GameInfo)> {
+        if self.max_movesets_considered > 0 && self.movesets_considered >= self.max_movesets_considered {
+            return None;
+        }
+
+        while !self.exhausted {
+            if self.choices.len() < self.physical.len() {
+                // Boards are left first, then their moves are tried in order
+                self.choices.push(0);
+                continue;
+            }
+            if self.played_jumps.is_empty() && !self.may_leave_boards() {
+                self.rejected += 1;
+                self.exhausted = !self.advance();
+                continue;
+            }
+
+            // The last move played was checked by `is_viable`, so only the boards left remain to be checked
+            let turn = if !self.assembler.moves.is_empty()
+                && self.all_boards_played()
+                && self.yielded.insert(moveset_key(&self.assembler.moves, &self.assembler.boards))
+            {
+                let mut info = self.assembler.info;
+                info.present += 1;
+                info.active_player = !info.active_player;
+                Some((self.assembler.moves.clone(), self.assembler.boards.clone(), info))
+            } else {
+                self.rejected += 1;
+                None
+            };
+            if !self.play_next_jump(0, 0) {
+                self.exhausted = !self.advance();
+            }
+
+            if turn.is_some() {
+                self.movesets_considered += 1;
+                return turn;
+            }
+        }
+        None
+    }
+
+    /// Returns whether every board that had to be played on was, see `moves::all_boards_played`
+    fn all_boards_played(&self) -> bool {
+        let merged_vboards: Vec<&Board> =
+            self.assembler.virtual_boards.iter().copied().chain(self.assembler.boards.iter()).collect();
+        all_boards_played(self.assembler.game, &merged_vboards, &self.assembler.info)
+    }
+}
+
+/// Returns whether two jumps of different boards, along with whether they create a timeline, lead to the same boards when played in either order: they neither both create a timeline nor share a board
+fn jumps_commute((a, a_branches): (Move, bool), (b, b_branches): (Move, bool)) -> bool {
+    let a_boards = [(a.src.0, a.src.1), (a.dst.0, a.dst.1)];
+    let b_boards = [(b.src.0, b.src.1), (b.dst.0, b.dst.1)];
+    let both_branch = a_branches && b_branches;
+    !both_branch && !a_boards.iter().any(|coords| b_boards.contains(coords))
+}
+
+/// A key of a moveset that does not depend on the order of its moves, but does on the boards that they create: the moveset itself and the coordinates and pieces of those boards, sorted
+type MovesetKey = (Moveset, Vec<(i32, isize, Vec<Piece>)>);
+
+/// Returns the `MovesetKey` of `moves`, which create `boards`
+fn moveset_key(moves: &[Move], boards: &[Board]) -> MovesetKey {
+    let mut boards: Vec<(i32, isize, Vec<Piece>)> = boards.iter().map(|b| (b.l, b.t, b.pieces.clone())).collect();
+    boards.sort_by_key(|(l, t, _)| (*l, *t));
+    (Moveset(moves.to_vec()), boards)
+}
+
+impl<'a> Iterator for PrunedMovesetIter<'a> {
+    type Item = Vec<Move>;
+
+    /// Yields a moveset, if there are still any to yield
+    fn next(&mut self) -> Option<Vec<Move>> {
+        self.next_turn().map(|(moves, _, _)| moves)
+    }
+}
+
+impl<'a> FusedIterator for PrunedMovesetIter<'a> {}
+
+/// Reasons for which `TurnAssembler::finalize` or `Moveset::is_legal` may reject a turn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TurnError {
+    /// The move at that index of the moveset cannot be played: it is not one of the moves of its piece, or its board cannot be played on (anymore)
+    InvalidMove(usize),
+    /// The turn puts the active player in check
+    Check,
+    /// The opponent could take a king from one of the boards that the turn did not create, eg. with a piece travelling from another timeline onto a board that the turn created
+    OpponentCheck,
+    /// Some of the mandatory boards were not played on
+    UnplayedBoards,
+}
+
+/**
+    Assembles a turn move by move, keeping track of the boards created so far.
+    Moves that cannot be played are refused by `try_push`; the last moves can be taken back with `pop`.
+    `finalize` validates the complete turn without altering the assembler, so that moves can be rolled back if it fails.
+**/
+pub struct TurnAssembler<'a> {
+    game: &'a Game,
+    virtual_boards: &'a Vec<&'a Board>,
+    initial_info: GameInfo,
+    /// The moves pushed so far
+    pub moves: Vec<Move>,
+    /// The boards created by `moves`
+    pub boards: Vec<Board>,
+    /// The `GameInfo` after `moves` were played
+    pub info: GameInfo,
+    /// Length of `boards` and `info` before each move was pushed
+    checkpoints: Vec<(usize, GameInfo)>,
+}
+
+impl<'a> TurnAssembler<'a> {
+    /// Creates a new, empty TurnAssembler
+    pub fn new(game: &'a Game, virtual_boards: &'a Vec<&'a Board>, info: &GameInfo) -> Self {
+        TurnAssembler {
+            game,
+            virtual_boards,
+            initial_info: *info,
+            moves: Vec::new(),
+            boards: Vec::new(),
+            info: *info,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Plays `mv` on top of the previous moves; returns false and leaves the assembler untouched if `mv` cannot be played
+    pub fn try_push(&mut self, mv: Move) -> bool {
+        match mv.generate_vboards(self.game, &self.info, self.virtual_boards, &self.boards) {
+            Some((new_info, mut new_boards)) => {
+                self.checkpoints.push((self.boards.len(), self.info));
+                self.boards.append(&mut new_boards);
+                self.info = new_info;
+                self.moves.push(mv);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Plays the move going from `src` to `dst` on top of the previous moves and returns it; returns None and leaves the assembler untouched if there is no such move or if it cannot be played
+    pub fn try_push_coords(&mut self, src: Coords, dst: Coords) -> Option<Move> {
+        let merged_vboards: Vec<&Board> = self
+            .virtual_boards
+            .iter()
+            .copied()
+            .chain(self.boards.iter())
+            .collect();
+        let board = get_own_boards(self.game, &merged_vboards, &self.info)
+            .into_iter()
+            .find(|board| board.l == src.0 && board.t == src.1)?;
+        let candidates: Vec<Move> = probable_moves(self.game, board, &merged_vboards)
+            .into_iter()
+            .filter(|mv| mv.src == src && mv.dst == dst)
+            .collect();
+
+        candidates.into_iter().find(|mv| self.try_push(*mv))
+    }
+
+    /// Takes back the last move, returning it
+    pub fn pop(&mut self) -> Option<Move> {
+        let (n_boards, info) = self.checkpoints.pop()?;
+        self.boards.truncate(n_boards);
+        self.info = info;
+        self.moves.pop()
+    }
+
+    /// Takes back every move
+    pub fn clear(&mut self) {
+        self.moves.clear();
+        self.boards.clear();
+        self.checkpoints.clear();
+        self.info = self.initial_info;
+    }
+
+    /**
+        Validates the complete turn, with the same rules as `score_moveset`.
+        On success, returns the moveset, the boards it creates and the `GameInfo` for the opponent's turn.
+    **/
+    pub fn finalize(&self) -> Result<(Vec<Move>, Vec<Board>, GameInfo), TurnError> {
+        let merged_vboards: Vec<&Board> = self
+            .virtual_boards
+            .iter()
+            .copied()
+            .chain(self.boards.iter())
+            .collect();
+
+        if !is_moveset_legal(self.game, &merged_vboards, &self.info, self.boards.iter()) {
+            return Err(TurnError::Check);
+        }
+        if !is_moveset_legal(
+            self.game,
+            &merged_vboards,
+            &self.info,
+            get_opponent_boards(self.game, self.virtual_boards, &self.initial_info).into_iter(),
+        ) {
+            return Err(TurnError::OpponentCheck);
+        }
+        if !all_boards_played(self.game, &merged_vboards, &self.info) {
+            return Err(TurnError::UnplayedBoards);
+        }
+
+        let mut info = self.info;
+        info.present += 1;
+        info.active_player = !info.active_player;
+
+        Ok((self.moves.clone(), self.boards.clone(), info))
+    }
+}
+
+/**
+    Displays a moveset as the notation of a full turn: the notation of each of its moves (see `Move`'s `Display`), separated by a space.
+    Moves are written in the order of their source boards, from the earliest board to the latest one and from the lowest timeline to the highest one; noops are omitted.
+
+    ```
+    println!("{}", MovesetNotation(&moveset));
+    ```
+**/
+pub struct MovesetNotation<'a>(pub &'a [Move]);
+
+impl<'a> fmt::Display for MovesetNotation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut moves: Vec<&Move> = self.0.iter().filter(|mv| !mv.noop).collect();
+        moves.sort_by_key(|mv| (mv.src.1, mv.src.0));
+
+        for (i, mv) in moves.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", mv)?;
+        }
+        Ok(())
+    }
+}
+
+/// The annotations following a move in the notation of a turn, see `AnnotatedMoveset`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotation {
+    /// The evaluation marks written right after the move, like `!`, `??` or `!?`
+    pub marks: String,
+    /// The comments following the move, without their braces
+    pub comments: Vec<String>,
+}
+
+/// Reasons for which `AnnotatedMoveset::parse` may reject a turn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    /// A `{` was never closed
+    UnclosedComment,
+    /// The move cannot be played at that point of the turn, or does not exist
+    UnknownMove(String),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationError::UnclosedComment => write!(f, "Unclosed comment"),
+            NotationError::UnknownMove(mv) => write!(f, "Unknown move: {}", mv),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+/**
+    A moveset along with the annotations of its moves, as found in shared games: evaluation marks (`!`, `?`, ...) and `{comments}`.
+    `annotations` maps indices within `moves` to their annotation; `comments` are the comments found before the first move.
+
+    The notation of the moves is the one of `MovesetNotation`; displaying an `AnnotatedMoveset` writes the moves in their order, each followed by its annotation, so that parsing it again yields the same moveset and annotations.
+**/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnnotatedMoveset {
+    pub moves: Vec<Move>,
+    pub annotations: HashMap<usize, Annotation>,
+    pub comments: Vec<String>,
+}
+
+impl AnnotatedMoveset {
+    /**
+        Parses the notation of a turn played from the given position, keeping the marks and comments of its moves.
+        Each move is looked up among the moves that can be played once the previous ones were made, hence the need for the position; the turn itself is not validated, see `TurnAssembler::finalize` for that.
+    **/
+    pub fn parse(
+        game: &Game,
+        virtual_boards: &Vec<&Board>,
+        info: &GameInfo,
+        raw: &str,
+    ) -> Result<Self, NotationError> {
+        let mut assembler = TurnAssembler::new(game, virtual_boards, info);
+        let mut res = AnnotatedMoveset::default();
+        let mut chars = raw.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '{' {
+                chars.next();
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(NotationError::UnclosedComment),
+                    }
+                }
+                match res.moves.len() {
+                    0 => res.comments.push(comment),
+                    n => res.annotations.entry(n - 1).or_default().comments.push(comment),
+                }
+            } else {
+                let mut token = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '{' {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                let notation = token.trim_end_matches(['!', '?']);
+                let marks = &token[notation.len()..];
+                if notation == "_" {
+                    continue;
+                }
+
+                let mv = find_move(&mut assembler, notation)
+                    .ok_or_else(|| NotationError::UnknownMove(token.clone()))?;
+                res.moves.push(mv);
+                if !marks.is_empty() {
+                    res.annotations.entry(res.moves.len() - 1).or_default().marks = marks.to_string();
+                }
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// A move as written by `Move`'s `Display`, see `parse_move_notation`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MoveNotation {
+    /// The king of board `(l, t)` castles, on the queen's side if `long` is true
+    Castle { board: (i32, isize), long: bool },
+    /// The piece written as `piece` (`None` for pawns) goes from `src` to `dst`; whether or not it takes is not needed to find the move
+    Normal { piece: Option<char>, src: Coords, dst: Coords },
+}
+
+/**
+    Reads a board written as `(LTn)`; turn `n` stands for the board of `active_player` within it.
+    Returns the `(l, t)` coordinates of the board and the rest of the notation.
+**/
+fn parse_board_notation(raw: &str, active_player: bool) -> Option<((i32, isize), &str)> {
+    let raw = raw.strip_prefix('(')?;
+    let end = raw.find(')')?;
+    let (l, turn) = raw[..end].split_once('T')?;
+    let l: i32 = l.parse().ok()?;
+    let turn: isize = turn.parse().ok()?;
+    if turn < 1 {
+        return None;
+    }
+    Some(((l, (turn - 1) * 2 + if active_player { 0 } else { 1 }), &raw[end + 1..]))
+}
+
+/// Reads a square written as a file letter followed by its rank, returning its `(x, y)` coordinates and the rest of the notation
+fn parse_square_notation(raw: &str) -> Option<((u8, u8), &str)> {
+    let mut chars = raw.chars();
+    let file = chars.next().filter(|c| c.is_ascii_lowercase())?;
+    let x = (0..23).find(|x| write_file(*x) == file)?;
+    let rest = chars.as_str();
+    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let rank: u8 = rest[..end].parse().ok()?;
+    if rank < 1 {
+        return None;
+    }
+    Some(((x, rank - 1), &rest[end..]))
+}
+
+/// Parses the notation of a single move, as written by `Move`'s `Display`, into the coordinates it refers to; the moves belong to `active_player`
+fn parse_move_notation(notation: &str, active_player: bool) -> Option<MoveNotation> {
+    let (board, rest) = parse_board_notation(notation, active_player)?;
+    match rest {
+        "O-O" => return Some(MoveNotation::Castle { board, long: false }),
+        "O-O-O" => return Some(MoveNotation::Castle { board, long: true }),
+        _ => {}
+    }
+
+    let piece = rest.chars().next().filter(|c| c.is_ascii_uppercase());
+    let rest = &rest[piece.map_or(0, |c| c.len_utf8())..];
+    let ((sx, sy), rest) = parse_square_notation(rest)?;
+    let (jumps, rest) = match rest.strip_prefix(">>") {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let rest = rest.strip_prefix('x').unwrap_or(rest);
+    let (dst_board, rest) = if jumps {
+        parse_board_notation(rest, active_player)?
+    } else {
+        (board, rest)
+    };
+    let ((dx, dy), rest) = parse_square_notation(rest)?;
+    if !rest.is_empty() {
+        return None;
+    }
+
+    Some(MoveNotation::Normal {
+        piece,
+        src: (board.0, board.1, sx, sy),
+        dst: (dst_board.0, dst_board.1, dx, dy),
+    })
+}
+
+/// Plays the move whose notation is `notation` on the assembler, returning it; the notation is parsed into coordinates, which are then looked up among the moves of the board it starts from
+fn find_move(assembler: &mut TurnAssembler, notation: &str) -> Option<Move> {
+    let parsed = parse_move_notation(notation, assembler.info.active_player)?;
+    let board = match parsed {
+        MoveNotation::Castle { board, .. } => board,
+        MoveNotation::Normal { src, .. } => (src.0, src.1),
+    };
+    let merged_vboards: Vec<&Board> = assembler
+        .virtual_boards
+        .iter()
+        .copied()
+        .chain(assembler.boards.iter())
+        .collect();
+    let board = get_own_boards(assembler.game, &merged_vboards, &assembler.info)
+        .into_iter()
+        .find(|b| (b.l, b.t) == board)?;
+    let candidates: Vec<Move> = probable_moves(assembler.game, board, &merged_vboards)
+        .into_iter()
+        .filter(|mv| match parsed {
+            MoveNotation::Castle { long, .. } => mv.castle && mv.castle_long == long,
+            MoveNotation::Normal { piece, src, dst } => {
+                !mv.castle
+                    && mv.src == src
+                    && mv.dst == dst
+                    && match piece {
+                        Some(c) => mv.src_piece.as_uppercase().starts_with(c),
+                        None => mv.src_piece.is_pawn(),
+                    }
+            }
+        })
+        .collect();
+
+    candidates.into_iter().find(|mv| assembler.try_push(*mv))
+}
+
+impl fmt::Display for AnnotatedMoveset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tokens: Vec<String> = self.comments.iter().map(|c| format!("{{{}}}", c)).collect();
+
+        for (i, mv) in self.moves.iter().enumerate() {
+            match self.annotations.get(&i) {
+                Some(annotation) => {
+                    tokens.push(format!("{}{}", mv, annotation.marks));
+                    tokens.extend(annotation.comments.iter().map(|c| format!("{{{}}}", c)));
+                }
+                None => tokens.push(mv.to_string()),
+            }
+        }
+
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+/// Counts describing a moveset, for move ordering and pruning heuristics; see `Moveset::complexity`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MovesetComplexity {
+    /// The number of moves taking a piece
+    pub captures: usize,
+    /// The number of moves whose piece attacks one of the opponent's kings from where it lands
+    pub checks: usize,
+    /// The number of timelines that the moveset creates
+    pub timelines_created: usize,
+    /// The number of boards played on: the boards of the moves' sources, and their destination boards unless they branch off
+    pub boards_moved_on: usize,
+}
+
+/**
+    A moveset whose comparison and hashing do not depend on the order of its moves, which makes it usable as a map key or to detect transpositions; noops are ignored.
+    Note that the order in which branching moves are played decides the indices of the timelines they create, which this comparison disregards.
+**/
+#[derive(Debug, Clone)]
+pub struct Moveset(pub Vec<Move>);
+
+impl Moveset {
+    /// Returns the moves of that moveset in a canonical order, without the noops
+    fn canonical(&self) -> Vec<&Move> {
+        let mut moves: Vec<&Move> = self.0.iter().filter(|mv| !mv.noop).collect();
+        moves.sort_by_key(|mv| (mv.src, mv.dst));
+        moves
+    }
+
+    /**
+        Returns the `(l, t)` coordinates of every board that the moveset touches: the boards its moves start from and land on, and the boards they create, including the first board of the new timelines.
+        The moves are played in their order, as this order decides the indices of the new timelines; the coordinates are sorted and deduplicated.
+        Returns `None` if one of the moves cannot be played.
+    **/
+    pub fn affected_boards(
+        &self,
+        game: &Game,
+        virtual_boards: &Vec<&Board>,
+        info: &GameInfo,
+    ) -> Option<Vec<(i32, isize)>> {
+        let mut info = *info;
+        let mut boards: Vec<Board> = Vec::new();
+        let mut res: Vec<(i32, isize)> = Vec::new();
+
+        for mv in self.0.iter().filter(|mv| !mv.noop) {
+            let (new_info, mut new_boards) =
+                mv.generate_vboards(game, &info, virtual_boards, &boards)?;
+            res.push((mv.src.0, mv.src.1));
+            res.push((mv.dst.0, mv.dst.1));
+            res.extend(new_boards.iter().map(|b| (b.l, b.t)));
+            boards.append(&mut new_boards);
+            info = new_info;
+        }
+
+        res.sort();
+        res.dedup();
+        Some(res)
+    }
+
+    /**
+        Classifies the moves of the moveset and returns the resulting counts, see `MovesetComplexity`. The moves are played in their order, as in `affected_boards`, but are not checked for legality.
+        Only direct checks are counted: the moves of the moved piece are generated from its destination, so that discovered checks and checks given by a castling rook are not counted.
+        Returns `None` if one of the moves cannot be played.
+    **/
+    pub fn complexity(
+        &self,
+        game: &Game,
+        virtual_boards: &Vec<&Board>,
+        info: &GameInfo,
+    ) -> Option<MovesetComplexity> {
+        let mut info = *info;
+        let mut boards: Vec<Board> = Vec::new();
+        let mut played: Vec<(i32, isize)> = Vec::new();
+        let mut res = MovesetComplexity::default();
+
+        for mv in self.0.iter().filter(|mv| !mv.noop) {
+            let (new_info, mut new_boards) =
+                mv.generate_vboards(game, &info, virtual_boards, &boards)?;
+            let created =
+                (new_info.max_timeline - info.max_timeline + info.min_timeline - new_info.min_timeline) as usize;
+
+            played.push((mv.src.0, mv.src.1));
+            if created == 0 {
+                played.push((mv.dst.0, mv.dst.1));
+            }
+            res.timelines_created += created;
+            if mv.captured().is_some() {
+                res.captures += 1;
+            }
+
+            boards.append(&mut new_boards);
+            info = new_info;
+
+            if !mv.castle {
+                let merged_vboards: Vec<&Board> = virtual_boards.iter().copied().chain(boards.iter()).collect();
+                let landed_on = &boards[boards.len() - 1];
+                // It is the opponent's turn on `landed_on`, so the piece (promoted, if it was a pawn) is made to move as one of its own colour
+                let piece = landed_on.get(mv.dst.2, mv.dst.3).unwrap_or(mv.src_piece);
+                let mut next_moves = Vec::new();
+                probable_moves_as(game, landed_on, &merged_vboards, &mut next_moves, piece, mv.dst.2, mv.dst.3, piece.is_white());
+                if game.travel_rules != TravelRules::default() {
+                    next_moves.retain(|next| is_travel_allowed(game, &merged_vboards, next));
+                }
+                if next_moves
+                    .iter()
+                    .any(|next| {
+                        next.dst_piece.is_king() && next.dst_piece.is_opponent_piece(mv.src_piece.is_white())
+                    })
+                {
+                    res.checks += 1;
+                }
+            }
+        }
+
+        played.sort();
+        played.dedup();
+        res.boards_moved_on = played.len();
+        Some(res)
+    }
+
+    /**
+        Checks whether the moveset is a legal turn for the active player, without generating the other movesets.
+        The moves are played in their order: each of them must be one of the moves of its piece (see `moves::probable_moves`), on a board that can still be played on once the previous moves were made;
+        the resulting turn is then validated like `TurnAssembler::finalize` does. Noops are ignored.
+    **/
+    pub fn is_legal(&self, game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> Result<(), TurnError> {
+        self.validate(game, virtual_boards, info).map(|_| ())
+    }
+
+    /// Same as `is_legal`, but returns the moves, the boards that they create and the `GameInfo` for the opponent's turn, as `TurnAssembler::finalize` does
+    pub fn validate(
+        &self,
+        game: &Game,
+        virtual_boards: &Vec<&Board>,
+        info: &GameInfo,
+    ) -> Result<(Vec<Move>, Vec<Board>, GameInfo), TurnError> {
+        let mut assembler = TurnAssembler::new(game, virtual_boards, info);
+
+        for (index, mv) in self.0.iter().enumerate().filter(|(_, mv)| !mv.noop) {
+            let valid = {
+                let merged_vboards: Vec<&Board> =
+                    virtual_boards.iter().copied().chain(assembler.boards.iter()).collect();
+                get_board(game, &merged_vboards, (mv.src.0, mv.src.1)).is_some_and(|board| {
+                    board.active_player() == info.active_player
+                        && is_last(game, &merged_vboards, board)
+                        && probable_moves(game, board, &merged_vboards).contains(mv)
+                })
+            };
+            if !valid || !assembler.try_push(*mv) {
+                return Err(TurnError::InvalidMove(index));
+            }
+        }
+
+        assembler.finalize()
+    }
+}
+
+impl From<Vec<Move>> for Moveset {
+    fn from(moves: Vec<Move>) -> Self {
+        Moveset(moves)
+    }
+}
+
+impl PartialEq for Moveset {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical() == other.canonical()
+    }
+}
+
+impl Eq for Moveset {}
+
+impl Hash for Moveset {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Returns the move from `src` to `dst` among the moves of the piece at `src`
+    fn find_move(game: &Game, src: Coords, dst: Coords) -> Move {
+        let board = game.get_board(src.0, src.1).unwrap();
+        probable_moves(game, board, &vec![])
+            .into_iter()
+            .find(|mv| mv.src == src && mv.dst == (dst.0, dst.1, dst.2, dst.3))
+            .unwrap()
+    }
+
+    /**
+        Timelines -1, 0 and 1 are to be played by white at `t = 0`; the black bishop on `(1T1)` (ahead of the present), e4 looks at the white king on `(-1T0)` e1
+        through `(1T0)` e3 and `(0T0)` e2, which are both blocked by a white piece.
+    **/
+    fn blocked_bishop() -> Game {
+        GameBuilder::new(8, 8)
+            .add_board(-1, 0, testing::board(-1, 0, &[
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "R...K...",
+            ]))
+            .add_board(0, 0, testing::board(0, 0, &[
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "....P...",
+                ".......R",
+            ]))
+            .add_board(1, 0, testing::board(1, 0, &[
+                "........",
+                "........",
+                "........",
+                "........",
+                "........",
+                "....N...",
+                "........",
+                ".......R",
+            ]))
+            .add_board(2, 1, testing::board(2, 1, &[
+                "....k...",
+                "........",
+                "........",
+                "........",
+                "....b...",
+                "........",
+                "........",
+                "........",
+            ]))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn turn_assembler_rolls_back_illegal_combination() {
+        let game = blocked_bishop();
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let rook = find_move(&game, (-1, 0, 0, 0), (-1, 0, 0, 1));
+        let pawn = find_move(&game, (0, 0, 4, 1), (0, 0, 4, 2));
+        let quiet_0 = find_move(&game, (0, 0, 7, 0), (0, 0, 7, 1));
+        let knight = find_move(&game, (1, 0, 4, 2), (1, 0, 6, 3));
+        let quiet_1 = find_move(&game, (1, 0, 7, 0), (1, 0, 7, 1));
+
+        // Each blocker may leave the line on its own
+        let mut assembler = TurnAssembler::new(&game, &virtual_boards, &game.info);
+        for mv in [rook, pawn, quiet_1] {
+            assert!(assembler.try_push(mv));
+        }
+        assert!(assembler.finalize().is_ok());
+        assembler.clear();
+        for mv in [rook, quiet_0, knight] {
+            assert!(assembler.try_push(mv));
+        }
+        assert!(assembler.finalize().is_ok());
+
+        // But not both of them: the bishop on the opponent board then reaches the king
+        assembler.clear();
+        for mv in [rook, pawn, knight] {
+            assert!(assembler.try_push(mv));
+        }
+        let (moves, n_boards, info) = (assembler.moves.clone(), assembler.boards.len(), assembler.info);
+        assert_eq!(assembler.finalize().err(), Some(TurnError::OpponentCheck));
+        assert_eq!(assembler.moves, moves);
+        assert_eq!(assembler.boards.len(), n_boards);
+        assert_eq!(assembler.info.present, info.present);
+
+        // Rolling the last move back leaves a working assembler
+        assert_eq!(assembler.pop(), Some(knight));
+        assert!(assembler.try_push(quiet_1));
+        let (moves, boards, info) = assembler.finalize().unwrap();
+        assert_eq!(moves, vec![rook, pawn, quiet_1]);
+        assert_eq!(boards.len(), 3);
+        assert!(!info.active_player);
+    }
+
+    #[test]
+    fn moveset_validation_errors() {
+        let game = blocked_bishop();
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let rook = find_move(&game, (-1, 0, 0, 0), (-1, 0, 0, 1));
+        let pawn = find_move(&game, (0, 0, 4, 1), (0, 0, 4, 2));
+        let knight = find_move(&game, (1, 0, 4, 2), (1, 0, 6, 3));
+        let quiet_1 = find_move(&game, (1, 0, 7, 0), (1, 0, 7, 1));
+        let legality = |moves: Vec<Move>| Moveset(moves).is_legal(&game, &virtual_boards, &game.info);
+
+        let (moves, boards, info) = Moveset(vec![rook, pawn, quiet_1]).validate(&game, &virtual_boards, &game.info).unwrap();
+        assert_eq!(moves, vec![rook, pawn, quiet_1]);
+        assert_eq!(boards.len(), 3);
+        assert!(!info.active_player);
+
+        assert_eq!(legality(vec![rook, pawn, knight]), Err(TurnError::OpponentCheck));
+        assert_eq!(legality(vec![rook, pawn]), Err(TurnError::UnplayedBoards));
+        // The board of the pawn was already played on
+        assert_eq!(legality(vec![rook, pawn, pawn, quiet_1]), Err(TurnError::InvalidMove(2)));
+        // The rook cannot go through the king
+        let through_king = Move { dst: (-1, 0, 7, 0), ..rook };
+        assert_eq!(legality(vec![through_king, pawn, quiet_1]), Err(TurnError::InvalidMove(0)));
+        // Nor can a move be made with a piece that is not on its square
+        assert_eq!(legality(vec![rook, Move { src_piece: Piece::RookB, ..pawn }, quiet_1]), Err(TurnError::InvalidMove(1)));
+
+        // The rook on c2 covers a2
+        let game = testing::game(0, &["k...", "....", "..r.", "K..."]);
+        let king = |x, y| find_move(&game, (0, 0, 0, 0), (0, 0, x, y));
+        assert_eq!(Moveset(vec![king(0, 1)]).is_legal(&game, &virtual_boards, &game.info), Err(TurnError::Check));
+        assert_eq!(Moveset(vec![king(1, 0)]).is_legal(&game, &virtual_boards, &game.info), Ok(()));
+    }
+
+    #[test]
+    fn generated_movesets_are_legal() {
+        let game = testing::fixture("standard-complex-2.json");
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let mut n_movesets = 0;
+        for (moves, _, _, _) in legal_movesets(&game, &game.info, &virtual_boards, 0, 50) {
+            assert_eq!(Moveset(moves.clone()).is_legal(&game, &virtual_boards, &game.info), Ok(()));
+            // Noops are ignored
+            let padded: Vec<Move> = std::iter::once(Move::noop((0, 0))).chain(moves.into_iter().filter(|mv| !mv.noop)).collect();
+            assert_eq!(Moveset(padded).is_legal(&game, &virtual_boards, &game.info), Ok(()));
+            n_movesets += 1;
+        }
+        assert!(n_movesets > 0);
+        assert_eq!(Moveset(vec![]).is_legal(&game, &virtual_boards, &game.info), Err(TurnError::UnplayedBoards));
+    }
+
+    #[test]
+    fn affected_boards_of_branching_moves() {
+        let mut game = crate::variant::standard();
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let knight = Moveset(vec![find_move(&game, (0, 0, 6, 0), (0, 0, 5, 2))]);
+        assert_eq!(knight.affected_boards(&game, &virtual_boards, &game.info), Some(vec![(0, 0), (0, 1)]));
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+
+        // The knight jumps back to the first board: the board it leaves, the one it lands on, their successors and the first board of the new timeline
+        let jump = Moveset(vec![find_move(&game, (0, 2, 5, 2), (0, 0, 7, 2))]);
+        assert_eq!(jump.affected_boards(&game, &virtual_boards, &game.info), Some(vec![(0, 0), (0, 2), (0, 3), (1, 1)]));
+
+        // The first move was already played from the knight's board
+        let twice = Moveset(vec![jump.0[0], jump.0[0]]);
+        assert_eq!(twice.affected_boards(&game, &virtual_boards, &game.info), None);
+    }
+
+    #[test]
+    fn complexity_counts() {
+        let mut game = crate::variant::standard();
+        let virtual_boards: Vec<&Board> = Vec::new();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        let jump = Moveset(vec![find_move(&game, (0, 2, 5, 2), (0, 0, 7, 2))]);
+        assert_eq!(jump.complexity(&game, &virtual_boards, &game.info), Some(MovesetComplexity {
+            captures: 0,
+            checks: 0,
+            timelines_created: 1,
+            boards_moved_on: 1,
+        }));
+
+        // The queen takes the rook on b2, from where it checks the king on b4
+        let game = testing::game(0, &[".k..", "....", ".r..", "Q..K"]);
+        let capture = Moveset(vec![find_move(&game, (0, 0, 0, 0), (0, 0, 1, 1))]);
+        assert_eq!(capture.complexity(&game, &virtual_boards, &game.info), Some(MovesetComplexity {
+            captures: 1,
+            checks: 1,
+            timelines_created: 0,
+            boards_moved_on: 1,
+        }));
+        let quiet = Moveset(vec![find_move(&game, (0, 0, 3, 0), (0, 0, 2, 0))]);
+        assert_eq!(quiet.complexity(&game, &virtual_boards, &game.info), Some(MovesetComplexity {
+            boards_moved_on: 1,
+            ..MovesetComplexity::default()
+        }));
+
+        // The pawn checks as the queen it promotes to
+        let game = testing::game(0, &["...k", "P...", "....", "K..."]);
+        let promotion = Moveset(vec![find_move(&game, (0, 0, 0, 2), (0, 0, 0, 3))]);
+        assert_eq!(promotion.complexity(&game, &virtual_boards, &game.info).map(|c| c.checks), Some(1));
+        let quiet = Moveset(vec![find_move(&game, (0, 0, 0, 0), (0, 0, 1, 0))]);
+        assert_eq!(quiet.complexity(&game, &virtual_boards, &game.info), Some(MovesetComplexity {
+            boards_moved_on: 1,
+            ..MovesetComplexity::default()
+        }));
+    }
+
+    #[test]
+    fn moveset_order_independence() {
+        let game = blocked_bishop();
+        let rook = find_move(&game, (-1, 0, 0, 0), (-1, 0, 0, 1));
+        let pawn = find_move(&game, (0, 0, 4, 1), (0, 0, 4, 2));
+        let quiet_1 = find_move(&game, (1, 0, 7, 0), (1, 0, 7, 1));
+        let hash = |moveset: &Moveset| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            moveset.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        // The same moves in another order, with noops for good measure
+        let a = Moveset(vec![rook, pawn, quiet_1]);
+        let b = Moveset(vec![quiet_1, Move::noop((0, 0)), rook, pawn]);
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        let mut set = HashSet::new();
+        assert!(set.insert(a.clone()));
+        assert!(!set.insert(b));
+
+        let c = Moveset(vec![rook, pawn]);
+        assert_ne!(a, c);
+        assert!(set.insert(c));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn moveset_iter_reset_matches_new() {
+        let start = crate::variant::standard();
+        let mut game = start.clone();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let ranked = |game: &Game| ranked_moves(game, &game.info, &virtual_boards, |_| true);
+
+        let mut fresh = MovesetIter::new(&game, &virtual_boards, &game.info, ranked(&game));
+        fresh.max_movesets_considered = 5;
+        let expected: Vec<Vec<Move>> = fresh.collect();
+
+        // Partially consumed on another position, with the limits kept across the reset
+        let mut iter = MovesetIter::new(&start, &virtual_boards, &start.info, ranked(&start));
+        iter.max_movesets_considered = 5;
+        assert!(iter.next().is_some());
+        iter.reset(&game, &virtual_boards, &game.info, ranked(&game));
+        assert_eq!(iter.max_movesets_considered, 5);
+        assert_eq!(iter.collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn exhausted_iterators_stay_exhausted() {
+        let game = testing::game(0, &["k...", "....", ".P..", "K..."]);
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let ranked = || ranked_moves(&game, &game.info, &virtual_boards, |_| true);
+
+        // Running out of movesets, or reaching `max_movesets_considered`
+        for max_movesets in [0, 2] {
+            let mut iter = MovesetIter::new(&game, &virtual_boards, &game.info, ranked());
+            iter.max_movesets_considered = max_movesets;
+            let n = iter.by_ref().count();
+            assert!(n > 0);
+            assert!(max_movesets == 0 || n == max_movesets);
+            let counters = (iter.movesets_considered, iter.moves_considered);
+            for _ in 0..3 {
+                assert_eq!(iter.next(), None);
+                assert_eq!((iter.movesets_considered, iter.moves_considered), counters);
+            }
+
+            // Resetting the iterator starts the search over
+            iter.reset(&game, &virtual_boards, &game.info, ranked());
+            assert_eq!(iter.count(), n);
+        }
+
+        let mut iter = PrunedMovesetIter::new(&game, &virtual_boards, &game.info, ranked());
+        assert!(iter.by_ref().count() > 0);
+        let counters = (iter.movesets_considered, iter.rejected);
+        for _ in 0..3 {
+            assert_eq!(iter.next(), None);
+            assert_eq!((iter.movesets_considered, iter.rejected), counters);
+        }
+    }
+
+    #[test]
+    fn pruned_movesets_in_any_jump_order() {
+        // Timelines -1, 0 and 1 are to be played by white; only the rook of `(0T1)` may reach `(1T1)`, by leaving its board
+        let rook = |l, x| {
+            let mut ranks = ["........"; 8];
+            let rank = format!("{}R{}", ".".repeat(x), ".".repeat(7 - x));
+            ranks[7] = &rank;
+            testing::board(l, 0, &ranks)
+        };
+        let game = GameBuilder::new(8, 8)
+            .add_board(-1, 0, rook(-1, 0))
+            .add_board(0, 0, rook(0, 7))
+            .add_board(1, 0, testing::board(1, 0, &["........"; 8]))
+            .build()
+            .unwrap();
+        let virtual_boards: Vec<&Board> = Vec::new();
+        // The movesets of `MovesetIter` hold a noop for each board left
+        let key = |(moves, boards, _, _): (Vec<Move>, Vec<Board>, GameInfo, f32)| {
+            let moves: Vec<Move> = moves.into_iter().filter(|mv| !mv.noop).collect();
+            moveset_key(&moves, &boards)
+        };
+
+        let all: HashSet<MovesetKey> = legal_movesets(&game, &game.info, &virtual_boards, 0, 0).map(key).collect();
+        let pruned: Vec<MovesetKey> = legal_movesets_pruned(&game, &game.info, &virtual_boards, 0, 0).map(key).collect();
+        assert_eq!(pruned.len(), all.len());
+        assert_eq!(pruned.into_iter().collect::<HashSet<_>>(), all);
+
+        // The rook of `(-1T1)` can only land on `(0T1)` once the other rook left it, creating a timeline
+        let leave = find_move(&game, (0, 0, 7, 0), (1, 0, 7, 0));
+        let land = find_move(&game, (-1, 0, 0, 0), (0, 0, 0, 0));
+        let ranked = ranked_moves(&game, &game.info, &virtual_boards, |_| true);
+        let mut iter = PrunedMovesetIter::new(&game, &virtual_boards, &game.info, ranked);
+        assert!(iter.any(|moves| moves == vec![leave, land]));
+    }
+
+    #[test]
+    fn moveset_notation_two_boards() {
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        testing::play(&mut game, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+        testing::play(&mut game, &[((1, 1, 6, 7), (0, 1, 6, 5))]);
+
+        // White now has to play on timelines -1 and 1; the moves are written from the lowest timeline to the highest one
+        let moveset = testing::play(&mut game, &[((1, 2, 7, 2), (1, 2, 6, 4)), ((-1, 2, 4, 1), (-1, 2, 4, 3))]);
+        assert_eq!(MovesetNotation(&moveset.0).to_string(), "(-1T2)e2e4 (1T2)Nh3g5");
+    }
+
+    #[test]
+    fn annotated_moveset_round_trip() {
+        let mut game = crate::variant::standard();
+        let parse = |game: &Game, raw: &str| AnnotatedMoveset::parse(game, &vec![], &game.info, raw);
+
+        let raw = "{opening} (0T1)Ng1f3!? {develops}";
+        let white = parse(&game, raw).unwrap();
+        assert_eq!(white.moves.len(), 1);
+        assert_eq!((white.moves[0].src, white.moves[0].dst), ((0, 0, 6, 0), (0, 0, 5, 2)));
+        assert_eq!(white.comments, vec!["opening".to_string()]);
+        assert_eq!(white.annotations[&0], Annotation {
+            marks: "!?".to_string(),
+            comments: vec!["develops".to_string()],
+        });
+        assert_eq!(white.to_string(), raw);
+        assert_eq!(parse(&game, &white.to_string()), Ok(white));
+
+        // The letter of the piece has to match the piece that moves
+        assert_eq!(parse(&game, "(0T1)Bg1f3").err(), Some(NotationError::UnknownMove("(0T1)Bg1f3".to_string())));
+
+        // The turn number refers to black's board when black is to move
+        testing::play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        let black = parse(&game, "(0T1)Ng8f6? {mirrors}").unwrap();
+        assert_eq!((black.moves[0].src, black.moves[0].dst), ((0, 1, 6, 7), (0, 1, 5, 5)));
+        assert_eq!(black.to_string(), "(0T1)Ng8f6? {mirrors}");
+
+        testing::play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        let jump = parse(&game, "(0T2)Nf3>>(0T1)h3 {branches}").unwrap();
+        assert_eq!((jump.moves[0].src, jump.moves[0].dst), ((0, 2, 5, 2), (0, 0, 7, 2)));
+        assert_eq!(parse(&game, &jump.to_string()), Ok(jump));
+    }
+}
@@ -1,16 +1,36 @@
 use super::game;
-use serde::Deserialize;
+use super::moves;
+use super::moveset::{Moveset, TurnAssembler};
+use super::variant::VariantRegistry;
+pub use super::game::PartialTurn;
+use serde::de::{Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct GameRaw {
     timelines: Vec<TimelineRaw>,
     width: u8,
     height: u8,
     active_player: bool,
+    /// The turns played from the position, as lists of moves
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    moves: Vec<Vec<MoveRaw>>,
+}
+
+/// Represents a move, its source and destination being written as `[l, t, x, y]` with `l` encoded like the index of a timeline
+#[derive(Debug, Deserialize, Serialize)]
+struct MoveRaw {
+    from: (f32, isize, u8, u8),
+    to: (f32, isize, u8, u8),
 }
 
 /// Represents an in-game timeline
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct TimelineRaw {
     index: f32,
     states: Vec<Vec<usize>>,
@@ -20,8 +40,106 @@ struct TimelineRaw {
     emerges_from: Option<f32>,
 }
 
+/// An error encountered while loading a game
+#[derive(Debug)]
+pub enum ParseError {
+    /// The file could not be read
+    Io(std::io::Error),
+    /// The contents are not valid JSON, or do not have the expected fields
+    Json(serde_json::Error),
+    /// The contents are valid JSON but do not describe a valid game
+    Format(&'static str),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "Couldn't read game: {}", e),
+            ParseError::Json(e) => write!(f, "Couldn't parse JSON: {}", e),
+            ParseError::Format(reason) => write!(f, "Invalid game: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<std::io::Error> for ParseError {
+    fn from(e: std::io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ParseError {
+    fn from(e: serde_json::Error) -> Self {
+        ParseError::Json(e)
+    }
+}
+
+/// Reads the file at `path` and parses it (see `try_parse`)
+pub fn try_read_and_parse<P: AsRef<Path>>(path: P) -> Result<game::Game, ParseError> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    try_parse(&contents)
+}
+
+/**
+    Reads and parses every file of the directory at `path` (see `try_read_and_parse`), without stopping at the files that could not be loaded.
+    Returns the games that were loaded and the paths of the files that couldn't be, along with the reason; subdirectories are ignored and files are read in the order of their paths.
+    If the directory itself cannot be read, the only error returned is for `path`.
+**/
+pub fn load_dir<P: AsRef<Path>>(path: P) -> (Vec<game::Game>, Vec<(PathBuf, ParseError)>) {
+    let mut games = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut paths = match std::fs::read_dir(&path)
+        .and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).collect::<Result<Vec<_>, _>>())
+    {
+        Ok(paths) => paths,
+        Err(e) => return (games, vec![(path.as_ref().to_path_buf(), ParseError::Io(e))]),
+    };
+    paths.sort();
+
+    for file in paths.into_iter().filter(|p| !p.is_dir()) {
+        match try_read_and_parse(&file) {
+            Ok(game) => games.push(game),
+            Err(e) => errors.push((file, e)),
+        }
+    }
+
+    (games, errors)
+}
+
+/// Parses a game in the JSON format outputted by the 5dchess-notation tool, returns None on failure; see `try_parse` for the reason of the failure
 pub fn parse(raw: &str) -> Option<game::Game> {
-    let game_raw: GameRaw = serde_json::from_str(raw).ok()?;
+    try_parse(raw).ok()
+}
+
+/// Options changing how `try_parse_with_options` reads a game; the default options are the ones of `try_parse`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseOptions {
+    /**
+        If none of the boards is of the turn of the player given by `active_player` (eg. a puzzle starting with black to move on a board at `begins_at = 0`),
+        move every board one half-turn later so that they are, instead of refusing the game.
+    **/
+    pub shift_to_active_player: bool,
+}
+
+/**
+    Parses a game in the JSON format outputted by the 5dchess-notation tool.
+    The player to move is the one given by `active_player`, which must be the player whose turn it is on at least one of the boards; see `try_parse_with_options` to accept other games.
+**/
+pub fn try_parse(raw: &str) -> Result<game::Game, ParseError> {
+    try_parse_with_options(raw, &ParseOptions::default())
+}
+
+/// Same as `try_parse`, reading the game according to `options`
+pub fn try_parse_with_options(raw: &str, options: &ParseOptions) -> Result<game::Game, ParseError> {
+    let game_raw: GameRaw = serde_json::from_str(raw)?;
+
+    let n_squares = game_raw.width as usize * game_raw.height as usize;
+    if game_raw.timelines.iter().any(|tl| tl.states.iter().any(|b| b.len() != n_squares)) {
+        return Err(ParseError::Format("a board does not have width × height squares"));
+    }
 
     let even_initial_timelines = game_raw
         .timelines
@@ -31,11 +149,13 @@ pub fn parse(raw: &str) -> Option<game::Game> {
     let min_timeline = game_raw.timelines
         .iter()
         .map(|tl| tl.index)
-        .min_by_key(|x| (*x) as isize)?;
+        .min_by_key(|x| (*x) as isize)
+        .ok_or(ParseError::Format("there are no timelines"))?;
     let max_timeline = game_raw.timelines
         .iter()
         .map(|tl| tl.index)
-        .max_by_key(|x| (*x) as isize)?;
+        .max_by_key(|x| (*x) as isize)
+        .ok_or(ParseError::Format("there are no timelines"))?;
 
     let timeline_width = ((-min_timeline).min(max_timeline) + 1.0).round();
     let active_timelines = game_raw.timelines
@@ -43,7 +163,8 @@ pub fn parse(raw: &str) -> Option<game::Game> {
         .filter(|tl| tl.index.abs() <= timeline_width);
     let present = active_timelines
         .map(|tl| tl.begins_at + (tl.states.len() as isize) - 1)
-        .min()?;
+        .min()
+        .ok_or(ParseError::Format("there are no active timelines"))?;
 
     let mut res = game::Game::new(game_raw.width, game_raw.height);
 
@@ -56,20 +177,425 @@ pub fn parse(raw: &str) -> Option<game::Game> {
     for tl in game_raw.timelines.into_iter() {
         res.timelines.insert(
             de_l(tl.index, even_initial_timelines),
-            de_timeline(tl, even_initial_timelines),
+            de_timeline(tl, even_initial_timelines)?,
         );
     }
 
-    Some(res)
+    if res
+        .timelines
+        .values()
+        .all(|tl| tl.states.iter().all(|b| b.active_player() != res.info.active_player))
+    {
+        if !options.shift_to_active_player {
+            return Err(ParseError::Format("none of the boards is of the active player's turn"));
+        }
+        res.delay_by_half_turn();
+    }
+
+    Ok(res)
+}
+
+/// A reason for which `read_and_validate` refuses a game
+#[derive(Debug)]
+pub enum PositionError {
+    /// The game could not be loaded
+    Parse(ParseError),
+    /// The loaded game is not consistent, see `Game::validate_consistency`
+    Inconsistent(game::ConsistencyError),
+    /// A pawn stands on the first or last rank, from which it can neither have started nor be left unpromoted
+    PawnOnBackRank(game::Coords),
+    /// The royal piece at these coordinates, of the player who just played, can be taken by the active player
+    OpponentInCheck(game::Coords),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::Parse(e) => write!(f, "{}", e),
+            PositionError::Inconsistent(e) => write!(f, "Inconsistent game: {:?}", e),
+            PositionError::PawnOnBackRank((l, t, x, y)) => {
+                write!(f, "Illegal position: pawn on a back rank at ({}T{}) ({}, {})", l, t, x, y)
+            }
+            PositionError::OpponentInCheck((l, t, x, y)) => {
+                write!(f, "Illegal position: the royal piece at ({}T{}) ({}, {}) can be taken", l, t, x, y)
+            }
+        }
+    }
 }
 
-fn de_board(raw: Vec<usize>, t: isize, l: i32, width: u8, height: u8) -> game::Board {
+impl std::error::Error for PositionError {}
+
+impl From<ParseError> for PositionError {
+    fn from(e: ParseError) -> Self {
+        PositionError::Parse(e)
+    }
+}
+
+/// Reads the file at `path`, parses it (see `try_parse`) and checks that the position that it holds could have been reached (see `validate_position`)
+pub fn read_and_validate<P: AsRef<Path>>(path: P) -> Result<game::Game, PositionError> {
+    let game = try_read_and_parse(path)?;
+    validate_position(&game)?;
+    Ok(game)
+}
+
+/**
+    Checks that `game` is consistent (see `Game::validate_consistency`) and that its position could have been reached, reporting the first violation found:
+    - no pawn stands on the first or last rank of a board
+    - the player who just played did not leave one of their royal pieces (see `Game::check_rule`) to be taken; with `CheckRule::AnyKingSafe`, this is not checked
+
+    Timelines are looked at from the lowest index upwards, and the boards of a timeline from the earliest one.
+**/
+pub fn validate_position(game: &game::Game) -> Result<(), PositionError> {
+    game.validate_consistency().map_err(PositionError::Inconsistent)?;
+
+    let mut indices = game.timelines.keys().copied().collect::<Vec<_>>();
+    indices.sort_unstable();
+    for l in indices {
+        for board in game.timelines[&l].states.iter() {
+            for (coords, _) in board.coords().filter(|(_, piece)| piece.is_pawn()) {
+                if coords.3 == 0 || coords.3 == board.height - 1 {
+                    return Err(PositionError::PawnOnBackRank(coords));
+                }
+            }
+        }
+    }
+
+    if game.check_rule != game::CheckRule::AnyKingSafe {
+        let virtual_boards: Vec<&game::Board> = Vec::new();
+        let mut boards = moves::get_own_boards(game, &virtual_boards, &game.info);
+        boards.sort_by_key(|board| (board.l, board.t));
+        for board in boards {
+            if let Some(mv) = moves::probable_moves(game, board, &virtual_boards)
+                .into_iter()
+                .find(|mv| game.check_rule.is_royal(mv.dst_piece, !game.info.active_player))
+            {
+                return Err(PositionError::OpponentInCheck(mv.dst));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// What `read_metadata` gathers about a game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameMeta {
+    /// The name of the registered variant (see `VariantRegistry`) whose starting boards the game starts from, None if there is none
+    pub variant: Option<String>,
+    pub width: u8,
+    pub height: u8,
+    /// The player to move (true for white)
+    pub active_player: bool,
+    /// The number of the current full turn, see `Game::full_turn_number`
+    pub turn: u32,
+    pub timelines: usize,
+    pub boards: usize,
+}
+
+/// The parts of `GameRaw` read by `read_metadata`
+#[derive(Debug, Deserialize)]
+struct GameMetaRaw {
+    timelines: Vec<TimelineMetaRaw>,
+    width: u8,
+    height: u8,
+    active_player: bool,
+}
+
+/// The parts of `TimelineRaw` read by `read_metadata`
+#[derive(Debug, Deserialize)]
+struct TimelineMetaRaw {
+    index: f32,
+    states: StatesMetaRaw,
+    begins_at: isize,
+    emerges_from: Option<f32>,
+}
+
+/// The first board of a timeline and its number of boards; the other boards are skipped over without being stored
+#[derive(Debug)]
+struct StatesMetaRaw {
+    first: Option<Vec<usize>>,
+    len: usize,
+}
+
+impl<'de> Deserialize<'de> for StatesMetaRaw {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StatesVisitor;
+
+        impl<'de> Visitor<'de> for StatesVisitor {
+            type Value = StatesMetaRaw;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a list of boards")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<StatesMetaRaw, A::Error> {
+                let first: Option<Vec<usize>> = seq.next_element()?;
+                let mut len = first.is_some() as usize;
+                while seq.next_element::<IgnoredAny>()?.is_some() {
+                    len += 1;
+                }
+                Ok(StatesMetaRaw { first, len })
+            }
+        }
+
+        deserializer.deserialize_seq(StatesVisitor)
+    }
+}
+
+/// Reads the file at `path` and gathers its metadata (see `parse_metadata`)
+pub fn read_metadata<P: AsRef<Path>>(path: P) -> Result<GameMeta, ParseError> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    parse_metadata(&contents)
+}
+
+/**
+    Gathers the metadata of a game in the JSON format outputted by the 5dchess-notation tool, as `try_parse` would read it, without building its boards:
+    only the first board of each timeline is kept, to be compared with the starting boards of the registered variants, and the other ones are merely counted.
+    It takes about half the time of `try_parse` (see `benches/metadata.rs`), which adds up when indexing large collections of games. The format does not record how a game ended, so the result is not part of the metadata.
+**/
+pub fn parse_metadata(raw: &str) -> Result<GameMeta, ParseError> {
+    let meta_raw: GameMetaRaw = serde_json::from_str(raw)?;
+
+    let even_initial_timelines = meta_raw
+        .timelines
+        .iter()
+        .any(|tl| tl.index == -0.5 || tl.index == 0.5);
+    let min_timeline = meta_raw.timelines
+        .iter()
+        .map(|tl| tl.index)
+        .min_by_key(|x| (*x) as isize)
+        .ok_or(ParseError::Format("there are no timelines"))?;
+    let max_timeline = meta_raw.timelines
+        .iter()
+        .map(|tl| tl.index)
+        .max_by_key(|x| (*x) as isize)
+        .ok_or(ParseError::Format("there are no timelines"))?;
+
+    // Same as in `try_parse`
+    let timeline_width = ((-min_timeline).min(max_timeline) + 1.0).round();
+    let present = meta_raw.timelines
+        .iter()
+        .filter(|tl| tl.index.abs() <= timeline_width)
+        .map(|tl| tl.begins_at + (tl.states.len as isize) - 1)
+        .min()
+        .ok_or(ParseError::Format("there are no active timelines"))?;
+    let has_active_board = meta_raw.timelines.iter().any(|tl| {
+        tl.states.len > 1 || tl.states.len == 1 && (tl.begins_at % 2 == 0) == meta_raw.active_player
+    });
+    let present = if has_active_board { present } else { present + 1 };
+
+    // The initial timelines are those starting with the game: the timelines created later start from their second board at least
+    let first_t = meta_raw.timelines.iter().map(|tl| tl.begins_at).min().unwrap_or(0);
+    let initial_timelines = || meta_raw.timelines.iter().filter(|tl| tl.begins_at == first_t && tl.emerges_from.is_none());
+    let starts_from = |variant: &game::Game| {
+        variant.width == meta_raw.width
+            && variant.height == meta_raw.height
+            && variant.timelines.len() == initial_timelines().count()
+            && initial_timelines().all(|tl| {
+                match (variant.get_timeline(de_l(tl.index, even_initial_timelines)), &tl.states.first) {
+                    (Some(timeline), Some(first)) => timeline.states.first().is_some_and(|board| {
+                        board.pieces.len() == first.len()
+                            && board.pieces.iter().zip(first.iter()).all(|(piece, raw)| game::Piece::try_from(*raw) == Ok(*piece))
+                    }),
+                    _ => false,
+                }
+            })
+    };
+    let variant = VariantRegistry::names()
+        .into_iter()
+        .find(|name| VariantRegistry::get(name).is_some_and(|variant| starts_from(&variant)));
+
+    Ok(GameMeta {
+        variant,
+        width: meta_raw.width,
+        height: meta_raw.height,
+        active_player: meta_raw.active_player,
+        turn: (present.max(0) / 2) as u32 + 1,
+        timelines: meta_raw.timelines.len(),
+        boards: meta_raw.timelines.iter().map(|tl| tl.states.len).sum(),
+    })
+}
+
+/// Reads the file at `path` and parses it (see `try_parse_partial`)
+pub fn try_read_and_parse_partial<P: AsRef<Path>>(path: P) -> Result<PartialTurn, ParseError> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    try_parse_partial(&contents)
+}
+
+/**
+    Same as `try_parse`, but if the game was saved in the middle of a turn, the boards created by the moves already made during that turn are separated from the rest of the game.
+
+    As the moves are not recorded, these boards are inferred from the position: they are the last boards of their timeline on which it is the opponent's turn,
+    and which follow a board at or before the present of the beginning of the turn.
+    Boards that the opponent was not required to play during their previous turn and that satisfy this are thus also considered to be part of the turn in progress.
+    If the game was saved between two turns, `boards` is empty.
+**/
+pub fn try_parse_partial(raw: &str) -> Result<PartialTurn, ParseError> {
+    let game = try_parse(raw)?;
+    let player = game.info.active_player;
+
+    // Timelines whose last board is the opponent's, which may have been played on during this turn
+    let played: Vec<(i32, isize)> = game
+        .timelines
+        .values()
+        .filter(|tl| tl.states[tl.states.len() - 1].active_player() != player)
+        .map(|tl| (tl.index, tl.begins_at + tl.states.len() as isize - 1))
+        .collect();
+
+    let mut res = game.clone();
+    for (l, _) in played.iter() {
+        let tl = res.timelines.get_mut(l).unwrap();
+        tl.states.pop();
+        if tl.states.is_empty() {
+            res.timelines.remove(l);
+        }
+    }
+    if res.timelines.is_empty() {
+        return Err(ParseError::Format("there are no timelines"));
+    }
+    res.info.min_timeline = *res.timelines.keys().min().unwrap();
+    res.info.max_timeline = *res.timelines.keys().max().unwrap();
+    let present = res
+        .timelines
+        .values()
+        .filter(|tl| res.info.is_timeline_active(tl.index))
+        .map(|tl| tl.begins_at + tl.states.len() as isize - 1)
+        .min()
+        .ok_or(ParseError::Format("there are no active timelines"))?;
+
+    // Put back the boards that could not have been created this turn
+    let mut boards = Vec::new();
+    for (l, t) in played {
+        if t <= present + 1 {
+            boards.push(game.get_board(l, t).unwrap().clone());
+        } else {
+            res.timelines.insert(l, game.timelines[&l].clone());
+        }
+    }
+
+    if boards.is_empty() {
+        return Ok(PartialTurn {
+            info: game.info,
+            game,
+            boards,
+        });
+    }
+    res.info.min_timeline = *res.timelines.keys().min().unwrap();
+    res.info.max_timeline = *res.timelines.keys().max().unwrap();
+    res.info.present = present;
+
+    Ok(PartialTurn {
+        info: res.info,
+        game: res,
+        boards,
+    })
+}
+
+/**
+    Serializes `game` into the JSON format read by `parse` (the one outputted by the 5dchess-notation tool), along with the turns played from it; noops are omitted.
+    `try_parse_with_moves` reads both back, eg. for a game played through a `GameDriver` from `start`: `to_notation_tool_json(&start, &driver.played().cloned().collect::<Vec<_>>())`.
+**/
+pub fn to_notation_tool_json(game: &game::Game, turns: &[Moveset]) -> String {
+    let even = game.info.even_initial_timelines;
+    let mut timelines = game.timelines.values().collect::<Vec<_>>();
+    timelines.sort_by_key(|tl| tl.index);
+
+    let en_coords = |(l, t, x, y): game::Coords| (en_l(l, even), t, x, y);
+    let game_raw = GameRaw {
+        timelines: timelines.into_iter().map(|tl| en_timeline(tl, even)).collect(),
+        width: game.width,
+        height: game.height,
+        active_player: game.info.active_player,
+        moves: turns
+            .iter()
+            .map(|turn| {
+                turn.0
+                    .iter()
+                    .filter(|mv| !mv.noop)
+                    .map(|mv| MoveRaw {
+                        from: en_coords(mv.src),
+                        to: en_coords(mv.dst),
+                    })
+                    .collect()
+            })
+            .collect(),
+    };
+
+    serde_json::to_string(&game_raw).expect("Couldn't serialize game")
+}
+
+/**
+    Parses a game written by `to_notation_tool_json` (see `try_parse`), along with the turns played from it.
+    Returns the starting position and the turns, each of them being validated when played; fails with `ParseError::Format` if one of them cannot be played.
+**/
+pub fn try_parse_with_moves(raw: &str) -> Result<(game::Game, Vec<Moveset>), ParseError> {
+    let game = try_parse(raw)?;
+    let turns_raw = serde_json::from_str::<GameRaw>(raw)?.moves;
+    let even = game.info.even_initial_timelines;
+    let de_coords = |(l, t, x, y): (f32, isize, u8, u8)| (de_l(l, even), t, x, y);
+
+    let mut current = game.clone();
+    let mut turns = Vec::with_capacity(turns_raw.len());
+    for turn_raw in turns_raw {
+        let virtual_boards = Vec::new();
+        let mut assembler = TurnAssembler::new(&current, &virtual_boards, &current.info);
+        for mv in turn_raw {
+            assembler
+                .try_push_coords(de_coords(mv.from), de_coords(mv.to))
+                .ok_or(ParseError::Format("a move cannot be played"))?;
+        }
+        let moveset = Moveset(assembler.moves);
+        current
+            .advance_turn(&moveset)
+            .map_err(|_| ParseError::Format("a turn cannot be played"))?;
+        turns.push(moveset);
+    }
+
+    Ok((game, turns))
+}
+
+/**
+    Serializes `turn` into a JSON snapshot, read back by `try_parse_snapshot`. Unlike the format of `to_notation_tool_json`, every field of the game and its boards is kept:
+    the boards played during the turn, the castling rights and king positions, the travel and check rules and the promotion ranks.
+**/
+pub fn to_snapshot_json(turn: &PartialTurn) -> String {
+    serde_json::to_string(turn).expect("Couldn't serialize snapshot")
+}
+
+/// Parses a snapshot written by `to_snapshot_json`
+pub fn try_parse_snapshot(raw: &str) -> Result<PartialTurn, ParseError> {
+    let turn: PartialTurn = serde_json::from_str(raw)?;
+
+    let n_squares = turn.game.width as usize * turn.game.height as usize;
+    let mut boards = turn.game.timelines.values().flat_map(|tl| tl.states.iter()).chain(turn.boards.iter());
+    if boards.any(|b| b.width != turn.game.width || b.height != turn.game.height || b.pieces.len() != n_squares) {
+        return Err(ParseError::Format("a board does not have width × height squares"));
+    }
+    if turn.game.timelines.iter().any(|(l, tl)| *l != tl.index || tl.states.is_empty()) {
+        return Err(ParseError::Format("a timeline is misindexed or empty"));
+    }
+
+    Ok(turn)
+}
+
+/// Reads the file at `path` and parses it as a snapshot (see `try_parse_snapshot`)
+pub fn try_read_and_parse_snapshot<P: AsRef<Path>>(path: P) -> Result<PartialTurn, ParseError> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    try_parse_snapshot(&contents)
+}
+
+fn de_board(raw: Vec<usize>, t: isize, l: i32, width: u8, height: u8) -> Result<game::Board, ParseError> {
     let mut res = game::Board::new(t, l, width, height);
     res.pieces = raw
         .into_iter()
-        .map(|x| game::Piece::from(x))
-        .collect();
-    res
+        .map(game::Piece::try_from)
+        .collect::<Result<_, _>>()
+        .map_err(|_| ParseError::Format("a square holds an unknown piece"))?;
+    res.recount_pieces();
+    Ok(res)
 }
 
 fn de_l(raw: f32, even: bool) -> i32 {
@@ -80,7 +606,7 @@ fn de_l(raw: f32, even: bool) -> i32 {
     }
 }
 
-fn de_timeline(raw: TimelineRaw, even: bool) -> game::Timeline {
+fn de_timeline(raw: TimelineRaw, even: bool) -> Result<game::Timeline, ParseError> {
     let mut res = game::Timeline::new(
         de_l(raw.index, even),
         raw.width,
@@ -99,7 +625,242 @@ fn de_timeline(raw: TimelineRaw, even: bool) -> game::Timeline {
         .into_iter()
         .enumerate()
         .map(|(i, b)| de_board(b, begins_at + i as isize, index, width, height))
-        .collect();
+        .collect::<Result<_, _>>()?;
+
+    Ok(res)
+}
+
+fn en_l(l: i32, even: bool) -> f32 {
+    if even && l == -1 {
+        -0.5
+    } else if even && l == 0 {
+        0.5
+    } else if even && l < -1 {
+        (l + 1) as f32
+    } else {
+        l as f32
+    }
+}
+
+fn en_timeline(tl: &game::Timeline, even: bool) -> TimelineRaw {
+    TimelineRaw {
+        index: en_l(tl.index, even),
+        states: tl
+            .states
+            .iter()
+            .map(|b| b.pieces.iter().map(|p| usize::from(*p)).collect())
+            .collect(),
+        width: tl.width,
+        height: tl.height,
+        begins_at: tl.begins_at,
+        emerges_from: tl.emerges_from.map(|l| en_l(l, even)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::play;
+    use crate::variant;
+
+    #[test]
+    fn notation_tool_json_round_trip() {
+        let start = variant::standard();
+        let mut game = start.clone();
+        let turns = vec![
+            play(&mut game, &[((0, 0, 6, 0), (0, 0, 5, 2))]),
+            play(&mut game, &[((0, 1, 6, 7), (0, 1, 5, 5))]),
+            // The knight jumps back to the first board, branching off timeline 1
+            play(&mut game, &[((0, 2, 5, 2), (0, 0, 7, 2))]),
+            play(&mut game, &[((1, 1, 1, 7), (1, 1, 2, 5))]),
+        ];
+        assert_eq!(game.info.max_timeline, 1);
+
+        let raw = to_notation_tool_json(&start, &turns);
+        let (parsed, parsed_turns) = try_parse_with_moves(&raw).unwrap();
+        assert_eq!(parsed_turns, turns);
+        assert_eq!(to_notation_tool_json(&parsed, &parsed_turns), raw);
+
+        // Without any turn, the output is the one read by `try_parse`
+        let raw = to_notation_tool_json(&game, &[]);
+        assert!(!raw.contains("moves"));
+        assert_eq!(to_notation_tool_json(&try_parse(&raw).unwrap(), &[]), raw);
+    }
+
+    #[test]
+    fn metadata_with_unknown_piece() {
+        let raw = to_notation_tool_json(&variant::standard(), &[]);
+        assert!(parse_metadata(&raw).unwrap().variant.is_some());
+
+        // An unknown piece index on a starting board matches no variant rather than panicking
+        assert!(raw.contains(r#""states":[[4,"#));
+        let raw = raw.replacen(r#""states":[[4,"#, r#""states":[[99,"#, 1);
+        let meta = parse_metadata(&raw).unwrap();
+        assert_eq!(meta.variant, None);
+        assert_eq!((meta.width, meta.height, meta.timelines, meta.turn), (8, 8, 1, 1));
+        assert!(try_parse(&raw).is_err());
+    }
+
+    #[test]
+    fn notation_tool_json_unplayable_move() {
+        let start = variant::standard();
+        let mut raw = to_notation_tool_json(&start, &[]);
+        raw.truncate(raw.len() - 1);
+        raw.push_str(r#","moves":[[{"from":[0.0,0,4,1],"to":[0.0,0,4,4]}]]}"#);
+        assert!(matches!(try_parse_with_moves(&raw), Err(ParseError::Format(_))));
+    }
+
+    #[test]
+    fn read_errors() {
+        let dir = std::env::temp_dir().join(format!("chess5dlib-parse-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert!(matches!(try_read_and_parse(dir.join("missing.json")), Err(ParseError::Io(_))));
 
-    res
+        std::fs::write(dir.join("malformed.json"), r#"{"timelines": [{"index": 0.0, "#).unwrap();
+        assert!(matches!(try_read_and_parse(dir.join("malformed.json")), Err(ParseError::Json(_))));
+
+        // The piece codes go up to 41; an unknown code is refused rather than panicking
+        let mut raw = to_notation_tool_json(&variant::standard(), &[]);
+        raw = raw.replacen("[4,2,3,5,6,3,2,4", "[99,2,3,5,6,3,2,4", 1);
+        std::fs::write(dir.join("unknown-piece.json"), raw).unwrap();
+        assert!(matches!(try_read_and_parse(dir.join("unknown-piece.json")), Err(ParseError::Format(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_dir_skips_unreadable_files() {
+        let dir = std::env::temp_dir().join(format!("chess5dlib-load-dir-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("a-standard.json"), to_notation_tool_json(&variant::standard(), &[])).unwrap();
+        std::fs::write(dir.join("b-truncated.json"), r#"{"timelines": ["#).unwrap();
+        std::fs::write(dir.join("c-notes.txt"), "1. (0T1)Ng1f3 / (0T1)Ng8f6").unwrap();
+        std::fs::write(dir.join("d-black.json"), to_notation_tool_json(&game::Game::standard_with_turn(false), &[])).unwrap();
+        // Subdirectories are not looked into
+        std::fs::write(dir.join("nested").join("e-standard.json"), to_notation_tool_json(&variant::standard(), &[])).unwrap();
+
+        let (games, errors) = load_dir(&dir);
+        assert_eq!(games.iter().map(|g| g.info.active_player).collect::<Vec<_>>(), vec![true, false]);
+        let failed: Vec<PathBuf> = errors.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(failed, vec![dir.join("b-truncated.json"), dir.join("c-notes.txt")]);
+        assert!(errors.iter().all(|(_, e)| matches!(e, ParseError::Json(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        let (games, errors) = load_dir(&dir);
+        assert!(games.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], (path, ParseError::Io(_)) if *path == dir));
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let value = |raw: &str| serde_json::from_str::<serde_json::Value>(raw).unwrap();
+        let mut turns: Vec<PartialTurn> = ["standard-complex-2.json", "mate-in-one.json"]
+            .iter()
+            .map(|name| crate::testing::fixture(name).into_partial_turn())
+            .collect();
+        // Halfway through a turn, with white's right to castle kingside gone
+        let mut start = variant::standard();
+        start.timelines.get_mut(&0).unwrap().states[0].castle_w = (true, false);
+        let virtual_boards = Vec::new();
+        let mut assembler = TurnAssembler::new(&start, &virtual_boards, &start.info);
+        assembler.try_push_coords((0, 0, 6, 0), (0, 0, 5, 2)).unwrap();
+        turns.push(PartialTurn {
+            boards: assembler.boards.clone(),
+            info: assembler.info,
+            game: start.clone(),
+        });
+
+        for turn in &turns {
+            let raw = to_snapshot_json(turn);
+            let parsed = try_parse_snapshot(&raw).unwrap();
+            assert_eq!(value(&to_snapshot_json(&parsed)), value(&raw));
+            assert_eq!(parsed.boards.len(), turn.boards.len());
+            let boards: Vec<&game::Board> = parsed.boards.iter().collect();
+            let original: Vec<&game::Board> = turn.boards.iter().collect();
+            assert_eq!(
+                crate::hash::zobrist_key(&parsed.game, &boards, &parsed.info),
+                crate::hash::zobrist_key(&turn.game, &original, &turn.info)
+            );
+        }
+        let parsed = try_parse_snapshot(&to_snapshot_json(&turns[2])).unwrap();
+        assert_eq!(parsed.game.get_board(0, 0).unwrap().castle_w, (true, false));
+        assert_eq!(parsed.boards[0].get(5, 2), Some(game::Piece::KnightW));
+
+        // A board missing a square is refused
+        let mut raw = value(&to_snapshot_json(&turns[2]));
+        raw["boards"][0]["pieces"].as_array_mut().unwrap().pop();
+        assert!(matches!(try_parse_snapshot(&raw.to_string()), Err(ParseError::Format(_))));
+    }
+
+    #[test]
+    fn partial_turn_fixture() {
+        // White branched off with (0T2)Nf3>>(0T1)h3 and black played on both timelines; white then played (1T2)Nh3g5 and may still play on timeline 0
+        let mut start = variant::standard();
+        play(&mut start, &[((0, 0, 6, 0), (0, 0, 5, 2))]);
+        play(&mut start, &[((0, 1, 6, 7), (0, 1, 5, 5))]);
+        play(&mut start, &[((0, 2, 5, 2), (0, 0, 7, 2))]);
+        play(&mut start, &[((0, 3, 1, 7), (0, 3, 2, 5)), ((1, 1, 1, 7), (1, 1, 2, 5))]);
+
+        let turn = try_read_and_parse_partial(crate::testing::fixture_path("mid-turn.json")).unwrap();
+        assert_eq!(turn.boards.iter().map(|b| (b.l, b.t)).collect::<Vec<_>>(), vec![(1, 3)]);
+        assert_eq!(turn.boards[0].get(6, 4), Some(game::Piece::KnightW));
+        assert!(turn.info.active_player);
+        assert_eq!((turn.info.present, start.info.present), (2, 2));
+        // The notation tool's format has no castling rights, so only the pieces are compared
+        let pieces = |game: &game::Game| {
+            let mut res: Vec<(i32, Vec<Vec<game::Piece>>)> =
+                game.timelines.values().map(|tl| (tl.index, tl.states.iter().map(|b| b.pieces.clone()).collect())).collect();
+            res.sort_by_key(|(l, _)| *l);
+            res
+        };
+        assert_eq!(pieces(&turn.game), pieces(&start));
+
+        // The turn can be carried on from the boards already played
+        let boards: Vec<&game::Board> = turn.boards.iter().collect();
+        let mut assembler = TurnAssembler::new(&turn.game, &boards, &turn.info);
+        assert!(assembler.try_push_coords((0, 4, 6, 1), (0, 4, 6, 2)).is_some());
+
+        // A game saved between two turns has no board in progress
+        let raw = std::fs::read_to_string(crate::testing::fixture_path("standard-complex-2.json")).unwrap();
+        let turn = try_parse_partial(&raw).unwrap();
+        assert!(turn.boards.is_empty());
+        assert_eq!(turn.info.present, turn.game.info.present);
+    }
+
+    #[test]
+    fn black_to_move() {
+        let raw = to_notation_tool_json(&game::Game::standard_with_turn(false), &[]);
+        let game = try_parse(&raw).unwrap();
+        assert!(!game.info.active_player);
+        let virtual_boards = Vec::new();
+        let own_boards = moves::get_own_boards(&game, &virtual_boards, &game.info);
+        assert_eq!(own_boards.iter().map(|b| (b.l, b.t)).collect::<Vec<_>>(), vec![(0, 1)]);
+        let moves = moves::probable_moves(&game, own_boards[0], &virtual_boards);
+        assert_eq!(moves.len(), 20);
+        assert!(moves.iter().all(|mv| mv.src_piece.is_black()));
+        assert_eq!(moves::legal_movesets(&game, &game.info, &virtual_boards, 0, 0).count(), 20);
+
+        // Black to move on white's starting board is refused, unless the boards may be shifted
+        let raw = to_notation_tool_json(&variant::standard(), &[]).replacen("\"active_player\":true", "\"active_player\":false", 1);
+        assert!(matches!(try_parse(&raw), Err(ParseError::Format(_))));
+        let options = ParseOptions {
+            shift_to_active_player: true,
+        };
+        let game = try_parse_with_options(&raw, &options).unwrap();
+        assert!(!game.info.active_player);
+        assert_eq!(game.get_board(0, 1).unwrap().get(4, 7), Some(game::Piece::KingB));
+        assert!(game.get_board(0, 0).is_none());
+    }
+
+    #[test]
+    fn pawn_on_first_rank_is_refused() {
+        let game = crate::testing::game(0, &["....k...", "pppppppp", "........", "........", "........", "........", "PPPPPPP.", "....K..P"]);
+        assert!(matches!(validate_position(&game), Err(PositionError::PawnOnBackRank((0, 0, 7, 0)))));
+
+        // Nine pawns are not refused: pawns may come from other timelines
+        let game = crate::testing::game(0, &["....k...", "pppppppp", "........", "....P...", "........", "........", "PPPPPPPP", "....K..."]);
+        assert!(validate_position(&game).is_ok());
+    }
 }
@@ -0,0 +1,60 @@
+// Re-exports of what is commonly needed to generate and validate moves, to be imported with `use chess5dlib::prelude::*;`
+pub use crate::driver::{DriverError, GameDriver, GameState};
+pub use crate::game::{
+    Board, BuildError, CheckRule, ConsistencyError, Coords, Game, GameBuilder, GameInfo, PartialTurn, Piece, Tile, Timeline,
+    TravelRules,
+};
+pub use crate::moves::{
+    all_boards_played, capture_movesets, check_evasions, checking_movesets, get_opponent_boards, get_own_boards, is_forced_to_branch,
+    is_move_valid, is_moveset_legal, jumpable_boards, legal_moves_from_streaming, legal_movesets, legal_movesets_on_timeline, legal_movesets_pruned,
+    move_targets_from, movers_to, probable_moves, validate_moves, Move, MoveIteratorExt, MoveTarget, MoveTargets,
+    MovesetIteratorExt,
+};
+pub use crate::moveset::{
+    AnnotatedMoveset, Annotation, Moveset, MovesetComplexity, MovesetNotation, NotationError, TurnAssembler, TurnError,
+};
+pub use crate::parse::{
+    load_dir, parse_metadata, read_and_validate, read_metadata, to_snapshot_json, try_parse, try_parse_snapshot, try_parse_with_options,
+    try_read_and_parse, validate_position, GameMeta, ParseError, ParseOptions, PositionError,
+};
+pub use crate::variant::VariantRegistry;
+
+/// Everything in the prelude, together with the lower-level types: iterators, virtual boardsets, hashing, opening books and random number generators
+pub mod full {
+    pub use super::*;
+    pub use crate::book::{FileBook, OpeningBook};
+    pub use crate::game::{BoardPool, Replay};
+    pub use crate::hash::{CanonicalKey, RepetitionTable, Zobrist, ZobristKey};
+    pub use crate::moves::{
+        DedupByResult, Direction5D, GenerationOrder, InverseMove, PinMask, QuietsOnly, Ray, ScanOrder, WithinRegion,
+    };
+    pub use crate::moveset::{MovesetIter, PrunedMovesetIter, RankedMoves};
+    pub use crate::random::{Rng, SplitMix64};
+    pub use crate::resolve::{Evaluator, Lore, PieceSquareTables, PieceValues};
+    pub use crate::tree::{Bound, PathHistory, TranspositionTable};
+    pub use crate::vboard::{
+        EmptyVirtualBoardset, RecursiveVirtualBoardset, RecursiveVirtualBoardsetIter, SimpleVirtualBoardset,
+        VirtualBoardset,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::full::*;
+
+    #[test]
+    fn play_a_turn_with_the_prelude_only() {
+        let game = Game::standard_with_turn(true);
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let (moves, _, info, _) = legal_movesets(&game, &game.info, &virtual_boards, 0, 0).next().unwrap();
+        assert!(!info.active_player);
+        assert!(validate_moves(&game, &virtual_boards, &game.info, &moves).into_iter().all(|valid| valid));
+
+        let mut driver = GameDriver::new(game.clone());
+        assert_eq!(driver.submit(&Moveset(moves)), Ok(GameState::Ongoing));
+        let snapshot = to_snapshot_json(&PartialTurn::snapshot(driver.game(), &virtual_boards, &driver.game().info));
+        assert_eq!(try_parse_snapshot(&snapshot).unwrap().info.present, 1);
+        let scheme = Zobrist::default();
+        assert_ne!(scheme.key(driver.game(), &virtual_boards, &driver.game().info), scheme.key(&game, &virtual_boards, &game.info));
+    }
+}
@@ -0,0 +1,102 @@
+// Tags positions by the kind of tactic that they hold, to sort them into a puzzle database
+
+use crate::{game::*, hash::*, moves::*, moveset::*, resolve::*, selfplay::*, tree::PathHistory};
+
+/// How much more than the current material balance the best moveset must keep, after the search, for `classify` to tag a position as `PuzzleTag::WinningMaterial`; in hundredths of the `*_VALUE` constants
+pub const WINNING_MATERIAL_MARGIN: i32 = 250;
+/// How much better than the second-best moveset the best moveset must be, after the search, for `classify` to tag a position as `PuzzleTag::OnlyMove`; in hundredths of the `*_VALUE` constants
+pub const ONLY_MOVE_MARGIN: i32 = 250;
+
+/// The kind of tactic found by `classify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PuzzleTag {
+    /// The active player checkmates their opponent in that many of their own turns, whatever the opponent plays
+    MateIn(usize),
+    /// The best moveset wins material (see `WINNING_MATERIAL_MARGIN`)
+    WinningMaterial,
+    /// The best moveset is much better than any other (see `ONLY_MOVE_MARGIN`), or is the only legal moveset
+    OnlyMove,
+    /// Nothing stands out
+    Unremarkable,
+}
+
+/**
+    Tags the position made of `game`, `virtual_boards` and `info` for its active player, along with the moveset that solves it (the best moveset found); returns None if there is no legal moveset.
+
+    Every legal moveset is searched with a material-only `Evaluator`, one more turn of the active player after the other, up to `depth` of their turns (each followed by the opponent's reply):
+    the first depth at which one of them forces a checkmate gives `PuzzleTag::MateIn`. Otherwise, the values of the deepest search are compared to the current material balance
+    for `PuzzleTag::WinningMaterial`, then the best value to the second best for `PuzzleTag::OnlyMove`.
+    As no moveset is left out, this is only practical on small positions and for shallow depths.
+**/
+pub fn classify(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, depth: usize) -> Option<(PuzzleTag, Moveset)> {
+    let eval = Evaluator {
+        material_w: 100.0,
+        mobility_w: 0.0,
+        king_safety_w: 0.0,
+        pst_w: 0.0,
+        timeline_w: 0.0,
+        ..Evaluator::default()
+    };
+    let limits = MatchLimits {
+        depth: 0,
+        max_bf: 0,
+        max_turns: 0,
+        ..MatchLimits::default()
+    };
+    let scheme = Zobrist::default();
+    let key = scheme.key(game, virtual_boards, info);
+    let movesets: Vec<(Vec<Move>, Vec<Board>, GameInfo)> = legal_movesets(game, info, virtual_boards, 0, 0)
+        .map(|(moves, boards, new_info, _score)| (moves, boards, new_info))
+        .collect();
+    if movesets.is_empty() {
+        return None;
+    }
+
+    let mut values: Vec<i32> = Vec::new();
+    for turns in 1..=depth.max(1) {
+        values = movesets
+            .iter()
+            .map(|(_moves, boards, new_info)| {
+                let merged_vboards: Vec<&Board> = virtual_boards.iter().copied().chain(boards.iter()).collect();
+                let mut history = PathHistory::new();
+                history.push(key);
+                history.push(scheme.update_key(key, boards, info, new_info));
+                -negamax(game, &merged_vboards, new_info, &eval, &limits, 2 * turns - 1, &mut history)
+            })
+            .collect();
+        if let Some(index) = values.iter().position(|value| *value == -MATE_SCORE) {
+            return Some((PuzzleTag::MateIn(turns), Moveset(movesets[index].0.clone())));
+        }
+    }
+
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by_key(|index| -values[*index]);
+    let best = values[order[0]];
+    let solution = Moveset(movesets[order[0]].0.clone());
+
+    let material = eval.evaluate(game, virtual_boards, info);
+    let material = if info.active_player { material } else { -material };
+    let tag = if best - material >= WINNING_MATERIAL_MARGIN {
+        PuzzleTag::WinningMaterial
+    } else if order.len() == 1 || best - values[order[1]] >= ONLY_MOVE_MARGIN {
+        PuzzleTag::OnlyMove
+    } else {
+        PuzzleTag::Unremarkable
+    };
+    Some((tag, solution))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn back_rank_mate_in_one() {
+        let game = testing::fixture("mate-in-one.json");
+        let (tag, solution) = classify(&game, &vec![], &game.info, 2).unwrap();
+        assert_eq!(tag, PuzzleTag::MateIn(1));
+        assert_eq!(solution.0.len(), 1);
+        assert_eq!(solution.0[0].to_string(), "(0T1)Ra1a8");
+    }
+}
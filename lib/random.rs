@@ -0,0 +1,43 @@
+// Sources of randomness, for random movers and playouts
+
+/**
+    A source of pseudo-random numbers.
+    Only `next_u64` needs to be implemented, which makes it easy to wrap other generators.
+**/
+pub trait Rng {
+    /// Returns a pseudo-random, uniformly distributed `u64`
+    fn next_u64(&mut self) -> u64;
+
+    /// Returns a pseudo-random number within `[0; 1)`
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a pseudo-random number within `0..n`; `n` must be greater than zero
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// A small and fast, seedable pseudo-random number generator (SplitMix64); it is not suited for cryptographic purposes
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Creates a new generator; two generators created with the same seed yield the same numbers
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+}
+
+impl Rng for SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
@@ -40,6 +40,17 @@ pub const ATTACK_ROOK_REWARD: i32 = 1;
 
 pub const MANY_KINGS_COST: i32 = -6;
 
+// Attack units of the pieces attacking the squares around a king, see `moves::king_attack_score`
+pub const KING_ATTACK_PAWN_UNITS: i32 = 1;
+pub const KING_ATTACK_KNIGHT_UNITS: i32 = 2;
+pub const KING_ATTACK_BISHOP_UNITS: i32 = 2;
+pub const KING_ATTACK_ROOK_UNITS: i32 = 3;
+pub const KING_ATTACK_QUEEN_UNITS: i32 = 5;
+pub const KING_ATTACK_PRINCESS_UNITS: i32 = 4;
+pub const KING_ATTACK_UNICORN_UNITS: i32 = 2;
+pub const KING_ATTACK_DRAGON_UNITS: i32 = 2;
+pub const KING_ATTACK_KING_UNITS: i32 = 1;
+
 /**
     Structure containing information about hotspots on a board, enemies attacking the current king, danger zones, etc.
 **/
@@ -297,6 +308,7 @@ pub fn score_moveset<'a, T: Iterator<Item = &'a Board>>(
     moveset: Vec<Move>,
 ) -> Option<(Vec<Move>, Vec<Board>, GameInfo, f32)> {
     let mut moveset_boards: Vec<Board> = Vec::new();
+    let initial_info = *info;
     let mut info = info.clone();
     let white = info.active_player;
 
@@ -307,6 +319,11 @@ pub fn score_moveset<'a, T: Iterator<Item = &'a Board>>(
         info = new_info;
     }
 
+    // Each move was generated on its own: an earlier move of the moveset may have played on the board that a later move jumps to
+    if !game.travel_rules.allow_branching && (info.min_timeline, info.max_timeline) != (initial_info.min_timeline, initial_info.max_timeline) {
+        return None;
+    }
+
     let merged_vboards: Vec<&Board> = virtual_boards
         .iter()
         .map(|x| *x)
@@ -320,196 +337,585 @@ pub fn score_moveset<'a, T: Iterator<Item = &'a Board>>(
         info.present += 1;
         info.active_player = !info.active_player;
 
-        let mut score: f32 = 0.0;
+        Some(score_legal_moveset(moveset, moveset_boards, info, white))
+    } else {
+        None
+    }
+}
 
-        for board in &moveset_boards {
-            if board.t > info.present {
-                score += if white {
-                    -INACTIVE_BOARD_MOVE_COST
-                } else {
-                    INACTIVE_BOARD_MOVE_COST
-                };
-            }
+/**
+    Gives a score to a moveset that is known to be legal, as `score_moveset` does: `moveset_boards` are the boards that `moveset` creates, `info` is the `GameInfo` for the opponent's turn
+    and `white` is the player who made the moveset.
+**/
+pub fn score_legal_moveset(
+    moveset: Vec<Move>,
+    moveset_boards: Vec<Board>,
+    info: GameInfo,
+    white: bool,
+) -> (Vec<Move>, Vec<Board>, GameInfo, f32) {
+    let mut score: f32 = 0.0;
 
-            // TODO: fix this as per the new index system
-            let board_mult: f32 = if board.l < 0 && -board.l > info.max_timeline + 1
-                || board.l > 0 && board.l > -info.min_timeline + 1
-            {
-                INACTIVE_BRANCH_MULTIPLIER.powf((info.max_timeline + info.min_timeline).abs() as f32 - 1.0)
+    for board in &moveset_boards {
+        if board.t > info.present {
+            score += if white {
+                -INACTIVE_BOARD_MOVE_COST
             } else {
-                1.0
+                INACTIVE_BOARD_MOVE_COST
             };
-            let mut w_kings: usize = 0;
-            let mut b_kings: usize = 0;
-
-            let mut controlled_squares_w: Vec<bool> = Vec::with_capacity((board.width * board.height) as usize);
-            let mut controlled_squares_b: Vec<bool> = Vec::with_capacity((board.width * board.height) as usize);
-            for _ in 0..(board.width * board.height) {
-                controlled_squares_w.push(false);
-                controlled_squares_b.push(false);
-            }
+        }
 
-            for (index, piece) in board.pieces.iter().enumerate() {
-                let x = (index % board.width as usize) as u8;
-                let y = (index / board.width as usize) as u8;
-                if piece.is_blank() {
-                    continue;
+        // TODO: fix this as per the new index system
+        let board_mult: f32 = if board.l < 0 && -board.l > info.max_timeline + 1
+            || board.l > 0 && board.l > -info.min_timeline + 1
+        {
+            INACTIVE_BRANCH_MULTIPLIER.powf((info.max_timeline + info.min_timeline).abs() as f32 - 1.0)
+        } else {
+            1.0
+        };
+        let mut w_kings: usize = 0;
+        let mut b_kings: usize = 0;
+
+        let n_squares = board.width as usize * board.height as usize;
+        let mut controlled_squares_w: Vec<bool> = Vec::with_capacity(n_squares);
+        let mut controlled_squares_b: Vec<bool> = Vec::with_capacity(n_squares);
+        for _ in 0..n_squares {
+            controlled_squares_w.push(false);
+            controlled_squares_b.push(false);
+        }
+
+        for (index, piece) in board.pieces.iter().enumerate() {
+            let (x, y) = match board.square_of(index) {
+                Some(square) => square,
+                None => break,
+            };
+            if piece.is_blank() {
+                continue;
+            }
+            let mult: f32 = if piece.is_white() { 1.0 } else { -1.0 };
+            if piece.is_king() {
+                if piece.is_white() {
+                    w_kings += 1;
+                    if w_kings > 1 {
+                        score += MANY_KINGS_VALUE;
+                    }
+                } else {
+                    b_kings += 1;
+                    if b_kings > 1 {
+                        score -= MANY_KINGS_VALUE;
+                    }
                 }
-                let mult: f32 = if piece.is_white() { 1.0 } else { -1.0 };
-                if piece.is_king() {
-                    if piece.is_white() {
-                        w_kings += 1;
-                        if w_kings > 1 {
-                            score += MANY_KINGS_VALUE;
-                        }
-                    } else {
-                        b_kings += 1;
-                        if b_kings > 1 {
-                            score -= MANY_KINGS_VALUE;
+                score += KING_VALUE * mult * board_mult;
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        if dx == 0 && dy == 0
+                            || x == 0 && dx < 0
+                            || y == 0 && dy < 0
+                            || x == board.width - 1 && dx > 0
+                            || y == board.height - 1 && dy > 0
+                        {
+                            continue;
                         }
-                    }
-                    score += KING_VALUE * mult * board_mult;
-                    for dx in -1..=1 {
-                        for dy in -1..=1 {
-                            if dx == 0 && dy == 0
-                                || x == 0 && dx < 0
-                                || y == 0 && dy < 0
-                                || x == board.width - 1 && dx > 0
-                                || y == board.height - 1 && dy > 0
-                            {
-                                continue;
-                            }
+                        if board
+                            .get((x as isize + dx) as u8, (y as isize + dy) as u8)
+                            .map(|p| p.is_blank() || p.is_opponent_piece(piece.is_white()))
+                            .unwrap_or(false)
+                        {
+                            score -= KING_PROTECTION_VALUE * mult;
+
                             if board
-                                .get((x as isize + dx) as u8, (y as isize + dy) as u8)
+                                .get((x as isize + 2 * dx) as u8, (y as isize + 2 * dy) as u8)
                                 .map(|p| p.is_blank() || p.is_opponent_piece(piece.is_white()))
                                 .unwrap_or(false)
                             {
-                                score -= KING_PROTECTION_VALUE * mult;
-
-                                if board
-                                    .get((x as isize + 2 * dx) as u8, (y as isize + 2 * dy) as u8)
-                                    .map(|p| p.is_blank() || p.is_opponent_piece(piece.is_white()))
-                                    .unwrap_or(false)
-                                {
-                                    score -= KING_PROTECTION_VALUE_2 * mult;
-                                }
+                                score -= KING_PROTECTION_VALUE_2 * mult;
                             }
                         }
                     }
-                } else if piece.is_knight() {
-                    score += KNIGHT_VALUE * mult * board_mult;
-                } else if piece.is_bishop() {
-                    score += BISHOP_VALUE * mult * board_mult;
-                } else if piece.is_rook() {
-                    score += ROOK_VALUE * mult * board_mult;
-                } else if piece.is_queen() {
-                    score += QUEEN_VALUE * mult * board_mult;
-                } else if piece.is_unicorn() {
-                    score += UNICORN_VALUE * mult * board_mult;
-                } else if piece.is_dragon() {
-                    score += DRAGON_VALUE * mult * board_mult;
-                } else if piece.is_pawn() {
-                    score += PAWN_VALUE * mult * board_mult;
-                } else if piece.is_princess() {
-                    score += PRINCESS_VALUE * mult * board_mult;
                 }
+            } else if piece.is_knight() {
+                score += KNIGHT_VALUE * mult * board_mult;
+            } else if piece.is_bishop() {
+                score += BISHOP_VALUE * mult * board_mult;
+            } else if piece.is_rook() {
+                score += ROOK_VALUE * mult * board_mult;
+            } else if piece.is_queen() {
+                score += QUEEN_VALUE * mult * board_mult;
+            } else if piece.is_unicorn() {
+                score += UNICORN_VALUE * mult * board_mult;
+            } else if piece.is_dragon() {
+                score += DRAGON_VALUE * mult * board_mult;
+            } else if piece.is_pawn() {
+                score += PAWN_VALUE * mult * board_mult;
+            } else if piece.is_princess() {
+                score += PRINCESS_VALUE * mult * board_mult;
+            }
 
-                // Maybe replace with bitboard operations
-                // Or just dedupe that horror
-                if piece.is_white() {
-                    if piece.is_pawn() {
-                        set_controlled_square(&mut controlled_squares_w, index, 1, 1, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_w, index, 1, -1, board.width, board.height);
-                    } else if piece.is_knight() {
-                        set_controlled_square(&mut controlled_squares_w, index, 2, 1, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_w, index, 2, -1, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_w, index, -2, 1, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_w, index, -2, -1, board.width, board.height);
-
-                        set_controlled_square(&mut controlled_squares_w, index, 1, 2, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_w, index, 1, -2, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_w, index, -1, 2, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_w, index, -1, -2, board.width, board.height);
-                    }
+            // Maybe replace with bitboard operations
+            // Or just dedupe that horror
+            if piece.is_white() {
+                if piece.is_pawn() {
+                    set_controlled_square(&mut controlled_squares_w, index, 1, 1, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_w, index, 1, -1, board.width, board.height);
+                } else if piece.is_knight() {
+                    set_controlled_square(&mut controlled_squares_w, index, 2, 1, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_w, index, 2, -1, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_w, index, -2, 1, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_w, index, -2, -1, board.width, board.height);
 
-                    if piece.is_bishop() || piece.is_queen() || piece.is_princess() {
-                        set_controlled_square_slide(board, &mut controlled_squares_w, index, 1, 1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_w, index, -1, 1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_w, index, 1, -1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_w, index, -1, -1, board.width, board.height, white);
-                    }
+                    set_controlled_square(&mut controlled_squares_w, index, 1, 2, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_w, index, 1, -2, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_w, index, -1, 2, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_w, index, -1, -2, board.width, board.height);
+                }
 
-                    if piece.is_rook() || piece.is_queen() || piece.is_princess() {
-                        set_controlled_square_slide(board, &mut controlled_squares_w, index, 0, 1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_w, index, 0, -1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_w, index, 1, 0, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_w, index, -1, 0, board.width, board.height, white);
-                    }
-                } else if piece.is_black() {
-                    if piece.is_pawn() {
-                        set_controlled_square(&mut controlled_squares_b, index, -1, 1, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_b, index, -1, -1, board.width, board.height);
-                    } else if piece.is_knight() {
-                        set_controlled_square(&mut controlled_squares_b, index, 2, 1, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_b, index, 2, -1, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_b, index, -2, 1, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_b, index, -2, -1, board.width, board.height);
-
-                        set_controlled_square(&mut controlled_squares_b, index, 1, 2, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_b, index, 1, -2, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_b, index, -1, 2, board.width, board.height);
-                        set_controlled_square(&mut controlled_squares_b, index, -1, -2, board.width, board.height);
-                    }
+                if piece.is_bishop() || piece.is_queen() || piece.is_princess() {
+                    set_controlled_square_slide(board, &mut controlled_squares_w, index, 1, 1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_w, index, -1, 1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_w, index, 1, -1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_w, index, -1, -1, board.width, board.height, white);
+                }
 
-                    if piece.is_bishop() || piece.is_queen() || piece.is_princess() {
-                        set_controlled_square_slide(board, &mut controlled_squares_b, index, 1, 1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_b, index, -1, 1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_b, index, 1, -1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_b, index, -1, -1, board.width, board.height, white);
-                    }
+                if piece.is_rook() || piece.is_queen() || piece.is_princess() {
+                    set_controlled_square_slide(board, &mut controlled_squares_w, index, 0, 1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_w, index, 0, -1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_w, index, 1, 0, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_w, index, -1, 0, board.width, board.height, white);
+                }
+            } else if piece.is_black() {
+                if piece.is_pawn() {
+                    set_controlled_square(&mut controlled_squares_b, index, -1, 1, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_b, index, -1, -1, board.width, board.height);
+                } else if piece.is_knight() {
+                    set_controlled_square(&mut controlled_squares_b, index, 2, 1, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_b, index, 2, -1, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_b, index, -2, 1, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_b, index, -2, -1, board.width, board.height);
 
-                    if piece.is_rook() || piece.is_queen() || piece.is_princess() {
-                        set_controlled_square_slide(board, &mut controlled_squares_b, index, 0, 1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_b, index, 0, -1, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_b, index, 1, 0, board.width, board.height, white);
-                        set_controlled_square_slide(board, &mut controlled_squares_b, index, -1, 0, board.width, board.height, white);
-                    }
+                    set_controlled_square(&mut controlled_squares_b, index, 1, 2, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_b, index, 1, -2, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_b, index, -1, 2, board.width, board.height);
+                    set_controlled_square(&mut controlled_squares_b, index, -1, -2, board.width, board.height);
                 }
-            }
 
-            for controlled_square in controlled_squares_w {
-                if controlled_square {
-                    score += CONTROLLED_SQUARE_SCORE;
+                if piece.is_bishop() || piece.is_queen() || piece.is_princess() {
+                    set_controlled_square_slide(board, &mut controlled_squares_b, index, 1, 1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_b, index, -1, 1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_b, index, 1, -1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_b, index, -1, -1, board.width, board.height, white);
                 }
-            }
 
-            for controlled_square in controlled_squares_b {
-                if controlled_square {
-                    score -= CONTROLLED_SQUARE_SCORE;
+                if piece.is_rook() || piece.is_queen() || piece.is_princess() {
+                    set_controlled_square_slide(board, &mut controlled_squares_b, index, 0, 1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_b, index, 0, -1, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_b, index, 1, 0, board.width, board.height, white);
+                    set_controlled_square_slide(board, &mut controlled_squares_b, index, -1, 0, board.width, board.height, white);
                 }
             }
-
         }
 
-        // TODO: fix this
-        // Timeline advantages
-        if info.max_timeline > -info.min_timeline {
-            // black advantageous
-            score -= BRANCH_VALUE;
-            if info.max_timeline > -info.min_timeline + 1 {
-                score -= INACTIVE_BRANCH_COST * (info.max_timeline + info.min_timeline - 1) as f32;
+        for controlled_square in controlled_squares_w {
+            if controlled_square {
+                score += CONTROLLED_SQUARE_SCORE;
             }
-        } else if info.max_timeline < -info.min_timeline {
-            // white advantageous
-            score += BRANCH_VALUE;
-            if info.max_timeline < -info.min_timeline - 1 {
-                score -= INACTIVE_BRANCH_COST * (info.max_timeline + info.min_timeline + 1) as f32;
+        }
+
+        for controlled_square in controlled_squares_b {
+            if controlled_square {
+                score -= CONTROLLED_SQUARE_SCORE;
             }
         }
 
-        Some((moveset, moveset_boards, info, score))
+    }
+
+    // TODO: fix this
+    // Timeline advantages
+    if info.max_timeline > -info.min_timeline {
+        // black advantageous
+        score -= BRANCH_VALUE;
+        if info.max_timeline > -info.min_timeline + 1 {
+            score -= INACTIVE_BRANCH_COST * (info.max_timeline + info.min_timeline - 1) as f32;
+        }
+    } else if info.max_timeline < -info.min_timeline {
+        // white advantageous
+        score += BRANCH_VALUE;
+        if info.max_timeline < -info.min_timeline - 1 {
+            score -= INACTIVE_BRANCH_COST * (info.max_timeline + info.min_timeline + 1) as f32;
+        }
+    }
+
+    (moveset, moveset_boards, info, score)
+}
+
+/// Returns the value of `piece` (see the `*_VALUE` constants), positive for white pieces and negative for black pieces; kings and blank squares are worth nothing
+pub fn piece_value(piece: Piece) -> f32 {
+    let value = if piece.is_knight() {
+        KNIGHT_VALUE
+    } else if piece.is_bishop() {
+        BISHOP_VALUE
+    } else if piece.is_rook() {
+        ROOK_VALUE
+    } else if piece.is_queen() {
+        QUEEN_VALUE
+    } else if piece.is_unicorn() {
+        UNICORN_VALUE
+    } else if piece.is_dragon() {
+        DRAGON_VALUE
+    } else if piece.is_pawn() {
+        PAWN_VALUE
+    } else if piece.is_princess() {
+        PRINCESS_VALUE
     } else {
-        None
+        0.0
+    };
+    if piece.is_white() {
+        value
+    } else {
+        -value
+    }
+}
+
+/**
+    The value of each kind of piece, as integers, given to `Board::material`; kings are worth `king` but blank squares are always worth nothing.
+    The default values are those of `piece_value`, in hundredths of the `*_VALUE` constants: kings are worth nothing.
+**/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceValues {
+    pub pawn: i32,
+    pub knight: i32,
+    pub bishop: i32,
+    pub rook: i32,
+    pub queen: i32,
+    pub king: i32,
+    pub princess: i32,
+    pub unicorn: i32,
+    pub dragon: i32,
+}
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        let hundredths = |value: f32| (value * 100.0).round() as i32;
+        PieceValues {
+            pawn: hundredths(PAWN_VALUE),
+            knight: hundredths(KNIGHT_VALUE),
+            bishop: hundredths(BISHOP_VALUE),
+            rook: hundredths(ROOK_VALUE),
+            queen: hundredths(QUEEN_VALUE),
+            king: 0,
+            princess: hundredths(PRINCESS_VALUE),
+            unicorn: hundredths(UNICORN_VALUE),
+            dragon: hundredths(DRAGON_VALUE),
+        }
+    }
+}
+
+impl PieceValues {
+    /// Returns the value of `piece`, positive for white pieces and negative for black pieces
+    pub fn value(&self, piece: Piece) -> i32 {
+        let value = match piece {
+            Piece::Blank => 0,
+            Piece::PawnW | Piece::PawnB => self.pawn,
+            Piece::KnightW | Piece::KnightB => self.knight,
+            Piece::BishopW | Piece::BishopB => self.bishop,
+            Piece::RookW | Piece::RookB => self.rook,
+            Piece::QueenW | Piece::QueenB => self.queen,
+            Piece::KingW | Piece::KingB => self.king,
+            Piece::PrincessW | Piece::PrincessB => self.princess,
+            Piece::UnicornW | Piece::UnicornB => self.unicorn,
+            Piece::DragonW | Piece::DragonB => self.dragon,
+        };
+        if piece.is_white() {
+            value
+        } else {
+            -value
+        }
+    }
+}
+
+/// Returns the attack units of `piece` (see the `KING_ATTACK_*_UNITS` constants), whatever its color; blank squares are worth nothing
+pub fn king_attack_units(piece: Piece) -> i32 {
+    if piece.is_knight() {
+        KING_ATTACK_KNIGHT_UNITS
+    } else if piece.is_bishop() {
+        KING_ATTACK_BISHOP_UNITS
+    } else if piece.is_rook() {
+        KING_ATTACK_ROOK_UNITS
+    } else if piece.is_queen() {
+        KING_ATTACK_QUEEN_UNITS
+    } else if piece.is_unicorn() {
+        KING_ATTACK_UNICORN_UNITS
+    } else if piece.is_dragon() {
+        KING_ATTACK_DRAGON_UNITS
+    } else if piece.is_pawn() {
+        KING_ATTACK_PAWN_UNITS
+    } else if piece.is_princess() {
+        KING_ATTACK_PRINCESS_UNITS
+    } else if piece.is_king() {
+        KING_ATTACK_KING_UNITS
+    } else {
+        0
+    }
+}
+
+/// Returns the sum of the values of the pieces on the last board of every timeline (see `piece_value`); positive values favour white
+pub fn material_balance(game: &Game, virtual_boards: &Vec<&Board>) -> f32 {
+    game.timelines
+        .values()
+        .map(|tl| &tl.states[tl.states.len() - 1])
+        .chain(virtual_boards.iter().copied())
+        .filter(|board| is_last(game, virtual_boards, board))
+        .flat_map(|board| board.pieces.iter())
+        .map(|piece| piece_value(*piece))
+        .sum()
+}
+
+/**
+    Returns the change in `material_balance` caused by `moveset`, without playing it out: the value of the pieces it takes and of its promotions.
+    The pieces copied onto the new timelines that it creates are not accounted for.
+**/
+pub fn material_delta(game: &Game, moveset: &[Move]) -> f32 {
+    let mut delta: f32 = 0.0;
+    for mv in moveset {
+        if let Some(piece) = mv.captured() {
+            delta -= piece_value(piece);
+        }
+
+        // Same condition as in `Move::generate_vboards`
+        if mv.src_piece.is_pawn()
+            && !mv.castle
+            && mv.en_passant.is_none()
+            && mv.src.0 == mv.dst.0
+            && mv.src.1 == mv.dst.1
+            && mv.dst.3 == game.promotion_rank(mv.src_piece.is_white())
+        {
+            let queen = if mv.src_piece.is_white() { Piece::QueenW } else { Piece::QueenB };
+            delta += piece_value(queen) - piece_value(mv.src_piece);
+        }
+    }
+    delta
+}
+
+/// Returns the sum of `Board::mobility` over the last board of every timeline; positive values favour white
+pub fn mobility_balance(game: &Game, virtual_boards: &Vec<&Board>) -> i32 {
+    game.timelines
+        .values()
+        .map(|tl| &tl.states[tl.states.len() - 1])
+        .chain(virtual_boards.iter().copied())
+        .filter(|board| is_last(game, virtual_boards, board))
+        .map(|board| board.mobility(game, virtual_boards))
+        .sum()
+}
+
+/// Returns the sum of `Board::king_safety` over the last board of every timeline; positive values favour white
+pub fn king_safety_balance(game: &Game, virtual_boards: &Vec<&Board>) -> f32 {
+    game.timelines
+        .values()
+        .map(|tl| &tl.states[tl.states.len() - 1])
+        .chain(virtual_boards.iter().copied())
+        .filter(|board| is_last(game, virtual_boards, board))
+        .map(|board| board.king_safety())
+        .sum()
+}
+
+// Default piece-square tables (see `PieceSquareTables`), in hundredths of the `*_VALUE` constants like `Evaluator::evaluate`.
+// Each table lists the squares of an 8x8 board from white's point of view, white's back rank first (`a1`, `b1`, ..., `h8`).
+pub const PAWN_TABLE: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,   5,  10,  25,  25,  10,   5,   5,
+     10,  10,  20,  30,  30,  20,  10,  10,
+     50,  50,  50,  50,  50,  50,  50,  50,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+pub const KNIGHT_TABLE: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+pub const BISHOP_TABLE: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+pub const ROOK_TABLE: [i32; 64] = [
+      0,   0,   0,   5,   5,   0,   0,   0,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      5,  10,  10,  10,  10,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+pub const QUEEN_TABLE: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+pub const KING_TABLE: [i32; 64] = [
+     20,  30,  10,   0,   0,  10,  30,  20,
+     20,  20,   0,   0,   0,   0,  20,  20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+];
+// Unicorns and dragons only move across boards, so where they stand on their own board matters little
+pub const FLAT_TABLE: [i32; 64] = [0; 64];
+
+/**
+    Bonuses and penalties given to each kind of piece depending on the square it stands on, see `Board::pst`.
+    Tables are written for an 8x8 board from white's point of view, white's back rank first; they are mirrored for black's pieces
+    and stretched over boards of other sizes. The default tables are the `*_TABLE` constants, princesses using `QUEEN_TABLE`.
+**/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceSquareTables {
+    pub pawn: [i32; 64],
+    pub knight: [i32; 64],
+    pub bishop: [i32; 64],
+    pub rook: [i32; 64],
+    pub queen: [i32; 64],
+    pub king: [i32; 64],
+    pub princess: [i32; 64],
+    pub unicorn: [i32; 64],
+    pub dragon: [i32; 64],
+}
+
+impl Default for PieceSquareTables {
+    fn default() -> Self {
+        PieceSquareTables {
+            pawn: PAWN_TABLE,
+            knight: KNIGHT_TABLE,
+            bishop: BISHOP_TABLE,
+            rook: ROOK_TABLE,
+            queen: QUEEN_TABLE,
+            king: KING_TABLE,
+            princess: QUEEN_TABLE,
+            unicorn: FLAT_TABLE,
+            dragon: FLAT_TABLE,
+        }
+    }
+}
+
+impl PieceSquareTables {
+    /// Returns the table used for `piece`, whatever its color; returns None for blank squares
+    pub fn table(&self, piece: Piece) -> Option<&[i32; 64]> {
+        match piece {
+            Piece::Blank => None,
+            Piece::PawnW | Piece::PawnB => Some(&self.pawn),
+            Piece::KnightW | Piece::KnightB => Some(&self.knight),
+            Piece::BishopW | Piece::BishopB => Some(&self.bishop),
+            Piece::RookW | Piece::RookB => Some(&self.rook),
+            Piece::QueenW | Piece::QueenB => Some(&self.queen),
+            Piece::KingW | Piece::KingB => Some(&self.king),
+            Piece::PrincessW | Piece::PrincessB => Some(&self.princess),
+            Piece::UnicornW | Piece::UnicornB => Some(&self.unicorn),
+            Piece::DragonW | Piece::DragonB => Some(&self.dragon),
+        }
+    }
+
+    /**
+        Returns the bonus of `piece` standing on `(x, y)` of a `width`x`height` board, positive for white pieces and negative for black pieces.
+        Each coordinate is mapped to the table cell that covers the center of the square, so that the tables stay symmetrical on boards of any size.
+    **/
+    pub fn value(&self, piece: Piece, x: u8, y: u8, width: u8, height: u8) -> i32 {
+        let table = match self.table(piece) {
+            Some(table) => table,
+            None => return 0,
+        };
+        let rank = if piece.is_white() { y } else { height - 1 - y };
+        let tx = (2 * x as usize + 1) * 8 / (2 * width as usize);
+        let ty = (2 * rank as usize + 1) * 8 / (2 * height as usize);
+        let value = table[tx + 8 * ty];
+        if piece.is_white() {
+            value
+        } else {
+            -value
+        }
+    }
+}
+
+/// Returns the sum of `Board::pst` over the last board of every timeline; positive values favour white
+pub fn pst_balance(game: &Game, virtual_boards: &Vec<&Board>, tables: &PieceSquareTables) -> i32 {
+    game.timelines
+        .values()
+        .map(|tl| &tl.states[tl.states.len() - 1])
+        .chain(virtual_boards.iter().copied())
+        .filter(|board| is_last(game, virtual_boards, board))
+        .map(|board| board.pst(tables))
+        .sum()
+}
+
+/**
+    A position evaluation made of weighted subscores, all from white's point of view: `material_balance`, `mobility_balance`, `king_safety_balance`,
+    `pst_balance` (using `tables`) and `GameInfo::strategic_timeline_balance`. Setting a weight to `0.0` removes that subscore from the evaluation.
+
+    The default weights give scores in hundredths of the `*_VALUE` constants: a pawn is worth `90`, each move counted by `mobility_balance` `2`
+    and each timeline of `strategic_timeline_balance` `BRANCH_VALUE`, as in `score_moveset`. King safety is scaled down to a quarter, so that a single open square next to a king
+    (`KING_PROTECTION_VALUE + KING_PROTECTION_VALUE_2`) is worth about a pawn rather than outweighing the material.
+    The piece-square tables are already written in those hundredths and are taken as-is.
+**/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Evaluator {
+    pub material_w: f32,
+    pub mobility_w: f32,
+    pub king_safety_w: f32,
+    pub pst_w: f32,
+    pub timeline_w: f32,
+    pub tables: PieceSquareTables,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator {
+            material_w: 100.0,
+            mobility_w: 2.0,
+            king_safety_w: 25.0,
+            pst_w: 1.0,
+            timeline_w: 100.0 * BRANCH_VALUE,
+            tables: PieceSquareTables::default(),
+        }
+    }
+}
+
+impl Evaluator {
+    /// Evaluates the position made of `game`, `virtual_boards` and `info`, rounding the weighted sum of the subscores; positive values favour white
+    pub fn evaluate(&self, game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> i32 {
+        let mut score: f32 = 0.0;
+        if self.material_w != 0.0 {
+            score += self.material_w * material_balance(game, virtual_boards);
+        }
+        if self.mobility_w != 0.0 {
+            score += self.mobility_w * mobility_balance(game, virtual_boards) as f32;
+        }
+        if self.king_safety_w != 0.0 {
+            score += self.king_safety_w * king_safety_balance(game, virtual_boards);
+        }
+        if self.pst_w != 0.0 {
+            score += self.pst_w * pst_balance(game, virtual_boards, &self.tables) as f32;
+        }
+        if self.timeline_w != 0.0 {
+            score += self.timeline_w * info.strategic_timeline_balance() as f32;
+        }
+        score.round() as i32
     }
 }
 
@@ -536,7 +942,7 @@ fn set_controlled_square_slide(board: &Board, controlled_squares: &mut Vec<bool>
         && ((index / width as usize) as isize) + length * dy < height as isize
     {
         let n_index = ((index as isize) + length * (dx + (width as isize) * dy)) as usize;
-        if board.pieces[n_index].is_takable_piece(white) {
+        if board.pieces.get(n_index).is_some_and(|piece| piece.is_takable_piece(white)) {
             controlled_squares[n_index] = true;
         } else {
             break;
@@ -544,3 +950,126 @@ fn set_controlled_square_slide(board: &Board, controlled_squares: &mut Vec<bool>
         length += 1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Returns the standard starting position, without the piece on `(x, y)`
+    fn standard_without(x: u8, y: u8) -> Game {
+        let mut game = crate::variant::standard();
+        game.timelines.get_mut(&0).unwrap().states[0].set(x, y, Piece::Blank).unwrap();
+        game
+    }
+
+    #[test]
+    fn material_balance_and_delta() {
+        let start = crate::variant::standard();
+        assert_eq!(material_balance(&start, &vec![]), 0.0);
+        // Without black's queen
+        let game = standard_without(3, 7);
+        assert!((material_balance(&game, &vec![]) - QUEEN_VALUE).abs() < 1e-3);
+
+        // Taking the rook on b2, then promoting the pawn on a3 of a 4x4 board
+        for ranks in &[["..k.", "....", ".r..", "Q..K"], ["....", "P..k", "....", "K..."]] {
+            let game = testing::game(0, ranks);
+            let virtual_boards = Vec::new();
+            let before = material_balance(&game, &virtual_boards);
+            let mut deltas = Vec::new();
+            for (moves, boards, _, _) in legal_movesets(&game, &game.info, &virtual_boards, 0, 0) {
+                let merged_vboards: Vec<&Board> = boards.iter().collect();
+                let delta = material_delta(&game, &moves);
+                assert!((material_balance(&game, &merged_vboards) - before - delta).abs() < 1e-3, "{:?}", moves);
+                deltas.push(delta);
+            }
+            assert!(deltas.iter().any(|delta| *delta > 0.0));
+        }
+        let game = testing::game(0, &["..k.", "....", ".r..", "Q..K"]);
+        let capture = probable_moves(&game, game.get_board(0, 0).unwrap(), &vec![])
+            .into_iter()
+            .find(|mv| mv.dst == (0, 0, 1, 1))
+            .unwrap();
+        assert_eq!(material_delta(&game, &[capture]), ROOK_VALUE);
+    }
+
+    #[test]
+    fn mobility_and_king_safety_balances() {
+        let start = crate::variant::standard();
+        assert_eq!(mobility_balance(&start, &vec![]), 0);
+        assert_eq!(king_safety_balance(&start, &vec![]), 0.0);
+
+        // Without the pawn on e2, the king, the queen and the bishop of f1 can move, and e2 and e3 are open in front of white's king
+        let game = standard_without(4, 1);
+        assert!(mobility_balance(&game, &vec![]) > 0);
+        assert_eq!(king_safety_balance(&game, &vec![]), -(KING_PROTECTION_VALUE + KING_PROTECTION_VALUE_2));
+
+        // Black's king is sheltered by its pieces, white's is not
+        let game = testing::game(0, &["kr..", "pp..", "....", "K..."]);
+        assert_eq!(king_safety_balance(&game, &vec![]), -3.0 * (KING_PROTECTION_VALUE + KING_PROTECTION_VALUE_2));
+        let mirrored = testing::game(0, &["k...", "....", "....", "K..."]);
+        assert_eq!(king_safety_balance(&mirrored, &vec![]), 0.0);
+    }
+
+    #[test]
+    fn evaluator_weights() {
+        let start = crate::variant::standard();
+        assert_eq!(Evaluator::default().evaluate(&start, &vec![], &start.info), 0);
+
+        let only = |material_w, mobility_w, king_safety_w| Evaluator {
+            material_w,
+            mobility_w,
+            king_safety_w,
+            pst_w: 0.0,
+            timeline_w: 0.0,
+            ..Evaluator::default()
+        };
+        let game = standard_without(4, 1);
+        let virtual_boards = Vec::new();
+        let (material, mobility, king_safety) = (
+            material_balance(&game, &virtual_boards),
+            mobility_balance(&game, &virtual_boards),
+            king_safety_balance(&game, &virtual_boards),
+        );
+        let evaluate = |eval: Evaluator| eval.evaluate(&game, &virtual_boards, &game.info);
+        assert_eq!(evaluate(only(100.0, 0.0, 0.0)), (100.0 * material).round() as i32);
+        assert_eq!(evaluate(only(0.0, 2.0, 0.0)), 2 * mobility);
+        assert_eq!(evaluate(only(0.0, 0.0, 25.0)), (25.0 * king_safety).round() as i32);
+        assert_eq!(evaluate(only(100.0, 2.0, 25.0)), (100.0 * material + 2.0 * mobility as f32 + 25.0 * king_safety).round() as i32);
+        assert_eq!(evaluate(only(0.0, 0.0, 0.0)), 0);
+    }
+
+    #[test]
+    fn pst_balance_of_pawn_moves() {
+        let tables = PieceSquareTables::default();
+        let mut game = crate::variant::standard();
+        assert_eq!(pst_balance(&game, &vec![], &tables), 0);
+        // The tables are mirrored for black, and stretched over a 4x4 board
+        let small = testing::game(0, &["k..n", "pp..", "PP..", "K..N"]);
+        assert_eq!(pst_balance(&small, &vec![], &tables), 0);
+
+        // 1. e4 moves a pawn from a square worth -20 to one worth 20
+        testing::play(&mut game, &[((0, 0, 4, 1), (0, 0, 4, 3))]);
+        assert_eq!(pst_balance(&game, &vec![], &tables), PAWN_TABLE[4 + 8 * 3] - PAWN_TABLE[4 + 8]);
+        assert_eq!(pst_balance(&game, &vec![], &tables), 40);
+        let flat = PieceSquareTables { pawn: FLAT_TABLE, ..tables };
+        assert_eq!(pst_balance(&game, &vec![], &flat), 0);
+
+        // Black answering 1. ... e5 evens it out
+        testing::play(&mut game, &[((0, 1, 4, 6), (0, 1, 4, 4))]);
+        assert_eq!(pst_balance(&game, &vec![], &tables), 0);
+
+        let only_pst = Evaluator {
+            material_w: 0.0,
+            mobility_w: 0.0,
+            king_safety_w: 0.0,
+            timeline_w: 0.0,
+            pst_w: 0.5,
+            ..Evaluator::default()
+        };
+        let mut game = crate::variant::standard();
+        testing::play(&mut game, &[((0, 0, 4, 1), (0, 0, 4, 3))]);
+        assert_eq!(only_pst.evaluate(&game, &vec![], &game.info), 20);
+        assert_eq!(Evaluator { tables: flat, ..only_pst }.evaluate(&game, &vec![], &game.info), 0);
+    }
+}
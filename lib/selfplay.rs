@@ -0,0 +1,277 @@
+// Plays evaluators against each other, to compare them when tuning their weights
+
+use crate::{driver::*, game::*, hash::*, moves::*, moveset::*, random::*, resolve::*, tree::PathHistory};
+
+/// The score given to a checkmated position, from the point of view of the checkmated player; it outweighs any evaluation
+pub const MATE_SCORE: i32 = -1_000_000;
+
+/// How the engines of `play_match` and `play_game` search and how long games may last
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchLimits {
+    /// How many turns (plies) ahead each engine looks; `1` plays the moveset whose resulting position evaluates best
+    pub depth: usize,
+    /// The maximum number of movesets considered per searched position, as yielded by `legal_movesets`; 0 for ∞ (not recommended!)
+    pub max_bf: usize,
+    /// The number of turns (plies) after which an unfinished game is scored as a draw
+    pub max_turns: usize,
+    /// The number of turns (plies) that `play_match` plays at random from the starting position before the engines take over, so that its games differ
+    pub random_turns: usize,
+    /// The seed of the random turns of `play_match`; two matches with the same seed and limits play the same games
+    pub seed: u64,
+}
+
+impl Default for MatchLimits {
+    fn default() -> Self {
+        MatchLimits {
+            depth: 1,
+            max_bf: 32,
+            max_turns: 40,
+            random_turns: 2,
+            seed: 0,
+        }
+    }
+}
+
+/// The games won, lost and drawn by the first evaluator of `play_match`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MatchResult {
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+impl MatchResult {
+    /// Returns the number of games played
+    pub fn games(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+
+    /// Returns the points scored by the first evaluator, counting a win as `1` and a draw as `0.5`
+    pub fn points(&self) -> f32 {
+        self.wins as f32 + 0.5 * self.draws as f32
+    }
+}
+
+/**
+    Plays `n_games` games between `eval_a` and `eval_b`, `eval_a` playing white in the even-numbered games and black in the odd-numbered ones; see `play_game`.
+    The games are played in pairs, both games of a pair starting from the same opening: `limits.random_turns` random turns played from the standard starting position (see `random_opening`).
+    The result is given from `eval_a`'s point of view.
+**/
+pub fn play_match(eval_a: &Evaluator, eval_b: &Evaluator, n_games: usize, limits: &MatchLimits) -> MatchResult {
+    let mut res = MatchResult::default();
+    let mut rng = SplitMix64::new(limits.seed);
+    let mut opening = Game::standard_with_turn(true);
+    for n in 0..n_games {
+        let a_is_white = n % 2 == 0;
+        if a_is_white {
+            opening = random_opening(limits, &mut rng);
+        }
+        let (white, black) = if a_is_white { (eval_a, eval_b) } else { (eval_b, eval_a) };
+        let (driver, winner) = play_game(opening.clone(), white, black, limits);
+        match winner {
+            Some(w) if w == a_is_white => res.wins += 1,
+            Some(_) => res.losses += 1,
+            None => res.draws += 1,
+        }
+        info!("Game {}: {:?} after {} turns ({:?})", n + 1, winner, driver.turns_played(), res);
+    }
+    res
+}
+
+/**
+    Returns the standard starting position after `limits.random_turns` turns, each picked uniformly among the legal movesets found within the first `limits.max_bf` movesets considered
+    (see `random_legal_moveset`); the opening stops early if none of them is legal.
+**/
+fn random_opening<R: Rng>(limits: &MatchLimits, rng: &mut R) -> Game {
+    let mut game = Game::standard_with_turn(true);
+    let virtual_boards: Vec<&Board> = Vec::new();
+    for _ in 0..limits.random_turns {
+        let moveset = match random_legal_moveset(&game, &game.info, &virtual_boards, 0, limits.max_bf, 0.0, rng) {
+            Some((moves, _, _, _)) => Moveset(moves),
+            None => break,
+        };
+        game.advance_turn(&moveset).unwrap();
+    }
+    game
+}
+
+/**
+    Plays a game from `game`, `white` and `black` choosing their turns with a search of `limits.depth` turns (see `best_moveset`), until a player is checkmated or stalemated
+    or until `limits.max_turns` turns were played. Returns the driver holding the game, to look at the turns played, along with the winner (true for white), None for a draw.
+**/
+pub fn play_game(game: Game, white: &Evaluator, black: &Evaluator, limits: &MatchLimits) -> (GameDriver, Option<bool>) {
+    let mut driver = GameDriver::new(game);
+    let mut state = driver.state();
+    while state == GameState::Ongoing && driver.turns_played() < limits.max_turns {
+        let eval = if driver.active_player() { white } else { black };
+        let moveset = match best_moveset(driver.game(), eval, limits) {
+            Some(moveset) => moveset,
+            None => break,
+        };
+        state = match driver.submit(&moveset) {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("Engine played an illegal turn {:?}: {:?}", moveset, e);
+                break;
+            }
+        };
+    }
+    let winner = if state == GameState::Checkmate {
+        Some(!driver.active_player())
+    } else {
+        None
+    };
+    (driver, winner)
+}
+
+/// Returns the moveset that `eval` prefers for the active player of `game`, searching `limits.depth` turns ahead; returns None if there is no legal moveset
+pub fn best_moveset(game: &Game, eval: &Evaluator, limits: &MatchLimits) -> Option<Moveset> {
+    let virtual_boards: Vec<&Board> = Vec::new();
+    let scheme = Zobrist::default();
+    let key = scheme.key(game, &virtual_boards, &game.info);
+    let mut history = PathHistory::new();
+    history.push(key);
+    let mut best: Option<(Vec<Move>, i32)> = None;
+    for (moves, boards, info, _score) in legal_movesets(game, &game.info, &virtual_boards, 0, 0).take(bf(limits)) {
+        let child_vboards: Vec<&Board> = boards.iter().collect();
+        history.push(scheme.update_key(key, &boards, &game.info, &info));
+        let value = -negamax(game, &child_vboards, &info, eval, limits, limits.depth.max(1) - 1, &mut history);
+        history.pop();
+        if best.as_ref().is_none_or(|(_, best_value)| value > *best_value) {
+            best = Some((moves, value));
+        }
+    }
+    best.map(|(moves, _)| Moveset(moves))
+}
+
+/**
+    Returns the value of the position made of `game`, `virtual_boards` and `info` after a search of `depth` turns, from the point of view of its active player.
+    A player left without legal moveset before the last turn searched scores `MATE_SCORE` if they are in check, `0` otherwise.
+
+    `history` holds the Zobrist keys of the line leading to the position, the position's own key last (see `PathHistory`); a moveset coming back to a position of that line scores `0`, as a draw.
+**/
+pub(crate) fn negamax(
+    game: &Game,
+    virtual_boards: &Vec<&Board>,
+    info: &GameInfo,
+    eval: &Evaluator,
+    limits: &MatchLimits,
+    depth: usize,
+    history: &mut PathHistory,
+) -> i32 {
+    if depth == 0 {
+        let value = eval.evaluate(game, virtual_boards, info);
+        return if info.active_player { value } else { -value };
+    }
+    let scheme = Zobrist::default();
+    let key = match history.last() {
+        Some(key) => key,
+        None => scheme.key(game, virtual_boards, info),
+    };
+    let mut best: Option<i32> = None;
+    for (_moves, boards, new_info, _score) in legal_movesets(game, info, virtual_boards, 0, 0).take(bf(limits)) {
+        let new_key = scheme.update_key(key, &boards, info, &new_info);
+        let value = if history.contains(new_key) {
+            0
+        } else {
+            let merged_vboards: Vec<&Board> = virtual_boards.iter().copied().chain(boards.iter()).collect();
+            history.push(new_key);
+            let value = -negamax(game, &merged_vboards, &new_info, eval, limits, depth - 1, history);
+            history.pop();
+            value
+        };
+        best = Some(best.map_or(value, |best| best.max(value)));
+    }
+    match best {
+        Some(value) => value,
+        None if is_draw(game, virtual_boards, info) => 0,
+        None => MATE_SCORE,
+    }
+}
+
+#[inline]
+fn bf(limits: &MatchLimits) -> usize {
+    if limits.max_bf == 0 {
+        usize::MAX
+    } else {
+        limits.max_bf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn repetition_scores_as_draw() {
+        // White's queen can take the rook on b2
+        let game = testing::game(0, &["..k.", "....", ".r..", "Q..K"]);
+        let eval = Evaluator {
+            mobility_w: 0.0,
+            king_safety_w: 0.0,
+            pst_w: 0.0,
+            timeline_w: 0.0,
+            ..Evaluator::default()
+        };
+        let limits = MatchLimits::default();
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let scheme = Zobrist::default();
+        let key = scheme.key(&game, &virtual_boards, &game.info);
+
+        let mut history = PathHistory::new();
+        history.push(key);
+        assert!(negamax(&game, &virtual_boards, &game.info, &eval, &limits, 1, &mut history) > 0);
+        assert_eq!(history.len(), 1);
+
+        // Once every position that white can reach is part of the line, each moveset repeats it
+        let mut history = PathHistory::new();
+        for (_moves, boards, new_info, _score) in legal_movesets(&game, &game.info, &virtual_boards, 0, 0) {
+            assert!(!new_info.active_player);
+            let child_key = scheme.update_key(key, &boards, &game.info, &new_info);
+            let child_vboards: Vec<&Board> = boards.iter().collect();
+            assert_eq!(child_key, scheme.key(&game, &child_vboards, &new_info));
+            history.push(child_key);
+        }
+        history.push(key);
+        assert_eq!(negamax(&game, &virtual_boards, &game.info, &eval, &limits, 1, &mut history), 0);
+    }
+
+    #[test]
+    fn short_match_between_trivial_evaluators() {
+        let eval = Evaluator {
+            mobility_w: 0.0,
+            king_safety_w: 0.0,
+            pst_w: 0.0,
+            timeline_w: 0.0,
+            ..Evaluator::default()
+        };
+        let limits = MatchLimits {
+            depth: 1,
+            max_bf: 8,
+            max_turns: 4,
+            random_turns: 2,
+            seed: 7,
+        };
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let opening_keys = |seed| {
+            let mut rng = SplitMix64::new(seed);
+            let limits = MatchLimits { seed, ..limits };
+            [random_opening(&limits, &mut rng), random_opening(&limits, &mut rng)].map(|game| {
+                assert_eq!(game.info.present, limits.random_turns as isize);
+                zobrist_key(&game, &virtual_boards, &game.info)
+            })
+        };
+
+        // The openings only depend on the seed, and a match goes through several of them
+        let keys = opening_keys(7);
+        assert_eq!(keys, opening_keys(7));
+        assert_ne!(keys[0], keys[1]);
+
+        let (driver, _winner) = play_game(random_opening(&limits, &mut SplitMix64::new(7)), &eval, &eval, &limits);
+        assert!(driver.turns_played() <= limits.max_turns);
+        let res = play_match(&eval, &eval, 2, &limits);
+        assert_eq!(res.games(), 2);
+        assert_eq!(res, play_match(&eval, &eval, 2, &limits));
+    }
+}
@@ -0,0 +1,83 @@
+// Helpers to set up positions in the unit tests
+
+use crate::game::*;
+use crate::moveset::{Moveset, TurnAssembler};
+
+const PIECES: [Piece; 19] = [
+    Piece::Blank,
+    Piece::KingW,
+    Piece::QueenW,
+    Piece::PawnW,
+    Piece::KnightW,
+    Piece::RookW,
+    Piece::BishopW,
+    Piece::UnicornW,
+    Piece::DragonW,
+    Piece::PrincessW,
+    Piece::KingB,
+    Piece::QueenB,
+    Piece::PawnB,
+    Piece::KnightB,
+    Piece::RookB,
+    Piece::BishopB,
+    Piece::UnicornB,
+    Piece::DragonB,
+    Piece::PrincessB,
+];
+
+/**
+    Builds the board `(l, t)` out of its ranks, written as in a diagram: the last rank (black's back rank) comes first, with one `Piece::to_char` letter per square.
+    The king's starting squares are set to the squares of the kings, without castling rights.
+**/
+pub fn board(l: i32, t: isize, ranks: &[&str]) -> Board {
+    let height = ranks.len() as u8;
+    let width = ranks[0].len() as u8;
+    let mut res = Board::new(t, l, width, height);
+    for (n, rank) in ranks.iter().enumerate() {
+        let y = height - 1 - n as u8;
+        for (x, c) in rank.chars().enumerate() {
+            let piece = *PIECES.iter().find(|p| p.to_char() == c).unwrap_or_else(|| panic!("Invalid piece letter: {}", c));
+            res.set(x as u8, y, piece).unwrap();
+            match piece {
+                Piece::KingW => res.king_w = Some((x as u8, y)),
+                Piece::KingB => res.king_b = Some((x as u8, y)),
+                _ => {}
+            }
+        }
+    }
+    res
+}
+
+/// Builds a game made of a single board at `(0, t)` (see `board`); it is white's turn if `t` is even
+pub fn game(t: isize, ranks: &[&str]) -> Game {
+    let board = board(0, t, ranks);
+    GameBuilder::new(board.width, board.height)
+        .add_board(0, t, board)
+        .build()
+        .unwrap()
+}
+
+/// Returns the path of `tests/games/<name>`
+pub fn fixture_path(name: &str) -> String {
+    format!("{}/tests/games/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// Loads the game in `tests/games/<name>` (see `parse::try_read_and_parse`)
+pub fn fixture(name: &str) -> Game {
+    let path = fixture_path(name);
+    crate::parse::try_read_and_parse(&path).unwrap_or_else(|e| panic!("Couldn't load {}: {:?}", path, e))
+}
+
+/// Plays the turn made of the moves going from and to the given coordinates (see `TurnAssembler::try_push_coords`) and returns it
+pub fn play(game: &mut Game, moves: &[(Coords, Coords)]) -> Moveset {
+    let virtual_boards = Vec::new();
+    let mut assembler = TurnAssembler::new(game, &virtual_boards, &game.info);
+    for (src, dst) in moves {
+        assembler
+            .try_push_coords(*src, *dst)
+            .unwrap_or_else(|| panic!("Invalid move: {:?} to {:?}", src, dst));
+    }
+    let moveset = Moveset(assembler.moves);
+    game.advance_turn(&moveset).unwrap();
+    moveset
+}
@@ -1,6 +1,10 @@
-use crate::{game::*, moves::*};
+use crate::{book::*, game::*, hash::*, moves::*, resolve::*};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cell::RefCell;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
@@ -916,6 +920,848 @@ pub mod iddfs {
     }
 }
 
+/**
+    The positions met along the line being searched, so that a line coming back to one of its positions is scored as a draw instead of being searched again; used by `selfplay::negamax`.
+    Positions are compared by their full Zobrist key (see `hash::zobrist_key`), which is updated along the line with `Zobrist::update_key`.
+    Unlike threefold repetition, only the current line is looked at and a single repetition is enough.
+**/
+#[derive(Debug, Clone, Default)]
+pub struct PathHistory {
+    keys: Vec<ZobristKey>,
+}
+
+impl PathHistory {
+    /// Creates a new, empty history
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the position whose key is `key` was already met along the line
+    pub fn contains(&self, key: ZobristKey) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// Appends a position to the line, when descending into it
+    pub fn push(&mut self, key: ZobristKey) {
+        self.keys.push(key);
+    }
+
+    /// Returns the key of the last position of the line
+    pub fn last(&self) -> Option<ZobristKey> {
+        self.keys.last().copied()
+    }
+
+    /// Removes the last position of the line, when going back up from it
+    pub fn pop(&mut self) -> Option<ZobristKey> {
+        self.keys.pop()
+    }
+
+    /// Returns the number of positions in the line
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns whether the line is empty
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// What kind of bound the value of a `TranspositionTable` entry is
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/**
+    A transposition table, mapping a position's Zobrist key and its active player to the depth it was searched to, its value and what kind of bound that value is.
+    It can be saved to and loaded from a JSON file, to resume a search later on (see `lazy_smp::SearchState`); the keys are only meaningful for the hashing scheme that they were computed with.
+**/
+#[derive(Debug, Clone, Default)]
+pub struct TranspositionTable {
+    entries: HashMap<(ZobristKey, bool), (usize, f32, Bound)>,
+}
+
+impl TranspositionTable {
+    /// Creates a new, empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the depth, value and bound stored for the position whose key is `key`, with `white` to play
+    pub fn get(&self, key: ZobristKey, white: bool) -> Option<(usize, f32, Bound)> {
+        self.entries.get(&(key, white)).copied()
+    }
+
+    /// Stores the result of searching the position whose key is `key`, with `white` to play, replacing any previous entry
+    pub fn insert(&mut self, key: ZobristKey, white: bool, depth: usize, value: f32, bound: Bound) {
+        self.entries.insert((key, white), (depth, value, bound));
+    }
+
+    /// Returns the number of positions stored
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no position is stored
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Reads a table from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        load_json(path)
+    }
+
+    /// Writes the table to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        save_json(self, path)
+    }
+}
+
+/// A `TranspositionTable` entry, as stored in JSON files
+#[derive(Serialize, Deserialize)]
+struct TableEntry {
+    key: ZobristKey,
+    white: bool,
+    depth: usize,
+    #[serde(with = "score")]
+    value: f32,
+    bound: Bound,
+}
+
+impl Serialize for TranspositionTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.entries.iter().map(|(&(key, white), &(depth, value, bound))| TableEntry {
+            key,
+            white,
+            depth,
+            value,
+            bound,
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for TranspositionTable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = Vec::<TableEntry>::deserialize(deserializer)?;
+        Ok(TranspositionTable {
+            entries: entries
+                .into_iter()
+                .map(|e| ((e.key, e.white), (e.depth, e.value, e.bound)))
+                .collect(),
+        })
+    }
+}
+
+/// (De)serializes scores, which JSON cannot represent when they are infinite (ie. for checkmates): these are written as the strings `"inf"` and `"-inf"`
+mod score {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f32(*value)
+        } else if *value > 0.0 {
+            serializer.serialize_str("inf")
+        } else {
+            serializer.serialize_str("-inf")
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Finite(f32),
+            Infinite(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Finite(value) => Ok(value),
+            Raw::Infinite(s) if s == "inf" => Ok(f32::INFINITY),
+            Raw::Infinite(s) if s == "-inf" => Ok(f32::NEG_INFINITY),
+            Raw::Infinite(s) => Err(serde::de::Error::custom(format!("invalid score: {}", s))),
+        }
+    }
+}
+
+fn load_json<T: for<'de> Deserialize<'de>, P: AsRef<Path>>(path: P) -> std::io::Result<T> {
+    let mut file = File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    serde_json::from_str(&contents)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn save_json<T: Serialize, P: AsRef<Path>>(value: &T, path: P) -> std::io::Result<()> {
+    let contents = serde_json::to_string(value)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    File::create(path)?.write_all(contents.as_bytes())
+}
+
+pub mod lazy_smp {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Transposition table shared by the threads
+    type Table = Mutex<TranspositionTable>;
+
+    /**
+        What a search started by `parallel_search_resumable` found so far, to continue it later on, possibly after saving it to a file:
+        the transposition table, the depth of the deepest completed iteration and the moveset that it found.
+        A state only applies to the position it was created for, whose Zobrist key is `root_key`.
+    **/
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct SearchState {
+        pub root_key: ZobristKey,
+        pub table: TranspositionTable,
+        pub depth: usize,
+        pub best_moveset: Option<Vec<Move>>,
+        #[serde(with = "score")]
+        pub best_value: f32,
+    }
+
+    impl SearchState {
+        /// Creates the state of a search of `game`'s current position that has not started yet
+        pub fn new(game: &Game) -> Self {
+            SearchState {
+                root_key: zobrist_key(game, &vec![], &game.info),
+                table: TranspositionTable::new(),
+                depth: 0,
+                best_moveset: None,
+                best_value: 0.0,
+            }
+        }
+
+        /// Reads a state from a JSON file
+        pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+            load_json(path)
+        }
+
+        /// Writes the state to a JSON file
+        pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+            save_json(self, path)
+        }
+    }
+
+    /**
+        Lazy SMP-style parallel search.
+
+        `n_threads` threads each run an iterative deepening, αβ-pruned search from the root, sharing what they find through a transposition table.
+        Even threads start at depth 1 and odd threads at depth 2, and each thread visits the root movesets in a different order, so that they do not all search the same nodes in lockstep.
+        The moveset of the deepest completed iteration is returned; should no iteration complete within `max_duration`, the first root moveset is returned instead.
+
+        - `game` is the game instance to look moves on
+        - `max_ms` is the maximum number of movesets to consider until the position is deemed to be draw or checkmate
+        - `bucket_size` correspond to the number of movesets to score and sort; ignored if `<= max_bf`
+        - `max_bf` corresponds to the maximum number of movesets (branching factor) to consider per tree node
+        - `n_threads` is the number of search threads
+        - `max_duration` is the maximum duration that this algorithm may take; the threads stop as soon as possible once it is reached
+    **/
+    pub fn parallel_search(
+        game: &Game,
+        max_ms: usize,
+        bucket_size: usize,
+        max_bf: usize,
+        n_threads: u32,
+        max_duration: Duration,
+    ) -> Option<(Node, f32)> {
+        let mut state = SearchState::new(game);
+        parallel_search_resumable(game, max_ms, bucket_size, max_bf, n_threads, max_duration, &mut state)
+    }
+
+    /**
+        Same as `parallel_search`, but continues the search described by `state` (its iterative deepening resumes after `state.depth`, using its transposition table) and updates it with what was found.
+        If `state` was created for another position, it is replaced by a new state first.
+    **/
+    pub fn parallel_search_resumable(
+        game: &Game,
+        max_ms: usize,
+        bucket_size: usize,
+        max_bf: usize,
+        n_threads: u32,
+        max_duration: Duration,
+        state: &mut SearchState,
+    ) -> Option<(Node, f32)> {
+        let begin = Instant::now();
+        let virtual_boards: Vec<&Board> = Vec::new();
+        let white = game.info.active_player;
+        let root: Vec<Node> = opt_apply_bucket(
+            bucket_size,
+            max_bf,
+            white,
+            legal_movesets(game, &game.info, &virtual_boards, 0, max_ms),
+        )
+        .collect();
+        let root_key = zobrist_key(game, &virtual_boards, &game.info);
+        if state.root_key != root_key {
+            warn!("The search state is for another position, starting over");
+            *state = SearchState::new(game);
+        }
+
+        // The moveset found by the deepest iteration of a previous search, if it was kept
+        let previous = state
+            .best_moveset
+            .as_ref()
+            .and_then(|moveset| root.iter().find(|node| &node.0 == moveset))
+            .map(|node| (state.depth, node.clone(), state.best_value));
+        let fallback = root.first()?.clone();
+        let fallback_score = fallback.3;
+
+        // (depth, node, value) of the deepest completed iteration
+        let res_data = Arc::new(Mutex::new(previous.unwrap_or((0, fallback, fallback_score))));
+        let start_depth = res_data.lock().unwrap().0;
+        let table: Table = Mutex::new(std::mem::take(&mut state.table));
+        let stop = AtomicBool::new(false);
+        let search = Search {
+            game,
+            max_ms,
+            bucket_size,
+            max_bf,
+            table: &table,
+            stop: &stop,
+            begin,
+            max_duration,
+        };
+
+        let mut pool = Pool::new(n_threads);
+        pool.scoped(|scope| {
+            for id in 0..(n_threads as usize) {
+                let res_data = Arc::clone(&res_data);
+                let root = &root;
+                let search = &search;
+
+                scope.execute(move || {
+                    let mut depth = start_depth + 1 + id % 2;
+                    loop {
+                        let mut alpha = f32::NEG_INFINITY;
+                        let mut beta = f32::INFINITY;
+                        let mut best: Option<(&Node, f32)> = None;
+
+                        for node in root.iter().cycle().skip(id % root.len()).take(root.len()) {
+                            let key = node.1.iter().fold(root_key, |key, b| key ^ board_key(b));
+                            let value = match search.search(&vec![], node, key, depth - 1, alpha, beta) {
+                                Some(value) => value,
+                                None => return,
+                            };
+                            if best.is_none_or(|(_, v)| if white { value > v } else { value < v }) {
+                                best = Some((node, value));
+                            }
+                            if white {
+                                alpha = alpha.max(value);
+                            } else {
+                                beta = beta.min(value);
+                            }
+                        }
+
+                        if let Some((node, value)) = best {
+                            let mut res_data = res_data.lock().unwrap();
+                            if depth > res_data.0 {
+                                *res_data = (depth, node.clone(), value);
+                                info!("Depth {} (thread {}): {:?} -> {}", depth, id, node.0, value);
+                            }
+                            if value.is_infinite() {
+                                search.stop.store(true, Ordering::Relaxed);
+                                return;
+                            }
+                        }
+                        depth += 1;
+                    }
+                });
+            }
+        });
+
+        let (depth, node, value) = res_data.lock().unwrap().clone();
+        state.table = table.into_inner().unwrap();
+        state.depth = depth;
+        if depth > 0 {
+            state.best_moveset = Some(node.0.clone());
+            state.best_value = value;
+        }
+        Some((node, value))
+    }
+
+    /// State shared by the different search threads
+    struct Search<'a> {
+        game: &'a Game,
+        max_ms: usize,
+        bucket_size: usize,
+        max_bf: usize,
+        table: &'a Table,
+        stop: &'a AtomicBool,
+        begin: Instant,
+        max_duration: Duration,
+    }
+
+    impl<'a> Search<'a> {
+        /// Recursive αβ-pruned search of `node`, whose Zobrist key is `key`; returns `None` once the time is up or the search was stopped
+        fn search(
+            &self,
+            virtual_boards: &Vec<&Board>,
+            node: &Node,
+            key: ZobristKey,
+            depth: usize,
+            mut alpha: f32,
+            mut beta: f32,
+        ) -> Option<f32> {
+            if self.stop.load(Ordering::Relaxed) || self.begin.elapsed() >= self.max_duration {
+                return None;
+            }
+            if depth == 0 {
+                return Some(node.3);
+            }
+
+            let white = node.2.active_player;
+            let entry = self.table.lock().unwrap().get(key, white);
+            if let Some((entry_depth, value, bound)) = entry {
+                if entry_depth >= depth {
+                    match bound {
+                        Bound::Exact => return Some(value),
+                        Bound::Lower => alpha = alpha.max(value),
+                        Bound::Upper => beta = beta.min(value),
+                    }
+                    if alpha >= beta {
+                        return Some(value);
+                    }
+                }
+            }
+            let (initial_alpha, initial_beta) = (alpha, beta);
+
+            let merged_vboards: Vec<&Board> =
+                virtual_boards.iter().copied().chain(node.1.iter()).collect();
+            let movesets = legal_movesets(self.game, &node.2, &merged_vboards, 0, self.max_ms);
+
+            let mut value = if white { f32::NEG_INFINITY } else { f32::INFINITY };
+            let mut yielded_move = false;
+            for ms in opt_apply_bucket(self.bucket_size, self.max_bf, white, movesets) {
+                yielded_move = true;
+                let child_key = ms.1.iter().fold(key, |key, b| key ^ board_key(b));
+                let n_value = self.search(&merged_vboards, &ms, child_key, depth - 1, alpha, beta)?;
+                if white {
+                    value = value.max(n_value);
+                    alpha = alpha.max(value);
+                } else {
+                    value = value.min(n_value);
+                    beta = beta.min(value);
+                }
+                if alpha >= beta {
+                    break;
+                }
+            }
+            if !yielded_move && is_draw(self.game, &merged_vboards, &node.2) {
+                value = 0.0;
+            }
+
+            let bound = if value <= initial_alpha {
+                Bound::Upper
+            } else if value >= initial_beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.table.lock().unwrap().insert(key, white, depth, value, bound);
+
+            Some(value)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::moveset::Moveset;
+
+        #[test]
+        fn short_budget_returns_legal_moveset() {
+            let game = crate::variant::standard();
+            let begin = Instant::now();
+            let ((moves, _, info, _), _) = parallel_search(&game, 64, 0, 8, 4, Duration::from_millis(200)).unwrap();
+
+            // The threads are stopped and joined soon after the budget runs out
+            assert!(begin.elapsed() < Duration::from_secs(5));
+            assert_eq!(Moveset(moves).is_legal(&game, &vec![], &game.info), Ok(()));
+            assert!(!info.active_player);
+        }
+    }
+}
+
+pub mod quiescence {
+    use super::*;
+
+    /** Quiescence search, to be called on the leaves of a search instead of trusting their score, which reduces the horizon effect.
+
+    From `node`, only the movesets taking a piece (see `capture_movesets`) are searched, until the position is quiet.
+    The player to move may instead "stand pat" and keep the value given by `eval`, except when in check: then every check evasion is searched (see `check_evasions`),
+    and having none is a checkmate (`±∞`). Values are given from white's point of view, as with `dfs::dfs`.
+
+    - `virtual_boards` are the boards that `node` is played on top of
+    - `alpha` and `beta` are the bounds of the αβ window
+    - `max_depth` is the maximum number of movesets played from `node`; once reached, `eval` is returned
+    - `max_ms` is the maximum number of movesets to consider at each position
+    - `eval` evaluates a position, such as `material_balance`
+    **/
+    #[allow(clippy::too_many_arguments)]
+    pub fn quiescence<F>(
+        game: &Game,
+        virtual_boards: &Vec<&Board>,
+        node: &Node,
+        mut alpha: f32,
+        mut beta: f32,
+        max_depth: usize,
+        max_ms: usize,
+        eval: &F,
+    ) -> f32
+    where
+        F: Fn(&Game, &Vec<&Board>, &GameInfo) -> f32,
+    {
+        let merged_vboards: Vec<&Board> =
+            virtual_boards.iter().copied().chain(node.1.iter()).collect();
+        let info = &node.2;
+        let white = info.active_player;
+        if max_depth == 0 {
+            return eval(game, &merged_vboards, info);
+        }
+
+        let in_check = !is_draw(game, &merged_vboards, info);
+        let mut value = if in_check {
+            if white { f32::NEG_INFINITY } else { f32::INFINITY }
+        } else {
+            let stand_pat = eval(game, &merged_vboards, info);
+            if white {
+                alpha = alpha.max(stand_pat);
+            } else {
+                beta = beta.min(stand_pat);
+            }
+            if alpha >= beta {
+                return stand_pat;
+            }
+            stand_pat
+        };
+
+        let movesets: Box<dyn Iterator<Item = Node>> = if in_check {
+            Box::new(check_evasions(game, info, &merged_vboards, 0, max_ms))
+        } else {
+            Box::new(capture_movesets(game, info, &merged_vboards, 0, max_ms))
+        };
+        for ms in movesets {
+            let n_value = quiescence(game, &merged_vboards, &ms, alpha, beta, max_depth - 1, max_ms, eval);
+            if white {
+                value = value.max(n_value);
+                alpha = alpha.max(value);
+            } else {
+                value = value.min(n_value);
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        value
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::testing;
+
+        #[test]
+        fn free_capture_beats_standing_pat() {
+            // White's queen can take the rook on b2, which nothing defends
+            let game = testing::game(0, &["..k.", "....", ".r..", "Q..K"]);
+            let virtual_boards: Vec<&Board> = Vec::new();
+            let root: Node = (vec![], vec![], game.info, 0.0);
+            let eval = |game: &Game, virtual_boards: &Vec<&Board>, _info: &GameInfo| material_balance(game, virtual_boards);
+            let stand_pat = material_balance(&game, &virtual_boards);
+
+            let value = quiescence(&game, &virtual_boards, &root, f32::NEG_INFINITY, f32::INFINITY, 0, 0, &eval);
+            assert_eq!(value, stand_pat);
+
+            let value = quiescence(&game, &virtual_boards, &root, f32::NEG_INFINITY, f32::INFINITY, 4, 0, &eval);
+            assert_ne!(value, stand_pat);
+            assert!((value - (stand_pat - piece_value(Piece::RookB))).abs() < 1e-3);
+        }
+    }
+}
+
+pub mod mcts {
+    use super::*;
+    use crate::moveset::MovesetIter;
+    use crate::random::Rng;
+
+    /// The exploration constant of the UCT formula used by `mcts`; results are within `[0; 1]`, for which `√2` is the usual choice
+    pub const EXPLORATION: f32 = std::f32::consts::SQRT_2;
+    /// The number of times a playout tries to make up a turn out of one random move per board that must be played on, before drawing among the legal movesets instead
+    const QUICK_TURN_ATTEMPTS: usize = 4;
+    /// The number of legal movesets (see `legal_movesets`) that a playout draws among when its random moves did not make up a legal turn
+    const PLAYOUT_MOVESETS: usize = 8;
+    /// The maximum number of movesets that a playout considers (see `MovesetIter::max_movesets_considered`) while looking for those legal movesets
+    const PLAYOUT_MAX_CONSIDERED: usize = 1024;
+
+    /// A position of the tree, reached by playing the moveset of `node` from its parent
+    struct TreeNode {
+        node: Node,
+        /// The indices of the children in the tree, None until the position is expanded; empty if the position has no legal moveset
+        children: Option<Vec<usize>>,
+        visits: u32,
+        /// The sum of the results of the playouts through this position, from the point of view of the player who played `node`'s moveset (`1` for a win, `0.5` for a draw)
+        reward: f32,
+    }
+
+    impl TreeNode {
+        fn new(node: Node) -> Self {
+            TreeNode {
+                node,
+                children: None,
+                visits: 0,
+                reward: 0.0,
+            }
+        }
+    }
+
+    /** Monte Carlo tree search
+
+    Grows a tree of positions from the one made of `game`, `virtual_boards` and `info`, one position per iteration: the tree is walked down by picking the child with the best UCT value
+    (see `EXPLORATION`; children that were never visited are picked first), the position reached is expanded, and a random game is played from one of its children.
+    The result of that game (a win, a draw or a loss) is then added to every position on the way down.
+    Returns the moveset of the most visited child of the starting position along with its expected result, from `-1` (black wins) to `1` (white wins); None if there is no legal moveset.
+
+    - `iterations` is the number of positions added to the tree
+    - `max_bf` is the maximum number of movesets (see `legal_movesets`) considered as children of a position; 0 for ∞ (not recommended!)
+    - `max_playout` is the number of turns after which a random game that did not end is scored as a draw
+    - `rng` drives the random games; their turns are made of one random move per board that must be played on (see `Board::random_move`) when that gives a legal turn
+
+    Positions are not shared between the branches of the tree, even when movesets transpose into each other.
+    **/
+    pub fn mcts<R: Rng>(
+        game: &Game,
+        virtual_boards: &Vec<&Board>,
+        info: &GameInfo,
+        iterations: usize,
+        max_bf: usize,
+        max_playout: usize,
+        rng: &mut R,
+    ) -> Option<(Node, f32)> {
+        let mut tree: Vec<TreeNode> = vec![TreeNode::new((vec![], vec![], *info, 0.0))];
+
+        for _ in 0..iterations.max(1) {
+            // Selection
+            let mut path: Vec<usize> = vec![0];
+            while let Some(children) = tree[*path.last().unwrap()].children.as_ref().filter(|c| !c.is_empty()) {
+                let parent_visits = tree[*path.last().unwrap()].visits.max(1) as f32;
+                let uct = |index: &usize| {
+                    let child = &tree[*index];
+                    if child.visits == 0 {
+                        f32::INFINITY
+                    } else {
+                        child.reward / child.visits as f32
+                            + EXPLORATION * (parent_visits.ln() / child.visits as f32).sqrt()
+                    }
+                };
+                let best = children
+                    .iter()
+                    .copied()
+                    .max_by(|a, b| uct(a).partial_cmp(&uct(b)).unwrap())
+                    .unwrap();
+                path.push(best);
+            }
+
+            // Expansion and simulation
+            let (value, expansion) = {
+                let leaf = *path.last().unwrap();
+                let merged_vboards: Vec<&Board> = virtual_boards
+                    .iter()
+                    .copied()
+                    .chain(path.iter().flat_map(|index| tree[*index].node.1.iter()))
+                    .collect();
+                let leaf_info = tree[leaf].node.2;
+                if tree[leaf].children.is_none() {
+                    let children: Vec<Node> = legal_movesets(game, &leaf_info, &merged_vboards, 0, 0)
+                        .take(bf(max_bf))
+                        .collect();
+                    if children.is_empty() {
+                        (terminal_value(game, &merged_vboards, &leaf_info), None)
+                    } else {
+                        let picked = rng.below(children.len());
+                        let child_vboards: Vec<&Board> = merged_vboards
+                            .iter()
+                            .copied()
+                            .chain(children[picked].1.iter())
+                            .collect();
+                        let value = playout(game, &child_vboards, &children[picked].2, max_playout, rng);
+                        (value, Some((children, picked)))
+                    }
+                } else {
+                    // The leaf was expanded before and has no legal moveset
+                    (terminal_value(game, &merged_vboards, &leaf_info), None)
+                }
+            };
+            if let Some((children, picked)) = expansion {
+                let leaf = *path.last().unwrap();
+                let first = tree.len();
+                tree.extend(children.into_iter().map(TreeNode::new));
+                tree[leaf].children = Some((first..tree.len()).collect());
+                path.push(first + picked);
+            } else {
+                let leaf = *path.last().unwrap();
+                tree[leaf].children.get_or_insert_with(Vec::new);
+            }
+
+            // Backpropagation
+            for index in path {
+                let node = &mut tree[index];
+                let mover_is_white = !node.node.2.active_player;
+                node.visits += 1;
+                node.reward += if mover_is_white { (1.0 + value) / 2.0 } else { (1.0 - value) / 2.0 };
+            }
+        }
+
+        let children = tree[0].children.as_ref()?;
+        let best = children.iter().copied().max_by_key(|index| tree[*index].visits)?;
+        let mean = tree[best].reward / tree[best].visits.max(1) as f32;
+        let value = if info.active_player { 2.0 * mean - 1.0 } else { 1.0 - 2.0 * mean };
+        let node = std::mem::replace(&mut tree[best].node, (vec![], vec![], *info, 0.0));
+        Some((node, value))
+    }
+
+    /**
+        Plays random turns from the given position until a player has no legal moveset or `max_playout` turns were played, returns the result from white's point of view.
+        The game is also scored as a draw, like an unfinished one, if `random_turn` could not tell whether the active player has a legal moveset.
+    **/
+    fn playout<R: Rng>(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, max_playout: usize, rng: &mut R) -> f32 {
+        let mut boards: Vec<Board> = Vec::new();
+        let mut info = *info;
+        for _ in 0..max_playout {
+            let (_moves, mut new_boards, new_info, _score) = {
+                let merged_vboards: Vec<&Board> = virtual_boards.iter().copied().chain(boards.iter()).collect();
+                match random_turn(game, &merged_vboards, &info, rng) {
+                    RandomTurn::Played(node) => node,
+                    RandomTurn::NoMoveset => return terminal_value(game, &merged_vboards, &info),
+                    RandomTurn::Unknown => return 0.0,
+                }
+            };
+            boards.append(&mut new_boards);
+            info = new_info;
+        }
+        0.0
+    }
+
+    /// The outcome of `random_turn`
+    enum RandomTurn {
+        Played(Node),
+        /// The active player has no legal moveset
+        NoMoveset,
+        /// No legal moveset was found among the first `PLAYOUT_MAX_CONSIDERED` movesets, which does not mean that there are none
+        Unknown,
+    }
+
+    /**
+        Returns a random legal moveset of the active player: one random move is first drawn on each board that must be played on,
+        and if that does not make up a legal turn after `QUICK_TURN_ATTEMPTS` tries, the moveset is drawn among the first `PLAYOUT_MOVESETS` legal movesets.
+        These are only looked for among the first `PLAYOUT_MAX_CONSIDERED` movesets: the active player is only known to have no legal moveset if there are fewer movesets than that.
+    **/
+    fn random_turn<R: Rng>(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo, rng: &mut R) -> RandomTurn {
+        let own_boards: Vec<&Board> = get_own_boards(game, virtual_boards, info)
+            .into_iter()
+            .filter(|board| board.is_active(info))
+            .collect();
+        let opponent_boards = get_opponent_boards(game, virtual_boards, info);
+        for _ in 0..QUICK_TURN_ATTEMPTS {
+            let moves: Vec<Move> = own_boards
+                .iter()
+                .filter_map(|board| board.random_move(game, virtual_boards, rng))
+                .collect();
+            if let Some(node) = score_moveset(game, virtual_boards, info, opponent_boards.iter().copied(), moves) {
+                return RandomTurn::Played(node);
+            }
+        }
+
+        let mut iter = MovesetIter::new(game, virtual_boards, info, ranked_moves(game, info, virtual_boards, |_| true));
+        iter.max_movesets_considered = PLAYOUT_MAX_CONSIDERED;
+        let mut movesets: Vec<Node> = Vec::new();
+        while movesets.len() < PLAYOUT_MOVESETS {
+            let moveset = match iter.next() {
+                Some(moveset) => moveset,
+                None => break,
+            };
+            movesets.extend(score_moveset(game, virtual_boards, info, opponent_boards.iter().copied(), moveset));
+        }
+
+        if !movesets.is_empty() {
+            RandomTurn::Played(movesets.swap_remove(rng.below(movesets.len())))
+        } else if iter.movesets_considered > PLAYOUT_MAX_CONSIDERED {
+            RandomTurn::Unknown
+        } else {
+            RandomTurn::NoMoveset
+        }
+    }
+
+    /// The result of a position whose active player has no legal moveset, from white's point of view: a draw if they are not in check, a loss for them otherwise
+    fn terminal_value(game: &Game, virtual_boards: &Vec<&Board>, info: &GameInfo) -> f32 {
+        if is_draw(game, virtual_boards, info) {
+            0.0
+        } else if info.active_player {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+
+    #[inline]
+    fn bf(max_bf: usize) -> usize {
+        if max_bf == 0 {
+            usize::MAX
+        } else {
+            max_bf
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::moveset::Moveset;
+        use crate::random::SplitMix64;
+        use crate::testing;
+
+        /// White to move, mating with Ra8
+        fn back_rank_mate() -> Game {
+            testing::game(0, &[".......k", "......pp", "........", "........", "........", "........", "........", "R.....K."])
+        }
+
+        #[test]
+        fn mcts_prefers_mate_in_one() {
+            let game = back_rank_mate();
+            let mut rng = SplitMix64::new(1);
+            let ((moves, _, _, _), value) = mcts(&game, &vec![], &game.info, 100, 0, 4, &mut rng).unwrap();
+            let moves: Vec<Move> = moves.into_iter().filter(|mv| !mv.noop).collect();
+            assert_eq!((moves.len(), moves[0].src, moves[0].dst), (1, (0, 0, 0, 0), (0, 0, 0, 7)));
+            assert!(value > 0.5);
+        }
+
+        #[test]
+        fn random_turn_is_legal() {
+            let mut game = back_rank_mate();
+            let virtual_boards: Vec<&Board> = Vec::new();
+            let mut rng = SplitMix64::new(2);
+            for _ in 0..8 {
+                match random_turn(&game, &virtual_boards, &game.info, &mut rng) {
+                    RandomTurn::Played((moves, _, _, _)) => {
+                        assert_eq!(Moveset(moves).is_legal(&game, &virtual_boards, &game.info), Ok(()))
+                    }
+                    _ => panic!("White has legal movesets"),
+                }
+            }
+
+            // Black is left without any legal moveset once mated, which is known as there are few movesets to look at
+            testing::play(&mut game, &[((0, 0, 0, 0), (0, 0, 0, 7))]);
+            assert!(matches!(random_turn(&game, &virtual_boards, &game.info, &mut rng), RandomTurn::NoMoveset));
+            assert_eq!(terminal_value(&game, &virtual_boards, &game.info), 1.0);
+        }
+    }
+}
+
 /// Optionally applies the `bucket_size` option to the legal movesets iterator; `bucket_size` will be ignored if it is less than or equal to `max_bf`
 fn opt_apply_bucket<'a, T: Iterator<Item = Node> + 'a>(
     bucket_size: usize,
@@ -937,3 +1783,95 @@ fn opt_apply_bucket<'a, T: Iterator<Item = Node> + 'a>(
         Box::new(iter.take(max_bf))
     }
 }
+
+/**
+    Looks the current position of `game` up in `book` and returns its moveset if it is legal; runs `search` otherwise.
+
+    ```
+    let best_move = search_with_book(&book, &game, |game| iddfs::iddfs_bfs(game, 10000, 64, 1024, 16, max_duration));
+    ```
+**/
+pub fn search_with_book<B, F>(book: &B, game: &Game, search: F) -> Option<(Node, f32)>
+where
+    B: OpeningBook,
+    F: FnOnce(&Game) -> Option<(Node, f32)>,
+{
+    let virtual_boards: Vec<&Board> = Vec::new();
+    if let Some(moveset) = book.lookup(zobrist_key(game, &virtual_boards, &game.info)) {
+        let node = score_moveset(
+            game,
+            &virtual_boards,
+            &game.info,
+            get_opponent_boards(game, &virtual_boards, &game.info).into_iter(),
+            moveset,
+        );
+        if let Some(node) = node {
+            let score = node.3;
+            return Some((node, score));
+        }
+        warn!("Book moveset is illegal, searching instead");
+    }
+    search(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transposition_table_round_trip() {
+        let mut table = TranspositionTable::new();
+        table.insert(1, true, 3, 0.25, Bound::Exact);
+        table.insert(1, false, 2, f32::INFINITY, Bound::Lower);
+        table.insert(u64::MAX, true, 5, f32::NEG_INFINITY, Bound::Upper);
+
+        // JSON has no infinite numbers, so these go through the `score` module
+        let raw = serde_json::to_string(&table).unwrap();
+        assert!(raw.contains(r#""inf""#) && raw.contains(r#""-inf""#));
+
+        let path = std::env::temp_dir().join(format!("chess5dlib-table-{}.json", std::process::id()));
+        table.save(&path).unwrap();
+        let loaded = TranspositionTable::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.get(1, true), Some((3, 0.25, Bound::Exact)));
+        assert_eq!(loaded.get(1, false), Some((2, f32::INFINITY, Bound::Lower)));
+        assert_eq!(loaded.get(u64::MAX, true), Some((5, f32::NEG_INFINITY, Bound::Upper)));
+        assert_eq!(loaded.get(u64::MAX, false), None);
+
+        // Unknown strings are refused rather than read as a score
+        let raw = raw.replacen(r#""-inf""#, r#""nan""#, 1);
+        assert!(serde_json::from_str::<TranspositionTable>(&raw).is_err());
+    }
+
+    #[test]
+    fn search_with_book_falls_back_to_search() {
+        let game = crate::variant::standard();
+        let moves = probable_moves(&game, game.get_board(0, 0).unwrap(), &vec![]);
+        let knight = *moves.iter().find(|mv| mv.dst == (0, 0, 5, 2)).unwrap();
+        let pawn = *moves.iter().find(|mv| mv.dst == (0, 0, 4, 3)).unwrap();
+        let searched = |game: &Game| {
+            let node = score_moveset(game, &vec![], &game.info, std::iter::empty(), vec![pawn]).unwrap();
+            Some((node, 0.0))
+        };
+
+        // The book's moveset is played without searching
+        let mut book = FileBook::new();
+        book.insert(&game, vec![knight]);
+        let ((moves, _, info, _), _) = search_with_book(&book, &game, |_| panic!("searched a book position")).unwrap();
+        assert_eq!(moves, vec![knight]);
+        assert!(!info.active_player);
+
+        // Positions that the book does not know are searched
+        let ((moves, _, _, _), _) = search_with_book(&FileBook::new(), &game, searched).unwrap();
+        assert_eq!(moves, vec![pawn]);
+        assert!(search_with_book(&FileBook::new(), &game, |_| None).is_none());
+
+        // As are the positions whose book moveset cannot be played, such as a moveset of black's
+        let mut book = FileBook::new();
+        book.insert(&game, vec![Move { src: (0, 1, 6, 7), dst: (0, 1, 5, 5), src_piece: Piece::KnightB, ..knight }]);
+        let ((moves, _, _, _), _) = search_with_book(&book, &game, searched).unwrap();
+        assert_eq!(moves, vec![pawn]);
+    }
+}
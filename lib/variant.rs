@@ -0,0 +1,118 @@
+// Variants and their starting positions
+use crate::game::*;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref VARIANTS: RwLock<HashMap<String, Game>> = {
+        let mut variants = HashMap::new();
+        variants.insert(String::from("Standard"), standard());
+        RwLock::new(variants)
+    };
+}
+
+/**
+    Global registry of variants, mapping each variant's name to its starting position.
+    The official "Standard" variant is registered by default; other variants can be registered at runtime:
+
+    ```
+    VariantRegistry::register("My variant", game);
+    let game = Game::variant("My variant").unwrap();
+    ```
+**/
+pub struct VariantRegistry;
+
+impl VariantRegistry {
+    /// Registers `game` as the starting position of the variant `name`; returns the previously registered starting position, if any
+    pub fn register<S: Into<String>>(name: S, game: Game) -> Option<Game> {
+        VARIANTS.write().expect("Couldn't lock the variant registry").insert(name.into(), game)
+    }
+
+    /// Returns a copy of the starting position of the variant `name`, None if no such variant is registered
+    pub fn get(name: &str) -> Option<Game> {
+        VARIANTS.read().expect("Couldn't lock the variant registry").get(name).cloned()
+    }
+
+    /// Returns the names of the registered variants, in alphabetical order
+    pub fn names() -> Vec<String> {
+        let mut res = VARIANTS
+            .read()
+            .expect("Couldn't lock the variant registry")
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        res.sort();
+        res
+    }
+}
+
+/// Returns the starting position of the standard variant: a single 8x8 board, with white to move
+pub fn standard() -> Game {
+    let mut game = Game::new(8, 8);
+    let mut board = Board::new(0, 0, 8, 8);
+    let back_rank_w = [
+        Piece::RookW,
+        Piece::KnightW,
+        Piece::BishopW,
+        Piece::QueenW,
+        Piece::KingW,
+        Piece::BishopW,
+        Piece::KnightW,
+        Piece::RookW,
+    ];
+    let back_rank_b = [
+        Piece::RookB,
+        Piece::KnightB,
+        Piece::BishopB,
+        Piece::QueenB,
+        Piece::KingB,
+        Piece::BishopB,
+        Piece::KnightB,
+        Piece::RookB,
+    ];
+
+    for x in 0..8 {
+        board.set_unsafe(x, 0, back_rank_w[x as usize]);
+        board.set_unsafe(x, 1, Piece::PawnW);
+        board.set_unsafe(x, 6, Piece::PawnB);
+        board.set_unsafe(x, 7, back_rank_b[x as usize]);
+    }
+
+    let mut timeline = Timeline::new(0, 8, 8, 0, None);
+    timeline.states.push(board);
+    game.timelines.insert(0, timeline);
+    populate_castling_rights(&mut game);
+
+    game
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn register_custom_variant() {
+        // The registry is shared by every test, hence a name that no other test uses
+        let name = "Kings and rooks (variant tests)";
+        let game = testing::game(0, &["r..k", "....", "....", "R..K"]);
+        assert!(VariantRegistry::get(name).is_none());
+        assert!(VariantRegistry::register(name, game.clone()).is_none());
+
+        let registered = VariantRegistry::get(name).unwrap();
+        assert_eq!((registered.width, registered.height), (4, 4));
+        assert_eq!(registered.get_board(0, 0).unwrap().pieces, game.get_board(0, 0).unwrap().pieces);
+        assert!(Game::variant(name).is_some());
+        let names = VariantRegistry::names();
+        assert!(names.contains(&name.to_string()) && names.contains(&"Standard".to_string()));
+        assert!(names.windows(2).all(|w| w[0] <= w[1]));
+        let raw = crate::parse::to_notation_tool_json(&game, &[]);
+        assert_eq!(crate::parse::parse_metadata(&raw).unwrap().variant.as_deref(), Some(name));
+
+        // Registering the name again replaces the starting position
+        let other = testing::game(0, &["...k", "....", "....", "Q..K"]);
+        let previous = VariantRegistry::register(name, other.clone()).unwrap();
+        assert_eq!(previous.get_board(0, 0).unwrap().pieces, game.get_board(0, 0).unwrap().pieces);
+        assert_eq!(VariantRegistry::get(name).unwrap().get_board(0, 0).unwrap().pieces, other.get_board(0, 0).unwrap().pieces);
+    }
+}
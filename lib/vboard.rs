@@ -31,6 +31,7 @@ pub fn empty<'b>(game: &'b Game) -> EmptyVirtualBoardset<'b> {
     EmptyVirtualBoardset::new(game, vec![])
 }
 
+/// A virtual boardset holding all of its boards in a single map; cloning it copies every virtual board, see `RecursiveVirtualBoardset::clone_layer` for a cheaper alternative
 #[derive(Debug, Clone)]
 pub struct SimpleVirtualBoardset<'a> {
     pub game: &'a Game,
@@ -75,6 +76,7 @@ impl<'a> VirtualBoardset<'a> for SimpleVirtualBoardset<'a> {
     }
 }
 
+/// A virtual boardset made of layers: each `push` creates a new layer holding the new boards, which refers to the previous one
 #[derive(Debug, Clone)]
 pub struct RecursiveVirtualBoardset<'a> {
     pub game: &'a Game,
@@ -100,6 +102,8 @@ impl<'a> VirtualBoardset<'a> for RecursiveVirtualBoardset<'a> {
     fn get_board(&'a self, l: i32, t: isize) -> Option<&'a Board> {
         self.game.get_board(l, t).or_else(|| {
             self.virtual_boards.get(&(l, t))
+        }).or_else(|| {
+            self.parent.and_then(|parent| parent.get_board(l, t))
         })
     }
 
@@ -129,6 +133,16 @@ impl<'a> VirtualBoardset<'a> for RecursiveVirtualBoardset<'a> {
     }
 }
 
+impl<'a> RecursiveVirtualBoardset<'a> {
+    /**
+        Returns a shallow copy of the boardset: the game and the parent layers are shared, only the boards of this layer (those given to `push`) are copied.
+        This is what `Clone` does, this method makes it explicit; cloning a `SimpleVirtualBoardset` on the other hand copies every one of its virtual boards.
+    **/
+    pub fn clone_layer(&self) -> Self {
+        self.clone()
+    }
+}
+
 pub struct RecursiveVirtualBoardsetIter<'a> {
     pub rvb: Option<&'a RecursiveVirtualBoardset<'a>>,
     pub iter: std::collections::hash_map::Values<'a, (i32, isize), Board>,
@@ -1,8 +1,6 @@
 #[allow(unused_imports)]
-use chess5dlib::{game::*, moves::*, moveset::*, resolve::*, tree::*, parse::parse};
+use chess5dlib::{game::*, moves::*, moveset::*, resolve::*, tree::*, parse::try_read_and_parse};
 use std::env;
-use std::fs::File;
-use std::io::prelude::*;
 extern crate json;
 
 // TODO: move replay, game analysis, args
@@ -15,12 +13,13 @@ fn main() -> std::io::Result<()> {
     // This is a simple example which will take the 40 most promising movesets, sort them by their score and display the 3 best movesets
     let path = env::args().last().unwrap();
 
-    let mut file = File::open(path)?;
-    let mut contents = String::new();
-
-    file.read_to_string(&mut contents)?;
-
-    let mut game = parse(&contents).expect("Couldn't parse JSON");
+    let mut game = match try_read_and_parse(&path) {
+        Ok(game) => game,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
 
     let virtual_boards: Vec<&Board> = vec![];
 
@@ -48,7 +47,7 @@ fn main() -> std::io::Result<()> {
 
     println!(
         "Turn {}, {} to play: (raw present = {})",
-        ((game.info.present) / 2) + 1,
+        game.full_turn_number(),
         if game.info.active_player {
             "white"
         } else {
@@ -80,7 +79,7 @@ fn main() -> std::io::Result<()> {
     );
     if let Some((best, value)) = best_move {
         println!("Best move:");
-        println!("{:?}: {}", best.0, value);
+        println!("{}: {}", MovesetNotation(&best.0), value);
         for b in &best.1 {
             println!("{}", b);
             println!("({}T{}{})\n", write_timeline(b.l, game.info.even_initial_timelines), b.t / 2 + 1, if b.active_player() {"w"} else {"b"});